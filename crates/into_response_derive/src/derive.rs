@@ -1,13 +1,11 @@
-use proc_macro::TokenStream;
-
 use quote::quote;
-use syn::DeriveInput;
+use syn::{spanned::Spanned, Data, DeriveInput, Fields};
 
 // TODO derive generic types
-pub fn into_response_derive_impl(input: DeriveInput) -> TokenStream {
+pub fn into_response_derive_impl(input: DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
     let name = &input.ident;
 
-    let expanded = quote! {
+    let mut expanded = quote! {
         impl axum::response::IntoResponse for #name {
             fn into_response(self) -> axum::response::Response {
                 let version = env!("CARGO_PKG_VERSION");
@@ -17,5 +15,112 @@ pub fn into_response_derive_impl(input: DeriveInput) -> TokenStream {
         }
     };
 
-    TokenStream::from(expanded)
+    // The response is wrapped in a BaseResponse, so the documented body must be as well
+    if openapi(&input)? {
+        expanded.extend(quote! {
+            impl lib::axum::openapi::ResponseBody for #name {
+                fn body_schema(
+                    generator: &mut lib::axum::openapi::schemars::gen::SchemaGenerator,
+                ) -> lib::axum::openapi::schemars::schema::Schema {
+                    generator.subschema_for::<lib::serde::response::BaseResponse<Self>>()
+                }
+            }
+        });
+    }
+
+    Ok(expanded)
+}
+
+/// Whether the `#[into_response(openapi)]` attribute is present.
+fn openapi(input: &DeriveInput) -> syn::Result<bool> {
+    let mut openapi = false;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("into_response"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("openapi") {
+                openapi = true;
+                Ok(())
+            } else {
+                Err(meta.error("unsupported into_response attribute, expected `openapi`"))
+            }
+        })?;
+    }
+    Ok(openapi)
+}
+
+pub fn serialize_object_derive_impl(input: &DeriveInput) -> syn::Result<proc_macro2::TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    // Only named fields are serialized as an object, which can be flattened into a BaseResponse
+    match &input.data {
+        Data::Struct(data) if matches!(data.fields, Fields::Named(_)) => {}
+        _ => {
+            return Err(syn::Error::new(
+                input.span(),
+                "SerializeObject can only be derived for structs with named fields",
+            ))
+        }
+    }
+
+    Ok(quote! {
+        impl #impl_generics lib::serde::response::SerializeObject for #name #type_generics #where_clause {}
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_into_response_openapi_is_opt_in() {
+        let input: DeriveInput = parse_quote!(
+            struct Count {
+                count: usize,
+            }
+        );
+        let expanded = into_response_derive_impl(input).unwrap().to_string();
+        assert!(!expanded.contains("ResponseBody"));
+
+        let input: DeriveInput = parse_quote!(
+            #[into_response(openapi)]
+            struct Count {
+                count: usize,
+            }
+        );
+        let expanded = into_response_derive_impl(input).unwrap().to_string();
+        assert!(expanded.contains("ResponseBody"));
+    }
+
+    #[test]
+    fn test_into_response_rejects_unknown_attributes() {
+        let input: DeriveInput = parse_quote!(
+            #[into_response(schema)]
+            struct Count {
+                count: usize,
+            }
+        );
+        assert!(into_response_derive_impl(input).is_err());
+    }
+
+    #[test]
+    fn test_serialize_object_rejects_tuple_structs() {
+        let input: DeriveInput = parse_quote!(
+            struct Id(u32);
+        );
+        assert!(serialize_object_derive_impl(&input).is_err());
+    }
+
+    #[test]
+    fn test_serialize_object_rejects_enums() {
+        let input: DeriveInput = parse_quote!(
+            enum Status {
+                Active,
+            }
+        );
+        assert!(serialize_object_derive_impl(&input).is_err());
+    }
 }
@@ -6,8 +6,40 @@ use {
 
 mod derive;
 
-#[proc_macro_derive(IntoResponse)]
+/// Derives `IntoResponse` for a struct, responding with the struct wrapped in a `BaseResponse`.
+/// The struct must also derive `Serialize` and `SerializeObject`.
+///
+/// With `#[into_response(openapi)]`, `ResponseBody` is derived as well, documenting the wrapped body.
+/// This requires the `openapi` feature of `lib`, and the struct to derive `JsonSchema`.
+/// # Example
+/// ```ignore
+/// #[derive(Serialize, SerializeObject, IntoResponse, JsonSchema)]
+/// #[into_response(openapi)]
+/// struct User {
+///     name: String,
+/// }
+/// ```
+#[proc_macro_derive(IntoResponse, attributes(into_response))]
 pub fn into_response_derive(input: TokenStream) -> TokenStream {
     let input = parse_macro_input!(input as DeriveInput);
     derive::into_response_derive_impl(input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
+
+/// Derives `SerializeObject` for a struct with named fields,
+/// which allows it as the body of a `BaseResponse`.
+/// # Example
+/// ```ignore
+/// #[derive(Serialize, SerializeObject)]
+/// struct User {
+///     name: String,
+/// }
+/// ```
+#[proc_macro_derive(SerializeObject)]
+pub fn serialize_object_derive(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    derive::serialize_object_derive_impl(&input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
 }
@@ -0,0 +1,180 @@
+use {
+    deluxe::{ExtractAttributes, Flag},
+    proc_macro2::TokenStream,
+    quote::quote,
+    syn::{
+        ext::IdentExt, spanned::Spanned, Data, DeriveInput, Expr, Fields, GenericArgument,
+        PathArguments, Type,
+    },
+};
+
+#[derive(ExtractAttributes, Default)]
+#[deluxe(attributes(multipart), default)]
+struct FieldAttributes {
+    rename: Option<String>,
+    limit: Option<Expr>,
+    content_type: Option<String>,
+    json: Flag,
+}
+
+enum Cardinality {
+    One,
+    Optional,
+    Many,
+}
+
+pub fn multipart_form_derive_impl(input: &mut DeriveInput) -> deluxe::Result<TokenStream> {
+    let name = &input.ident;
+    if !input.generics.params.is_empty() {
+        return Err(syn::Error::new(
+            input.generics.span(),
+            "MultipartForm cannot be derived for generic types",
+        ));
+    }
+    let Data::Struct(data) = &mut input.data else {
+        return Err(syn::Error::new(input.span(), "Expected a struct"));
+    };
+    let Fields::Named(fields) = &mut data.fields else {
+        return Err(syn::Error::new(
+            data.fields.span(),
+            "Expected a struct with named fields",
+        ));
+    };
+
+    let mut specs = Vec::new();
+    let mut values = Vec::new();
+    for field in fields.named.iter_mut() {
+        let attributes: FieldAttributes = deluxe::extract_attributes(field)?;
+        let ident = field.ident.as_ref().expect("Named fields have identifiers");
+        let form_name = attributes
+            .rename
+            .unwrap_or_else(|| ident.unraw().to_string());
+        let (cardinality, inner) = unwrap_type(&field.ty);
+        let kind = file_kind(inner);
+        if kind.is_some() && attributes.json.is_set() {
+            return Err(syn::Error::new(
+                field.span(),
+                "Files cannot be deserialized as JSON",
+            ));
+        }
+
+        let kind_ident = kind.unwrap_or("Text");
+        let kind_ident = syn::Ident::new(kind_ident, field.ty.span());
+        let limit = attributes
+            .limit
+            .map(|limit| quote! { .limit((#limit) as u64) });
+        let content_type = attributes
+            .content_type
+            .map(|content_type| quote! { .content_type(#content_type) });
+        specs.push(quote! {
+            lib::axum::multipart::FieldSpec::new(
+                #form_name,
+                lib::axum::multipart::FieldKind::#kind_ident,
+            )#limit #content_type
+        });
+
+        let taken = match kind {
+            Some("File") => quote! { form.files(#form_name) },
+            Some(_) => quote! { form.spooled_files(#form_name) },
+            None if attributes.json.is_set() => quote! { form.json::<#inner>(#form_name)? },
+            None => quote! { form.parse::<#inner>(#form_name)? },
+        };
+        let value = match cardinality {
+            Cardinality::One => quote! { lib::axum::multipart::one(#form_name, #taken)? },
+            Cardinality::Optional => {
+                quote! { lib::axum::multipart::optional(#form_name, #taken)? }
+            }
+            Cardinality::Many => taken,
+        };
+        values.push(quote! { #ident: #value });
+    }
+
+    Ok(quote! {
+        #[axum::async_trait]
+        impl<S> axum::extract::FromRequest<S> for #name
+        where
+            S: Send + Sync,
+        {
+            type Rejection = lib::axum::multipart::MultipartFormRejection;
+
+            async fn from_request(
+                req: axum::extract::Request,
+                state: &S,
+            ) -> Result<Self, Self::Rejection> {
+                let specs = [#(#specs),*];
+                let mut form = lib::axum::multipart::FormParts::from_request(req, state, &specs).await?;
+                Ok(Self {
+                    #(#values),*
+                })
+            }
+        }
+    })
+}
+
+/// Splits `Option<T>` and `Vec<T>` into the cardinality and `T`.
+fn unwrap_type(ty: &Type) -> (Cardinality, &Type) {
+    let Type::Path(path) = ty else {
+        return (Cardinality::One, ty);
+    };
+    let Some(segment) = path.path.segments.last() else {
+        return (Cardinality::One, ty);
+    };
+    let cardinality = match segment.ident.to_string().as_str() {
+        "Option" => Cardinality::Optional,
+        "Vec" => Cardinality::Many,
+        _ => return (Cardinality::One, ty),
+    };
+    match &segment.arguments {
+        PathArguments::AngleBracketed(arguments) => match arguments.args.first() {
+            Some(GenericArgument::Type(inner)) if arguments.args.len() == 1 => (cardinality, inner),
+            _ => (Cardinality::One, ty),
+        },
+        _ => (Cardinality::One, ty),
+    }
+}
+
+/// Returns the kind of file the type is, or `None` for text.
+fn file_kind(ty: &Type) -> Option<&'static str> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    match path.path.segments.last()?.ident.to_string().as_str() {
+        "File" => Some("File"),
+        "SpooledFile" => Some("SpooledFile"),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_unwrap_type() {
+        let ty: Type = parse_quote!(Option<File>);
+        let (cardinality, inner) = unwrap_type(&ty);
+        assert!(matches!(cardinality, Cardinality::Optional));
+        assert_eq!(file_kind(inner), Some("File"));
+
+        let ty: Type = parse_quote!(Vec<u32>);
+        let (cardinality, inner) = unwrap_type(&ty);
+        assert!(matches!(cardinality, Cardinality::Many));
+        assert_eq!(file_kind(inner), None);
+
+        let ty: Type = parse_quote!(lib::axum::multipart::SpooledFile);
+        let (cardinality, inner) = unwrap_type(&ty);
+        assert!(matches!(cardinality, Cardinality::One));
+        assert_eq!(file_kind(inner), Some("SpooledFile"));
+    }
+
+    #[test]
+    fn test_rejects_enums() {
+        let mut input: DeriveInput = parse_quote!(
+            enum Form {
+                A,
+            }
+        );
+        assert!(multipart_form_derive_impl(&mut input).is_err());
+    }
+}
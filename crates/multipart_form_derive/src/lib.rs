@@ -0,0 +1,39 @@
+extern crate proc_macro;
+use {
+    proc_macro::TokenStream,
+    syn::{parse_macro_input, DeriveInput},
+};
+
+mod derive;
+
+/// Derives `FromRequest` for a struct of multipart form fields.
+///
+/// Fields of the types `File` and `SpooledFile` are read as files, other fields are read as text
+/// and parsed with `FromStr`. Wrap a field in `Option` to make it optional,
+/// or in `Vec` to accept any number of values.
+/// Fields in the request without a matching struct field are ignored.
+/// # Field Attributes
+/// - rename: String - The name of the field in the form (Optional, defaults to the field name)
+/// - limit: Expr - The maximum size of the field in bytes (Optional, defaults to the max field size
+///   or max file size of the `SpoolConfig`)
+/// - content_type: String - Comma separated content types, like `image/*` (Optional)
+/// - json: Flag - Deserializes text with `serde_json` instead of `FromStr`
+/// # Example
+/// ```ignore
+/// #[derive(MultipartForm)]
+/// struct Upload {
+///     title: String,
+///     #[multipart(rename = "tag")]
+///     tags: Vec<String>,
+///     #[multipart(limit = 10 * 1024 * 1024, content_type = "image/*")]
+///     image: File,
+///     thumbnail: Option<File>,
+/// }
+/// ```
+#[proc_macro_derive(MultipartForm, attributes(multipart))]
+pub fn multipart_form_derive(input: TokenStream) -> TokenStream {
+    let mut input = parse_macro_input!(input as DeriveInput);
+    derive::multipart_form_derive_impl(&mut input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
@@ -0,0 +1,45 @@
+extern crate proc_macro;
+use {
+    proc_macro::TokenStream,
+    syn::{parse_macro_input, ItemFn},
+};
+
+mod named;
+mod route;
+
+macro_rules! route_attribute {
+    ($($method:ident),*) => {
+        $(
+            #[doc = concat!("Registers the function as a handler for `", stringify!($method), "` requests to the path.")]
+            /// See `route` for the arguments.
+            #[proc_macro_attribute]
+            pub fn $method(args: TokenStream, item: TokenStream) -> TokenStream {
+                let args = parse_macro_input!(args as route::Args);
+                let item = parse_macro_input!(item as ItemFn);
+                route::route_impl(stringify!($method), args, item).into()
+            }
+        )*
+    };
+}
+
+route_attribute!(get, post, put, patch, delete, head, options, trace);
+
+/// Declares named routes with a typed URL builder.
+/// Each route is a unit struct with the `NAME` and `PATH` constants,
+/// and a `url` function taking a value for each path parameter, in order.
+/// The path is validated at compile time, like for the route attributes.
+/// # Example
+/// ```ignore
+/// named_routes! {
+///     /// The page of a user.
+///     pub UserShow = "user.show" => "/users/:id";
+/// }
+///
+/// let (router, routes) = RouteTable::record(|| routes!(get UserShow::PATH => show_user as UserShow::NAME));
+/// let url = UserShow::url(1).query("tab", "posts").build(&routes);
+/// ```
+#[proc_macro]
+pub fn named_routes(input: TokenStream) -> TokenStream {
+    let routes = parse_macro_input!(input as named::NamedRoutes);
+    named::named_routes_impl(routes).into()
+}
@@ -0,0 +1,135 @@
+use {
+    crate::route::validate_path,
+    proc_macro2::TokenStream,
+    quote::quote,
+    syn::{
+        parse::{Parse, ParseStream},
+        Attribute, Ident, LitStr, Token, Visibility,
+    },
+};
+
+/// A route declared with `named_routes!`: `#[attr]* vis Ident = "name" => "/path";`.
+pub struct NamedRoute {
+    attrs: Vec<Attribute>,
+    vis: Visibility,
+    ident: Ident,
+    name: LitStr,
+    path: LitStr,
+    params: Vec<Ident>,
+}
+
+impl Parse for NamedRoute {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let attrs = input.call(Attribute::parse_outer)?;
+        let vis = input.parse()?;
+        let ident = input.parse()?;
+        input.parse::<Token![=]>()?;
+        let name = input.parse()?;
+        input.parse::<Token![=>]>()?;
+        let path: LitStr = input.parse()?;
+        input.parse::<Token![;]>()?;
+        let params = params(&path.value())
+            .map_err(|message| syn::Error::new(path.span(), message))?
+            .into_iter()
+            .map(|param| {
+                syn::parse_str(param).unwrap_or_else(|_| Ident::new_raw(param, path.span()))
+            })
+            .collect();
+        Ok(NamedRoute {
+            attrs,
+            vis,
+            ident,
+            name,
+            path,
+            params,
+        })
+    }
+}
+
+/// The routes of a `named_routes!` invocation.
+pub struct NamedRoutes(Vec<NamedRoute>);
+
+impl Parse for NamedRoutes {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let mut routes = Vec::new();
+        while !input.is_empty() {
+            routes.push(input.parse()?);
+        }
+        Ok(NamedRoutes(routes))
+    }
+}
+
+/// Returns the names of the parameters in the path, in order.
+fn params(path: &str) -> Result<Vec<&str>, String> {
+    validate_path(path)?;
+    let params: Vec<_> = path
+        .split('/')
+        .filter_map(|segment| segment.strip_prefix([':', '*']))
+        .collect();
+    if params.contains(&"_") {
+        return Err(format!(
+            "Parameter `_` in `{path}` cannot be used in a named route"
+        ));
+    }
+    Ok(params)
+}
+
+pub fn named_routes_impl(NamedRoutes(routes): NamedRoutes) -> TokenStream {
+    routes
+        .into_iter()
+        .map(|route| {
+            let NamedRoute {
+                attrs,
+                vis,
+                ident,
+                name,
+                path,
+                params,
+            } = route;
+            let param_names = params
+                .iter()
+                .map(|param| LitStr::new(param.to_string().trim_start_matches("r#"), param.span()));
+            quote! {
+                #(#attrs)*
+                #[derive(Debug, Clone, Copy, PartialEq, Eq)]
+                #vis struct #ident;
+
+                impl #ident {
+                    /// The name of the route, for `routes!` and `url_for`.
+                    pub const NAME: &'static str = #name;
+                    /// The path of the route, relative to the router it is nested in.
+                    pub const PATH: &'static str = #path;
+
+                    /// Creates a builder for the URL of the route, with a value for each path parameter.
+                    pub fn url(#(#params: impl ::std::fmt::Display),*) -> lib::axum::url::UrlFor {
+                        lib::axum::url::url_for(Self::NAME)#(.param(#param_names, #params))*
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_params() {
+        assert_eq!(params("/"), Ok(vec![]));
+        assert_eq!(params("/users/:user/files/*path"), Ok(vec!["user", "path"]));
+        assert!(params("/users/:_").is_err());
+        assert!(params("/users/:id/:id").is_err());
+    }
+
+    #[test]
+    fn test_keyword_params() {
+        let routes: NamedRoutes = parse_quote!(
+            pub ByType = "by.type" => "/items/:type";
+        );
+        let expanded = named_routes_impl(routes).to_string();
+        assert!(expanded.contains("r#type"));
+        assert!(expanded.contains("\"type\""));
+    }
+}
@@ -0,0 +1,184 @@
+use {
+    proc_macro2::TokenStream,
+    quote::{format_ident, quote},
+    std::collections::HashSet,
+    syn::{
+        parse::{Parse, ParseStream},
+        Expr, Ident, ItemFn, LitStr, Token, Type,
+    },
+};
+
+/// Arguments of the route attributes: `"/path" (, name = "name")? (, state = Type)? (, layer = expr)*`.
+pub struct Args {
+    path: LitStr,
+    name: Option<LitStr>,
+    state: Option<Type>,
+    layers: Vec<Expr>,
+}
+
+impl Parse for Args {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path: LitStr = input.parse()?;
+        validate_path(&path.value()).map_err(|message| syn::Error::new(path.span(), message))?;
+        let mut args = Args {
+            path,
+            name: None,
+            state: None,
+            layers: Vec::new(),
+        };
+        while !input.is_empty() {
+            input.parse::<Token![,]>()?;
+            if input.is_empty() {
+                break;
+            }
+            let key: Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            match key.to_string().as_str() {
+                "name" if args.name.is_none() => args.name = Some(input.parse()?),
+                "state" if args.state.is_none() => args.state = Some(input.parse()?),
+                "name" | "state" => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        format!("Duplicate {key} argument"),
+                    ))
+                }
+                "layer" => args.layers.push(input.parse()?),
+                _ => {
+                    return Err(syn::Error::new(
+                        key.span(),
+                        "Unknown argument, expected `name`, `state` or `layer`",
+                    ))
+                }
+            }
+        }
+        Ok(args)
+    }
+}
+
+/// Checks that the path starts with a slash, that parameters are named with identifiers
+/// and are unique, and that a wildcard is only used as the last segment.
+pub fn validate_path(path: &str) -> Result<(), String> {
+    if !path.starts_with('/') {
+        return Err(format!("Path `{path}` must start with `/`"));
+    }
+    let segments: Vec<_> = path.split('/').skip(1).collect();
+    let mut names = HashSet::new();
+    for (index, segment) in segments.iter().enumerate() {
+        let Some(name) = segment.strip_prefix([':', '*']) else {
+            if segment.contains([':', '*']) {
+                return Err(format!(
+                    "Segment `{segment}` in `{path}` must start with `:` or `*` to be a parameter"
+                ));
+            }
+            continue;
+        };
+        if segment.starts_with('*') && index != segments.len() - 1 {
+            return Err(format!(
+                "Wildcard `{segment}` in `{path}` must be the last segment"
+            ));
+        }
+        let mut chars = name.chars();
+        let valid = chars
+            .next()
+            .is_some_and(|char| char.is_alphabetic() || char == '_')
+            && chars.all(|char| char.is_alphanumeric() || char == '_');
+        if !valid {
+            return Err(format!(
+                "Parameter `{segment}` in `{path}` must be named with an identifier"
+            ));
+        }
+        if !names.insert(name) {
+            return Err(format!("Parameter `{name}` is used twice in `{path}`"));
+        }
+    }
+    Ok(())
+}
+
+pub fn route_impl(method: &str, args: Args, item: ItemFn) -> TokenStream {
+    let Args {
+        path,
+        name,
+        state,
+        layers,
+    } = args;
+    let handler = &item.sig.ident;
+    let handler_name = handler.to_string();
+    let routing_fn = format_ident!("{method}");
+    let method = method.to_uppercase();
+    let name = name.map_or_else(
+        || quote!(::std::option::Option::None),
+        |name| quote!(::std::option::Option::Some(#name)),
+    );
+    let state = state.map_or_else(|| quote!(()), |state| quote!(#state));
+    // Registering the same method and path twice in a module defines the same constant twice,
+    // which is reported by `cargo check` with the span of the attribute
+    let marker = format_ident!(
+        "__ROUTE_{}_{}",
+        method,
+        path.value()
+            .bytes()
+            .map(|byte| format!("{byte:02x}"))
+            .collect::<String>()
+    );
+
+    quote! {
+        #item
+
+        #[doc(hidden)]
+        #[allow(non_upper_case_globals)]
+        const #marker: () = ();
+
+        const _: () = {
+            fn state() -> ::std::any::TypeId {
+                ::std::any::TypeId::of::<#state>()
+            }
+
+            fn register(router: ::std::boxed::Box<dyn ::std::any::Any>) -> ::std::boxed::Box<dyn ::std::any::Any> {
+                let router = *router
+                    .downcast::<axum::Router<#state>>()
+                    .expect("Router state does not match the route");
+                ::std::boxed::Box::new(router.route(
+                    #path,
+                    axum::routing::#routing_fn(#handler)#(.route_layer(#layers))*,
+                ))
+            }
+
+            lib::axum::registry::inventory::submit! {
+                lib::axum::registry::RouteRegistration::new(#method, #path, #handler_name, #name, state, register)
+            }
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_paths() {
+        for path in [
+            "/",
+            "/users",
+            "/users/:id",
+            "/users/:id/posts/:post_id",
+            "/files/*path",
+        ] {
+            assert_eq!(validate_path(path), Ok(()), "{path}");
+        }
+    }
+
+    #[test]
+    fn test_invalid_paths() {
+        for path in [
+            "users",
+            "/users/:",
+            "/users/:1d",
+            "/users/:id-name",
+            "/users/id:",
+            "/users/:id/:id",
+            "/files/*path/name",
+        ] {
+            assert!(validate_path(path).is_err(), "{path}");
+        }
+    }
+}
@@ -0,0 +1,54 @@
+use lib::axum::idempotency::{
+    IdempotencyRecord, IdempotencyStore, PgIdempotencyStore, StoredResponse,
+};
+use std::time::Duration;
+use test_containers::create_test_containers_pool;
+
+#[cfg(test)]
+pub mod test_containers;
+
+const TTL: Duration = Duration::from_secs(60);
+
+#[tokio::test]
+async fn test_pg_idempotency_store() {
+    let container = create_test_containers_pool().await.unwrap();
+    let store = PgIdempotencyStore::new(container.pool.clone());
+    store.migrate().await.unwrap();
+
+    assert_eq!(store.begin("key", "a", TTL).await.unwrap(), None);
+    assert_eq!(
+        store.begin("key", "a", TTL).await.unwrap(),
+        Some(IdempotencyRecord::InFlight {
+            fingerprint: "a".to_string()
+        })
+    );
+
+    let response = StoredResponse {
+        status: 201,
+        headers: vec![("content-type".to_string(), "text/plain".to_string())],
+        body: b"created".to_vec(),
+    };
+    store.complete("key", &response, TTL).await.unwrap();
+    assert_eq!(
+        store.begin("key", "a", TTL).await.unwrap(),
+        Some(IdempotencyRecord::Completed {
+            fingerprint: "a".to_string(),
+            response
+        })
+    );
+
+    store.abort("key").await.unwrap();
+    assert_eq!(store.begin("key", "b", TTL).await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_pg_idempotency_store_expired() {
+    let container = create_test_containers_pool().await.unwrap();
+    let store = PgIdempotencyStore::new(container.pool.clone());
+    store.migrate().await.unwrap();
+
+    store.begin("key", "a", Duration::ZERO).await.unwrap();
+    assert_eq!(store.begin("key", "b", TTL).await.unwrap(), None);
+    store.begin("other", "a", Duration::ZERO).await.unwrap();
+    assert_eq!(store.delete_expired().await.unwrap(), 1);
+}
@@ -0,0 +1,38 @@
+use lib::axum::session::{PgSessionStore, SessionRecord, SessionStore};
+use std::collections::HashMap;
+use std::time::{Duration, SystemTime};
+use test_containers::create_test_containers_pool;
+
+#[cfg(test)]
+pub mod test_containers;
+
+#[tokio::test]
+async fn test_pg_session_store() {
+    let container = create_test_containers_pool().await.unwrap();
+    let store = PgSessionStore::new(container.pool.clone());
+    store.migrate().await.unwrap();
+
+    let mut data = HashMap::new();
+    data.insert("user".to_string(), "admin".into());
+    let record = SessionRecord::new("id", data, SystemTime::now() + Duration::from_secs(60));
+    store.save(&record).await.unwrap();
+    assert_eq!(
+        store.load("id").await.unwrap().map(|record| record.data),
+        Some(record.data)
+    );
+
+    store.delete("id").await.unwrap();
+    assert_eq!(store.load("id").await.unwrap(), None);
+}
+
+#[tokio::test]
+async fn test_pg_session_store_expired() {
+    let container = create_test_containers_pool().await.unwrap();
+    let store = PgSessionStore::new(container.pool.clone());
+    store.migrate().await.unwrap();
+
+    let record = SessionRecord::new("id", HashMap::new(), SystemTime::now());
+    store.save(&record).await.unwrap();
+    assert_eq!(store.load("id").await.unwrap(), None);
+    assert_eq!(store.delete_expired().await.unwrap(), 1);
+}
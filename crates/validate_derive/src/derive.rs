@@ -0,0 +1,296 @@
+use {
+    deluxe::{ExtractAttributes, Flag, ParseMetaItem},
+    proc_macro2::TokenStream,
+    quote::quote,
+    syn::{
+        ext::IdentExt, meta::ParseNestedMeta, spanned::Spanned, Attribute, Data, DeriveInput, Expr,
+        Fields, LitStr, Token, Type,
+    },
+};
+
+#[derive(ParseMetaItem, Default)]
+#[deluxe(default)]
+struct Bounds {
+    min: Option<Expr>,
+    max: Option<Expr>,
+}
+
+/// Parses `length(min = 1)`, which deluxe only supports for bounds that are not optional.
+mod optional_bounds {
+    use {super::Bounds, deluxe::ParseMetaItem, proc_macro2::Span, syn::parse::ParseStream};
+
+    pub fn parse_meta_item_named(
+        input: ParseStream,
+        name: &str,
+        span: Span,
+    ) -> deluxe::Result<Option<Bounds>> {
+        Bounds::parse_meta_item_named(input, name, span).map(Some)
+    }
+}
+
+#[derive(ExtractAttributes, Default)]
+#[deluxe(attributes(validate), default)]
+struct FieldAttributes {
+    #[deluxe(with = optional_bounds)]
+    length: Option<Bounds>,
+    #[deluxe(with = optional_bounds)]
+    range: Option<Bounds>,
+    regex: Option<LitStr>,
+    email: Flag,
+    url: Flag,
+    nested: Flag,
+    custom: Option<syn::Path>,
+    message: Option<String>,
+    rename: Option<String>,
+}
+
+pub fn validate_derive_impl(input: &mut DeriveInput) -> deluxe::Result<TokenStream> {
+    let name = &input.ident;
+    let (impl_generics, type_generics, where_clause) = input.generics.split_for_impl();
+    let rename_all = serde_name(&input.attrs, "rename_all")?;
+    let Data::Struct(data) = &mut input.data else {
+        return Err(syn::Error::new(input.span(), "Expected a struct"));
+    };
+    let Fields::Named(fields) = &mut data.fields else {
+        return Err(syn::Error::new(
+            data.fields.span(),
+            "Expected a struct with named fields",
+        ));
+    };
+
+    let mut validations = Vec::new();
+    for field in fields.named.iter_mut() {
+        let attributes: FieldAttributes = deluxe::extract_attributes(field)?;
+        let ident = field.ident.as_ref().expect("Named fields have identifiers");
+        // Errors use the name of the field in the request, so serde renames apply as well
+        let path = match (&attributes.rename, serde_name(&field.attrs, "rename")?) {
+            (Some(rename), _) => rename.clone(),
+            (None, Some(rename)) => rename.value(),
+            (None, None) => match &rename_all {
+                Some(rule) => rename_field(&ident.unraw().to_string(), rule)?,
+                None => ident.unraw().to_string(),
+            },
+        };
+        let (error, message) = match &attributes.message {
+            Some(message) => (quote! { _ }, quote! { #message }),
+            None => (quote! { message }, quote! { message }),
+        };
+
+        let mut rules = Vec::new();
+        if let Some(Bounds { min, max }) = &attributes.length {
+            let min = bound(min, quote! { as usize });
+            let max = bound(max, quote! { as usize });
+            rules.push(quote! { lib::axum::validation::rules::length(value, #min, #max) });
+        }
+        if let Some(Bounds { min, max }) = &attributes.range {
+            let (min, max) = (bound(min, quote! {}), bound(max, quote! {}));
+            rules.push(quote! { lib::axum::validation::rules::range(value, #min, #max) });
+        }
+        if let Some(pattern) = &attributes.regex {
+            if let Err(error) = regex::Regex::new(&pattern.value()) {
+                return Err(syn::Error::new(
+                    pattern.span(),
+                    format!("Invalid regex: {error}"),
+                ));
+            }
+            rules.push(quote! {{
+                static REGEX: std::sync::OnceLock<lib::axum::validation::Regex> =
+                    std::sync::OnceLock::new();
+                let regex = REGEX.get_or_init(|| {
+                    lib::axum::validation::Regex::new(#pattern)
+                        .expect(concat!("Invalid regex for the field `", #path, "`"))
+                });
+                lib::axum::validation::rules::regex(value, regex)
+            }});
+        }
+        if attributes.email.is_set() {
+            rules.push(quote! { lib::axum::validation::rules::email(value) });
+        }
+        if attributes.url.is_set() {
+            rules.push(quote! { lib::axum::validation::rules::url(value) });
+        }
+        if let Some(custom) = &attributes.custom {
+            rules.push(quote! { #custom(value) });
+        }
+        let nested = attributes.nested.is_set().then(|| {
+            quote! { errors.merge(#path, lib::axum::validation::Validate::validate(value)); }
+        });
+        if rules.is_empty() && nested.is_none() {
+            continue;
+        }
+
+        let checks = quote! {
+            #(
+                if let Err(#error) = #rules {
+                    errors.add(#path, #message);
+                }
+            )*
+            #nested
+        };
+        validations.push(match is_option(&field.ty) {
+            true => quote! {
+                if let Some(value) = &self.#ident {
+                    #checks
+                }
+            },
+            false => quote! {{
+                let value = &self.#ident;
+                #checks
+            }},
+        });
+    }
+
+    Ok(quote! {
+        impl #impl_generics lib::axum::validation::Validate for #name #type_generics #where_clause {
+            fn validate(&self) -> Result<(), lib::axum::validation::ValidationErrors> {
+                let mut errors = lib::axum::validation::ValidationErrors::new();
+                #(#validations)*
+                errors.into_result()
+            }
+        }
+    })
+}
+
+/// Returns the value of a `#[serde(...)]` attribute like `rename = "..."`, or its `deserialize` value,
+/// since the errors are about the deserialized request.
+fn serde_name(attrs: &[Attribute], key: &str) -> syn::Result<Option<LitStr>> {
+    let mut name = None;
+    for attr in attrs.iter().filter(|attr| attr.path().is_ident("serde")) {
+        attr.parse_nested_meta(|meta| {
+            if !meta.path.is_ident(key) {
+                return skip_meta(meta);
+            }
+            if meta.input.peek(Token![=]) {
+                name = Some(meta.value()?.parse()?);
+                return Ok(());
+            }
+            meta.parse_nested_meta(|meta| {
+                if meta.path.is_ident("deserialize") {
+                    name = Some(meta.value()?.parse()?);
+                    Ok(())
+                } else {
+                    skip_meta(meta)
+                }
+            })
+        })?;
+    }
+    Ok(name)
+}
+
+/// Skips the value of a serde attribute, like `default = "..."` or `bound(...)`.
+fn skip_meta(meta: ParseNestedMeta) -> syn::Result<()> {
+    if meta.input.peek(Token![=]) {
+        meta.value()?.parse::<Expr>()?;
+    } else if meta.input.peek(syn::token::Paren) {
+        meta.parse_nested_meta(skip_meta)?;
+    }
+    Ok(())
+}
+
+/// Renames a snake case field with a serde `rename_all` rule.
+fn rename_field(field: &str, rule: &LitStr) -> syn::Result<String> {
+    let pascal_case = || {
+        field
+            .split('_')
+            .map(|word| {
+                let mut chars = word.chars();
+                chars.next().map_or_else(String::new, |first| {
+                    first.to_ascii_uppercase().to_string() + chars.as_str()
+                })
+            })
+            .collect::<String>()
+    };
+    Ok(match rule.value().as_str() {
+        "lowercase" | "snake_case" => field.to_string(),
+        "UPPERCASE" | "SCREAMING_SNAKE_CASE" => field.to_ascii_uppercase(),
+        "PascalCase" => pascal_case(),
+        "camelCase" => {
+            let pascal = pascal_case();
+            pascal[..1].to_ascii_lowercase() + &pascal[1..]
+        }
+        "kebab-case" => field.replace('_', "-"),
+        "SCREAMING-KEBAB-CASE" => field.to_ascii_uppercase().replace('_', "-"),
+        _ => return Err(syn::Error::new(rule.span(), "Unknown rename_all rule")),
+    })
+}
+
+fn bound(bound: &Option<Expr>, cast: TokenStream) -> TokenStream {
+    match bound {
+        Some(bound) => quote! { Some((#bound) #cast) },
+        None => quote! { None },
+    }
+}
+
+fn is_option(ty: &Type) -> bool {
+    let Type::Path(path) = ty else {
+        return false;
+    };
+    path.path
+        .segments
+        .last()
+        .is_some_and(|segment| segment.ident == "Option")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use syn::parse_quote;
+
+    #[test]
+    fn test_is_option() {
+        assert!(is_option(&parse_quote!(Option<String>)));
+        assert!(is_option(&parse_quote!(std::option::Option<u8>)));
+        assert!(!is_option(&parse_quote!(Vec<Option<u8>>)));
+    }
+
+    #[test]
+    fn test_rejects_invalid_regex() {
+        let mut input: DeriveInput = parse_quote!(
+            struct Form {
+                #[validate(regex = "[a-z")]
+                name: String,
+            }
+        );
+        let error = validate_derive_impl(&mut input).unwrap_err();
+        assert!(error.to_string().starts_with("Invalid regex"));
+    }
+
+    #[test]
+    fn test_serde_names() {
+        let mut input: DeriveInput = parse_quote!(
+            #[serde(rename_all = "camelCase", deny_unknown_fields)]
+            struct Form {
+                #[validate(email)]
+                email_address: String,
+                #[serde(default, rename(serialize = "out", deserialize = "in"))]
+                #[validate(length(min = 1))]
+                renamed: String,
+                #[serde(rename = "user")]
+                #[validate(length(min = 1), rename = "login")]
+                username: String,
+            }
+        );
+        let expanded = validate_derive_impl(&mut input).unwrap().to_string();
+        assert!(expanded.contains("\"emailAddress\""));
+        assert!(expanded.contains("\"in\""));
+        assert!(expanded.contains("\"login\""));
+        assert!(!expanded.contains("\"user\""));
+    }
+
+    #[test]
+    fn test_rename_field() {
+        let rename = |rule: &str| rename_field("user_id", &parse_quote!(#rule)).unwrap();
+        assert_eq!(rename("camelCase"), "userId");
+        assert_eq!(rename("PascalCase"), "UserId");
+        assert_eq!(rename("SCREAMING-KEBAB-CASE"), "USER-ID");
+        assert!(rename_field("id", &parse_quote!("Title Case")).is_err());
+    }
+
+    #[test]
+    fn test_rejects_tuple_structs() {
+        let mut input: DeriveInput = parse_quote!(
+            struct Form(#[validate(email)] String);
+        );
+        assert!(validate_derive_impl(&mut input).is_err());
+    }
+}
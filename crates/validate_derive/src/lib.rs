@@ -0,0 +1,46 @@
+extern crate proc_macro;
+use {
+    proc_macro::TokenStream,
+    syn::{parse_macro_input, DeriveInput},
+};
+
+mod derive;
+
+/// Derives `Validate` for a struct, checking the fields with the rules in their `validate` attributes.
+///
+/// The rules of an `Option` field are only checked if it is `Some`.
+/// All failing rules are returned, with the name of the field as the path.
+/// # Field Attributes
+/// - length(min = Expr, max = Expr) - The number of characters of a string, or elements of a collection (Optional bounds)
+/// - range(min = Expr, max = Expr) - The bounds of a number, or any `PartialOrd` value (Optional bounds)
+/// - regex: String - A pattern the string must match, checked when the macro is expanded
+/// - email: Flag - The string must be an email address
+/// - url: Flag - The string must be an absolute URL
+/// - nested: Flag - Validates the field with its own `Validate` implementation, prefixing the paths of its errors
+/// - custom: Path - A function `fn(&T) -> Result<(), String>` returning the error message
+/// - message: String - Replaces the message of every failing rule of the field (Optional)
+/// - rename: String - The name of the field in the errors (Optional, defaults to the name given by
+///   `#[serde(rename)]` or `#[serde(rename_all)]`, or the field name)
+/// # Example
+/// ```ignore
+/// #[derive(Deserialize, Validate)]
+/// struct CreateUser {
+///     #[validate(length(min = 1, max = 100))]
+///     name: String,
+///     #[validate(email, message = "Invalid email")]
+///     email: String,
+///     #[validate(range(min = 13))]
+///     age: Option<u8>,
+///     #[validate(nested)]
+///     addresses: Vec<Address>,
+///     #[validate(custom = not_admin)]
+///     username: String,
+/// }
+/// ```
+#[proc_macro_derive(Validate, attributes(validate))]
+pub fn validate_derive(input: TokenStream) -> TokenStream {
+    let mut input = parse_macro_input!(input as DeriveInput);
+    derive::validate_derive_impl(&mut input)
+        .unwrap_or_else(|error| error.to_compile_error())
+        .into()
+}
@@ -1,7 +1,8 @@
 use {
+    crate::axum::router::RouteTable,
     axum::{
-        extract::Request, handler::Handler, response::IntoResponse, routing::Route, Router,
-        ServiceExt,
+        extract::Request, handler::Handler, response::IntoResponse, routing::Route, Extension,
+        Router, ServiceExt,
     },
     std::{
         convert::Infallible,
@@ -37,6 +38,7 @@ pub struct AppBuilder {
     cors: Option<CorsLayer>,
     normalize_path: Option<bool>,
     tracing: Option<TraceLayer<HttpMakeClassifier>>,
+    route_table: Option<RouteTable>,
 }
 
 impl AppBuilder {
@@ -106,6 +108,13 @@ impl AppBuilder {
         self
     }
 
+    /// Sets the route table of the router, recorded with `RouteTable::record`.
+    /// The table is added to every route as an `Extension`, and logged on startup.
+    pub fn route_table(mut self, routes: RouteTable) -> Self {
+        self.route_table = Some(routes);
+        self
+    }
+
     /// Sets the cors layer.
     pub fn cors(mut self, cors: CorsLayer) -> Self {
         self.cors = Some(cors);
@@ -129,6 +138,9 @@ impl AppBuilder {
     /// Options used for configuring the listener will be lost.
     pub fn build(self) -> Router {
         let mut app = self.router;
+        if let Some(routes) = self.route_table {
+            app = app.layer(Extension(routes));
+        }
         if let Some(cors) = self.cors {
             app = app.layer(cors);
         }
@@ -148,9 +160,14 @@ impl AppBuilder {
     /// - Cors == None
     /// - Normalize Path == true
     /// - Tracing == Default compact
+    ///
+    /// The routes in the route table are logged on startup.
     pub async fn serve(self) -> io::Result<()> {
         let _ = fmt_trace(); // Allowed to fail
         let listener = self.listener().await?;
+        if let Some(routes) = &self.route_table {
+            routes.log();
+        }
 
         if self.normalize_path.unwrap_or(true) {
             let app = NormalizePathLayer::trim_trailing_slash().layer(self.build());
@@ -200,6 +217,7 @@ mod tests {
                 .socket((Ipv4Addr::LOCALHOST, 8080))
                 .routes([Router::new()])
                 .fallback(|| async { "Fallback" })
+                .route_table(RouteTable::default())
                 .cors(CorsLayer::new())
                 .normalize_path(true)
                 .tracing(TraceLayer::new_for_http())
@@ -0,0 +1,280 @@
+use {
+    super::{
+        layer::Authenticator,
+        principal::{AuthRejection, Principal},
+    },
+    axum::{
+        async_trait,
+        extract::Query,
+        http::{request::Parts, HeaderName},
+    },
+    sha2::{Digest, Sha256},
+    std::{collections::HashMap, fmt, str::FromStr},
+    subtle::ConstantTimeEq,
+};
+
+/// The SHA-256 hash of an API key.
+/// Only the hashes of keys should be stored, so a leaked store does not leak the keys.
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct KeyHash([u8; 32]);
+
+impl KeyHash {
+    /// Hashes the given key.
+    pub fn of(key: impl AsRef<[u8]>) -> Self {
+        Self(Sha256::digest(key.as_ref()).into())
+    }
+
+    /// The raw bytes of the hash.
+    pub fn as_bytes(&self) -> &[u8; 32] {
+        &self.0
+    }
+}
+
+impl fmt::Display for KeyHash {
+    /// Formats the hash as lowercase hex.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.iter().try_for_each(|byte| write!(f, "{byte:02x}"))
+    }
+}
+
+impl fmt::Debug for KeyHash {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "KeyHash({self})")
+    }
+}
+
+/// Error returned when parsing a `KeyHash` from a string that is not 64 hex characters.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Expected 64 hex characters")]
+pub struct ParseKeyHashError;
+
+impl FromStr for KeyHash {
+    type Err = ParseKeyHashError;
+
+    /// Parses a hash formatted as hex, e.g. from a configuration file or a database.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.len() != 64 || !s.is_ascii() {
+            return Err(ParseKeyHashError);
+        }
+        let mut bytes = [0; 32];
+        for (byte, chunk) in bytes.iter_mut().zip(s.as_bytes().chunks(2)) {
+            let hex = std::str::from_utf8(chunk).map_err(|_| ParseKeyHashError)?;
+            *byte = u8::from_str_radix(hex, 16).map_err(|_| ParseKeyHashError)?;
+        }
+        Ok(Self(bytes))
+    }
+}
+
+/// Finds the principal owning an API key.
+///
+/// Implementing the trait requires the `async_trait` macro.
+#[async_trait]
+pub trait ApiKeyStore: Send + Sync + 'static {
+    /// Returns the principal owning the key with the given hash, or `None` if the key is unknown.
+    async fn find(&self, hash: &KeyHash) -> Option<Principal>;
+}
+
+/// An API key store with a fixed set of keys.
+/// # Example
+/// ```
+/// use lib::axum::auth::{KeyHash, MemoryApiKeyStore};
+///
+/// let store = MemoryApiKeyStore::new()
+///     .key(KeyHash::of("my-secret-key"), "grafana", ["metrics"])
+///     .key(
+///         "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08".parse().unwrap(),
+///         "deploy",
+///         ["admin"],
+///     );
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct MemoryApiKeyStore {
+    keys: Vec<(KeyHash, Principal)>,
+}
+
+impl MemoryApiKeyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a key with the given owner and scopes.
+    pub fn key<T: Into<String>>(
+        mut self,
+        hash: KeyHash,
+        id: impl Into<String>,
+        scopes: impl IntoIterator<Item = T>,
+    ) -> Self {
+        self.keys.push((hash, Principal::new(id, scopes)));
+        self
+    }
+}
+
+#[async_trait]
+impl ApiKeyStore for MemoryApiKeyStore {
+    async fn find(&self, hash: &KeyHash) -> Option<Principal> {
+        // Compare against every key, so the time taken does not reveal which key matched
+        self.keys
+            .iter()
+            .fold(None, |found, (key, principal)| {
+                let matches: bool = key.0.ct_eq(&hash.0).into();
+                found.or(matches.then_some(principal))
+            })
+            .cloned()
+    }
+}
+
+/// Where the API key is read from.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum KeySource {
+    Header(HeaderName),
+    Query(String),
+}
+
+/// Authenticates requests using an API key sent in a header or a query parameter.
+/// Reads the `X-Api-Key` header by default. Use with an `AuthLayer`.
+/// # Example
+/// ```
+/// use lib::axum::auth::{ApiKeyAuth, AuthLayer, KeyHash, MemoryApiKeyStore};
+///
+/// let store = MemoryApiKeyStore::new().key(KeyHash::of("secret"), "grafana", ["metrics"]);
+/// let auth = ApiKeyAuth::new(store).query("api_key");
+/// let _router: axum::Router = lib::routes!(get "/metrics" => || async {})
+///     .layer(AuthLayer::new(auth).require_scopes(["metrics"]));
+/// ```
+pub struct ApiKeyAuth<St> {
+    store: St,
+    sources: Vec<KeySource>,
+}
+
+impl<St> ApiKeyAuth<St> {
+    pub fn new(store: St) -> Self {
+        Self {
+            store,
+            sources: vec![KeySource::Header(HeaderName::from_static("x-api-key"))],
+        }
+    }
+
+    /// Reads the key from the given header, instead of `X-Api-Key`.
+    /// Can be combined with `query`, the sources are tried in the order they were added.
+    pub fn header(mut self, name: HeaderName) -> Self {
+        self.sources
+            .retain(|source| !matches!(source, KeySource::Header(_)));
+        self.sources.push(KeySource::Header(name));
+        self
+    }
+
+    /// Also reads the key from the given query parameter.
+    /// Keys in urls may end up in logs, so prefer headers where possible.
+    pub fn query(mut self, name: impl Into<String>) -> Self {
+        self.sources.push(KeySource::Query(name.into()));
+        self
+    }
+
+    fn key(&self, parts: &Parts) -> Option<String> {
+        self.sources.iter().find_map(|source| match source {
+            KeySource::Header(name) => parts
+                .headers
+                .get(name)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string),
+            KeySource::Query(name) => Query::<HashMap<String, String>>::try_from_uri(&parts.uri)
+                .ok()?
+                .0
+                .remove(name),
+        })
+    }
+}
+
+#[async_trait]
+impl<St: ApiKeyStore> Authenticator for ApiKeyAuth<St> {
+    async fn authenticate(&self, parts: &Parts) -> Result<Principal, AuthRejection> {
+        let unauthorized = || AuthRejection::Unauthorized { challenge: None };
+        let key = self.key(parts).ok_or_else(unauthorized)?;
+        self.store
+            .find(&KeyHash::of(key))
+            .await
+            .ok_or_else(unauthorized)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::axum::auth::AuthLayer;
+    use axum::{body::Body, extract::Request, http::StatusCode, Router};
+    use tower::ServiceExt;
+
+    fn store() -> MemoryApiKeyStore {
+        MemoryApiKeyStore::new()
+            .key(KeyHash::of("admin-key"), "admin", ["admin"])
+            .key(KeyHash::of("metrics-key"), "grafana", ["metrics"])
+    }
+
+    fn app() -> Router {
+        crate::routes!(get "/" => |principal: Principal| async move { principal.id }).layer(
+            AuthLayer::new(ApiKeyAuth::new(store()).query("api_key")).require_scopes(["metrics"]),
+        )
+    }
+
+    #[test]
+    fn test_key_hash_hex() {
+        let hash = KeyHash::of("test");
+        assert_eq!(
+            hash.to_string(),
+            "9f86d081884c7d659a2feaa0c55ad015a3bf4f1b2b0b822cd15d6c15b0f00a08"
+        );
+        assert_eq!(hash.to_string().parse(), Ok(hash));
+        assert_eq!("abc".parse::<KeyHash>(), Err(ParseKeyHashError));
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        assert_eq!(
+            store().find(&KeyHash::of("admin-key")).await.map(|p| p.id),
+            Some("admin".to_string())
+        );
+        assert_eq!(store().find(&KeyHash::of("other")).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_layer_header() {
+        let request = Request::builder()
+            .uri("/")
+            .header("x-api-key", "metrics-key")
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_layer_query() {
+        let request = Request::builder()
+            .uri("/?api_key=metrics-key")
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_layer_unknown_key() {
+        let request = Request::builder()
+            .uri("/?api_key=other")
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    }
+
+    #[tokio::test]
+    async fn test_layer_missing_scope() {
+        let request = Request::builder()
+            .uri("/")
+            .header("x-api-key", "admin-key")
+            .body(Body::empty())
+            .unwrap();
+        let response = app().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
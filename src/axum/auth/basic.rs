@@ -0,0 +1,224 @@
+use {
+    super::{
+        layer::Authenticator,
+        principal::{AuthRejection, Principal},
+    },
+    axum::{
+        async_trait,
+        http::{header::AUTHORIZATION, request::Parts, HeaderMap},
+    },
+    base64::{engine::general_purpose::STANDARD, Engine},
+    sha2::{Digest, Sha256},
+    std::collections::HashMap,
+    subtle::ConstantTimeEq,
+};
+
+/// Verifies a username and password, e.g. against a configuration file or a database.
+///
+/// Implementing the trait requires the `async_trait` macro.
+/// Implementations should compare secrets in constant time, e.g. with `constant_time_eq`.
+#[async_trait]
+pub trait CredentialProvider: Send + Sync + 'static {
+    /// Returns the principal for the given credentials, or `None` if they are invalid.
+    async fn verify(&self, username: &str, password: &str) -> Option<Principal>;
+}
+
+/// Compares two secrets in constant time.
+/// The secrets are hashed first, so the time taken does not depend on their lengths either.
+pub fn constant_time_eq(a: impl AsRef<[u8]>, b: impl AsRef<[u8]>) -> bool {
+    Sha256::digest(a.as_ref())
+        .ct_eq(&Sha256::digest(b.as_ref()))
+        .into()
+}
+
+/// A credential provider with a fixed set of users.
+/// # Example
+/// ```
+/// use lib::axum::auth::StaticCredentials;
+///
+/// let credentials = StaticCredentials::new()
+///     .user("admin", "secret", ["admin", "metrics"])
+///     .user("prometheus", "scrape", ["metrics"]);
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct StaticCredentials {
+    users: HashMap<String, (String, Principal)>,
+}
+
+impl StaticCredentials {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a user with the given password and scopes.
+    pub fn user<T: Into<String>>(
+        mut self,
+        username: impl Into<String>,
+        password: impl Into<String>,
+        scopes: impl IntoIterator<Item = T>,
+    ) -> Self {
+        let username = username.into();
+        let principal = Principal::new(username.clone(), scopes);
+        self.users.insert(username, (password.into(), principal));
+        self
+    }
+}
+
+#[async_trait]
+impl CredentialProvider for StaticCredentials {
+    async fn verify(&self, username: &str, password: &str) -> Option<Principal> {
+        let user = self.users.get(username);
+        // Unknown users are still compared, so the time taken doesn't reveal which users exist
+        let expected = user.map_or("", |(expected, _)| expected);
+        let matches = constant_time_eq(expected, password);
+        user.filter(|_| matches)
+            .map(|(_, principal)| principal.clone())
+    }
+}
+
+/// Authenticates requests using HTTP Basic authentication.
+/// Use with an `AuthLayer`.
+pub struct BasicAuth<P> {
+    provider: P,
+    challenge: String,
+}
+
+impl<P> BasicAuth<P> {
+    /// Creates a basic authenticator with the realm "Restricted".
+    pub fn new(provider: P) -> Self {
+        Self::with_realm(provider, "Restricted")
+    }
+
+    /// Creates a basic authenticator with the given realm, shown to users by browsers.
+    pub fn with_realm(provider: P, realm: &str) -> Self {
+        Self {
+            provider,
+            challenge: format!(
+                "Basic realm=\"{}\", charset=\"UTF-8\"",
+                realm.replace('"', "")
+            ),
+        }
+    }
+
+    fn unauthorized(&self) -> AuthRejection {
+        AuthRejection::Unauthorized {
+            challenge: Some(self.challenge.clone()),
+        }
+    }
+}
+
+#[async_trait]
+impl<P: CredentialProvider> Authenticator for BasicAuth<P> {
+    async fn authenticate(&self, parts: &Parts) -> Result<Principal, AuthRejection> {
+        let (username, password) =
+            basic_credentials(&parts.headers).ok_or_else(|| self.unauthorized())?;
+        self.provider
+            .verify(&username, &password)
+            .await
+            .ok_or_else(|| self.unauthorized())
+    }
+}
+
+/// Returns the username and password of an `Authorization: Basic <credentials>` header, if present.
+pub fn basic_credentials(headers: &HeaderMap) -> Option<(String, String)> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, credentials) = value.split_once(' ')?;
+    if !scheme.eq_ignore_ascii_case("basic") {
+        return None;
+    }
+    let decoded = String::from_utf8(STANDARD.decode(credentials.trim()).ok()?).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some((username.to_string(), password.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::axum::auth::AuthLayer;
+    use axum::{
+        body::Body,
+        extract::Request,
+        http::{header::WWW_AUTHENTICATE, HeaderValue, StatusCode},
+        Router,
+    };
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        let credentials = StaticCredentials::new()
+            .user("admin", "secret", ["admin"])
+            .user("viewer", "secret", ["metrics"]);
+        crate::routes!(get "/" => |principal: Principal| async move { principal.id }).layer(
+            AuthLayer::new(BasicAuth::with_realm(credentials, "admin")).require_scopes(["admin"]),
+        )
+    }
+
+    fn request(credentials: Option<&str>) -> Request {
+        let builder = Request::builder().uri("/");
+        match credentials {
+            Some(credentials) => builder.header(
+                AUTHORIZATION,
+                format!("Basic {}", STANDARD.encode(credentials)),
+            ),
+            None => builder,
+        }
+        .body(Body::empty())
+        .unwrap()
+    }
+
+    #[test]
+    fn test_constant_time_eq() {
+        assert!(constant_time_eq("secret", "secret"));
+        assert!(!constant_time_eq("secret", "secrets"));
+    }
+
+    #[test]
+    fn test_basic_credentials() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_static("Basic YWRtaW46czpz"),
+        );
+        assert_eq!(
+            basic_credentials(&headers),
+            Some(("admin".to_string(), "s:s".to_string()))
+        );
+        headers.insert(
+            AUTHORIZATION,
+            HeaderValue::from_static("Bearer YWRtaW46czpz"),
+        );
+        assert_eq!(basic_credentials(&headers), None);
+    }
+
+    #[tokio::test]
+    async fn test_static_credentials() {
+        let credentials = StaticCredentials::new().user("admin", "secret", ["admin"]);
+        assert!(credentials.verify("admin", "secret").await.is_some());
+        assert!(credentials.verify("admin", "wrong").await.is_none());
+        assert!(credentials.verify("other", "secret").await.is_none());
+        assert!(credentials.verify("other", "").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_layer() {
+        let response = app().oneshot(request(Some("admin:secret"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_layer_unauthorized() {
+        for credentials in [None, Some("admin:wrong")] {
+            let response = app().oneshot(request(credentials)).await.unwrap();
+            assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+            assert_eq!(
+                response.headers().get(WWW_AUTHENTICATE).unwrap(),
+                "Basic realm=\"admin\", charset=\"UTF-8\""
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_layer_missing_scope() {
+        let response = app().oneshot(request(Some("viewer:secret"))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
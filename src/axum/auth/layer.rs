@@ -0,0 +1,121 @@
+use {
+    super::principal::{AuthRejection, Principal},
+    axum::{
+        async_trait,
+        extract::Request,
+        http::request::Parts,
+        response::{IntoResponse, Response},
+    },
+    futures_util::future::BoxFuture,
+    std::{
+        sync::Arc,
+        task::{Context, Poll},
+    },
+    tower::{Layer, Service},
+};
+
+/// Authenticates a request, typically by verifying credentials sent in the headers.
+///
+/// Implementing the trait requires the `async_trait` macro.
+#[async_trait]
+pub trait Authenticator: Send + Sync + 'static {
+    async fn authenticate(&self, parts: &Parts) -> Result<Principal, AuthRejection>;
+}
+
+/// Layer that rejects requests which cannot be authenticated, or lack any of the required scopes.
+/// The authenticated `Principal` is available to handlers as an extractor.
+/// # Example
+/// ```
+/// use lib::axum::auth::{AuthLayer, BasicAuth, StaticCredentials};
+///
+/// let credentials = StaticCredentials::new().user("admin", "secret", ["metrics"]);
+/// let layer = AuthLayer::new(BasicAuth::new(credentials)).require_scopes(["metrics"]);
+/// let _router: axum::Router = lib::routes!(get "/metrics" => || async {}).layer(layer);
+/// ```
+pub struct AuthLayer<A> {
+    authenticator: Arc<A>,
+    scopes: Arc<[String]>,
+}
+
+impl<A> AuthLayer<A> {
+    pub fn new(authenticator: A) -> Self {
+        Self {
+            authenticator: Arc::new(authenticator),
+            scopes: Arc::new([]),
+        }
+    }
+
+    /// Requires the principal to have all the given scopes, otherwise the request is forbidden.
+    pub fn require_scopes<T: Into<String>>(mut self, scopes: impl IntoIterator<Item = T>) -> Self {
+        self.scopes = scopes.into_iter().map(Into::into).collect();
+        self
+    }
+}
+
+impl<A> Clone for AuthLayer<A> {
+    fn clone(&self) -> Self {
+        Self {
+            authenticator: self.authenticator.clone(),
+            scopes: self.scopes.clone(),
+        }
+    }
+}
+
+impl<S, A> Layer<S> for AuthLayer<A> {
+    type Service = AuthService<S, A>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        AuthService {
+            inner,
+            layer: self.clone(),
+        }
+    }
+}
+
+/// Service created by the `AuthLayer`.
+pub struct AuthService<S, A> {
+    inner: S,
+    layer: AuthLayer<A>,
+}
+
+impl<S: Clone, A> Clone for AuthService<S, A> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            layer: self.layer.clone(),
+        }
+    }
+}
+
+impl<S, A> Service<Request> for AuthService<S, A>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    A: Authenticator,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer.clone();
+        Box::pin(async move {
+            let (mut parts, body) = req.into_parts();
+            let principal = match layer.authenticator.authenticate(&parts).await {
+                Ok(principal) => principal,
+                Err(rejection) => return Ok(rejection.into_response()),
+            };
+            if let Some(scope) = layer.scopes.iter().find(|s| !principal.has_scope(s)) {
+                return Ok(AuthRejection::MissingScope(scope.clone()).into_response());
+            }
+            parts.extensions.insert(principal);
+            inner.call(Request::from_parts(parts, body)).await
+        })
+    }
+}
@@ -0,0 +1,9 @@
+pub mod api_key;
+pub mod basic;
+pub mod layer;
+pub mod principal;
+
+pub use api_key::{ApiKeyAuth, ApiKeyStore, KeyHash, MemoryApiKeyStore};
+pub use basic::{BasicAuth, CredentialProvider, StaticCredentials};
+pub use layer::{AuthLayer, Authenticator};
+pub use principal::{AuthRejection, Principal};
@@ -0,0 +1,126 @@
+use {
+    axum::{
+        async_trait,
+        extract::FromRequestParts,
+        http::{header::WWW_AUTHENTICATE, request::Parts, HeaderValue, StatusCode},
+        response::{IntoResponse, Response},
+        Json,
+    },
+    serde_json::json,
+    std::collections::HashSet,
+    thiserror::Error,
+};
+
+/// An authenticated user or client, and the scopes it has been granted.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Principal {
+    pub id: String,
+    pub scopes: HashSet<String>,
+}
+
+impl Principal {
+    /// Creates a new principal with the given id and scopes.
+    pub fn new<T: Into<String>>(
+        id: impl Into<String>,
+        scopes: impl IntoIterator<Item = T>,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            scopes: scopes.into_iter().map(Into::into).collect(),
+        }
+    }
+
+    /// Returns true if the principal has been granted the given scope.
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scopes.contains(scope)
+    }
+}
+
+/// Rejection type for the authentication layers and the `Principal` extractor.
+/// Responds with a JSON body containing the error message.
+#[derive(Debug, Error)]
+pub enum AuthRejection {
+    /// The request has no, or invalid, credentials.
+    /// The challenge is sent in the `WWW-Authenticate` header.
+    #[error("Missing or invalid credentials")]
+    Unauthorized { challenge: Option<String> },
+    #[error("Missing scope: {0}")]
+    MissingScope(String),
+    #[error("Authentication layer is missing")]
+    MissingLayer,
+}
+
+impl IntoResponse for AuthRejection {
+    fn into_response(self) -> Response {
+        let body = Json(json!({ "error": self.to_string() }));
+        match self {
+            AuthRejection::Unauthorized { challenge } => {
+                let mut response = (StatusCode::UNAUTHORIZED, body).into_response();
+                if let Some(value) = challenge.and_then(|c| HeaderValue::from_str(&c).ok()) {
+                    response.headers_mut().insert(WWW_AUTHENTICATE, value);
+                }
+                response
+            }
+            AuthRejection::MissingScope(_) => (StatusCode::FORBIDDEN, body).into_response(),
+            AuthRejection::MissingLayer => {
+                (StatusCode::INTERNAL_SERVER_ERROR, body).into_response()
+            }
+        }
+    }
+}
+
+/// Extractor for the principal authenticated by an `AuthLayer`.
+/// # Example
+/// ```
+/// use lib::axum::auth::Principal;
+///
+/// async fn whoami(principal: Principal) -> String {
+///     principal.id
+/// }
+/// ```
+#[async_trait]
+impl<S> FromRequestParts<S> for Principal
+where
+    S: Send + Sync,
+{
+    type Rejection = AuthRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Principal>()
+            .cloned()
+            .ok_or(AuthRejection::MissingLayer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_has_scope() {
+        let principal = Principal::new("admin", ["metrics:read"]);
+        assert!(principal.has_scope("metrics:read"));
+        assert!(!principal.has_scope("metrics:write"));
+    }
+
+    #[test]
+    fn test_unauthorized_response() {
+        let response = AuthRejection::Unauthorized {
+            challenge: Some("Basic realm=\"admin\"".to_string()),
+        }
+        .into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            response.headers().get(WWW_AUTHENTICATE).unwrap(),
+            "Basic realm=\"admin\""
+        );
+    }
+
+    #[test]
+    fn test_missing_scope_response() {
+        let response = AuthRejection::MissingScope("admin".to_string()).into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+}
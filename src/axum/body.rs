@@ -0,0 +1,51 @@
+use {
+    axum::body::{Body, Bytes},
+    futures_util::{stream, StreamExt},
+};
+
+/// Reads the body into memory, if it is not larger than the limit.
+/// Larger bodies, and bodies failing to be read, are returned as an equivalent body,
+/// so a response can still be passed through unchanged.
+pub(crate) async fn buffer(body: Body, limit: usize) -> Result<Bytes, Body> {
+    let mut stream = body.into_data_stream();
+    let mut buffer = Vec::new();
+    while let Some(chunk) = stream.next().await {
+        let chunk = match chunk {
+            Ok(chunk) => chunk,
+            Err(error) => {
+                let read = stream::iter([Ok(Bytes::from(buffer)), Err(error)]);
+                return Err(Body::from_stream(read));
+            }
+        };
+        buffer.extend_from_slice(&chunk);
+        if buffer.len() > limit {
+            let read = stream::once(async { Ok(Bytes::from(buffer)) });
+            return Err(Body::from_stream(read.chain(stream)));
+        }
+    }
+    Ok(Bytes::from(buffer))
+}
+
+#[cfg(test)]
+mod tests {
+    use {super::*, axum::body::to_bytes};
+
+    fn chunked(chunks: &'static [&'static str]) -> Body {
+        Body::from_stream(stream::iter(
+            chunks.iter().map(|chunk| Ok::<_, axum::Error>(*chunk)),
+        ))
+    }
+
+    #[tokio::test]
+    async fn test_buffer_within_limit() {
+        let bytes = buffer(chunked(&["ab", "cd"]), 4).await.unwrap();
+        assert_eq!(bytes, "abcd");
+    }
+
+    #[tokio::test]
+    async fn test_buffer_over_limit_keeps_body() {
+        let body = buffer(chunked(&["ab", "cd", "ef"]), 3).await.unwrap_err();
+        let bytes = to_bytes(body, usize::MAX).await.unwrap();
+        assert_eq!(bytes, "abcdef");
+    }
+}
@@ -0,0 +1,669 @@
+use {
+    crate::axum::body::buffer,
+    axum::{
+        async_trait,
+        body::{Body, Bytes, HttpBody},
+        extract::{FromRequestParts, OriginalUri, Request},
+        http::{
+            header::{AGE, AUTHORIZATION, CACHE_CONTROL, COOKIE, SET_COOKIE, VARY},
+            request::Parts,
+            HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+        },
+        response::{IntoResponse, Response},
+        Json,
+    },
+    futures_util::future::BoxFuture,
+    lru::LruCache,
+    serde_json::json,
+    std::{
+        num::NonZeroUsize,
+        sync::{Arc, Mutex},
+        task::{Context, Poll},
+        time::{Duration, Instant},
+    },
+    thiserror::Error,
+    tower::{Layer, Service},
+};
+
+/// The header telling whether a response was served from the cache, with `HIT` or `MISS`.
+pub const X_CACHE: HeaderName = HeaderName::from_static("x-cache");
+
+/// The maximum number of responses differing in their `Vary` headers stored for a key.
+const MAX_VARIANTS: usize = 8;
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+struct CacheKey {
+    method: Method,
+    path: String,
+    query: Option<String>,
+    headers: Vec<Option<HeaderValue>>,
+}
+
+#[derive(Debug, Clone)]
+struct CachedResponse {
+    status: StatusCode,
+    headers: HeaderMap,
+    body: Bytes,
+    /// The request headers named by `Vary`, with the values the response was stored for.
+    vary: Vec<(HeaderName, Option<HeaderValue>)>,
+    /// Whether the response may be served to requests with credentials.
+    public: bool,
+    stored_at: Instant,
+    expires_at: Instant,
+}
+
+impl CachedResponse {
+    fn matches(&self, headers: &HeaderMap, credentials: bool) -> bool {
+        (self.public || !credentials)
+            && self
+                .vary
+                .iter()
+                .all(|(name, value)| headers.get(name) == value.as_ref())
+    }
+
+    fn to_response(&self, now: Instant) -> Response {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() = self.status;
+        *response.headers_mut() = self.headers.clone();
+        let headers = response.headers_mut();
+        headers.insert(
+            AGE,
+            HeaderValue::from(now.duration_since(self.stored_at).as_secs()),
+        );
+        headers.insert(X_CACHE, HeaderValue::from_static("HIT"));
+        response
+    }
+}
+
+/// A size-bounded, least recently used, cache of responses, shared by the `CacheLayer`.
+///
+/// Responses are stored by the full path of the request, also inside nested routers.
+/// Each key holds up to 8 responses differing in the request headers named by `Vary`.
+///
+/// The cache is a cheap handle that can be cloned, and is available to handlers as an extractor,
+/// so they can invalidate entries after writes.
+/// # Example
+/// ```
+/// use lib::axum::cache::ResponseCache;
+///
+/// async fn update_user(cache: ResponseCache) {
+///     // Update the user...
+///     cache.invalidate("/users");
+///     cache.invalidate_prefix("/users/");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct ResponseCache {
+    entries: Arc<Mutex<LruCache<CacheKey, Vec<CachedResponse>>>>,
+}
+
+impl ResponseCache {
+    /// Creates a cache holding responses for at most `capacity` keys.
+    /// # Panics
+    /// If the capacity is zero.
+    pub fn new(capacity: usize) -> Self {
+        let capacity = NonZeroUsize::new(capacity).expect("Cache capacity must not be zero");
+        Self {
+            entries: Arc::new(Mutex::new(LruCache::new(capacity))),
+        }
+    }
+
+    /// Removes all responses for the path, regardless of method, query and headers.
+    /// The path is the full path of the request, including the prefixes of nested routers.
+    pub fn invalidate(&self, path: &str) {
+        self.retain(|key| key.path != path);
+    }
+
+    /// Removes all responses for paths starting with the prefix.
+    pub fn invalidate_prefix(&self, prefix: &str) {
+        self.retain(|key| !key.path.starts_with(prefix));
+    }
+
+    /// Removes all responses.
+    pub fn clear(&self) {
+        self.lock().clear();
+    }
+
+    /// Returns the number of stored responses, including expired responses not yet evicted.
+    pub fn len(&self) -> usize {
+        self.lock().iter().map(|(_, variants)| variants.len()).sum()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    fn retain(&self, f: impl Fn(&CacheKey) -> bool) {
+        let mut entries = self.lock();
+        let removed: Vec<_> = entries
+            .iter()
+            .map(|(key, _)| key)
+            .filter(|key| !f(key))
+            .cloned()
+            .collect();
+        for key in removed {
+            entries.pop(&key);
+        }
+    }
+
+    fn get(
+        &self,
+        key: &CacheKey,
+        headers: &HeaderMap,
+        credentials: bool,
+        max_age: Option<u64>,
+    ) -> Option<Response> {
+        let mut entries = self.lock();
+        let now = Instant::now();
+        let variants = entries.get_mut(key)?;
+        variants.retain(|variant| variant.expires_at > now);
+        if variants.is_empty() {
+            entries.pop(key);
+            return None;
+        }
+        let entry = variants
+            .iter()
+            .find(|variant| variant.matches(headers, credentials))?;
+        if max_age.is_some_and(|max_age| now.duration_since(entry.stored_at).as_secs() > max_age) {
+            return None;
+        }
+        Some(entry.to_response(now))
+    }
+
+    /// Stores the response, replacing the response stored for the same `Vary` header values.
+    fn put(&self, key: CacheKey, response: CachedResponse) {
+        let mut entries = self.lock();
+        let Some(variants) = entries.get_mut(&key) else {
+            entries.put(key, vec![response]);
+            return;
+        };
+        variants.retain(|variant| variant.vary != response.vary);
+        if variants.len() >= MAX_VARIANTS {
+            variants.remove(0);
+        }
+        variants.push(response);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, LruCache<CacheKey, Vec<CachedResponse>>> {
+        self.entries.lock().expect("Response cache lock poisoned")
+    }
+}
+
+/// Rejection type for the `ResponseCache` extractor.
+#[derive(Debug, Error)]
+pub enum CacheRejection {
+    #[error("Cache layer is missing")]
+    MissingLayer,
+}
+
+impl IntoResponse for CacheRejection {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::INTERNAL_SERVER_ERROR,
+            Json(json!({ "error": self.to_string() })),
+        )
+            .into_response()
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ResponseCache
+where
+    S: Send + Sync,
+{
+    type Rejection = CacheRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<ResponseCache>()
+            .cloned()
+            .ok_or(CacheRejection::MissingLayer)
+    }
+}
+
+/// The `Cache-Control` directives relevant for a shared cache.
+#[derive(Debug, Default, PartialEq, Eq)]
+struct CacheControl {
+    no_store: bool,
+    no_cache: bool,
+    private: bool,
+    public: bool,
+    max_age: Option<u64>,
+    s_maxage: Option<u64>,
+}
+
+impl CacheControl {
+    fn from_headers(headers: &HeaderMap) -> Self {
+        let mut cache_control = Self::default();
+        let directives = headers
+            .get_all(CACHE_CONTROL)
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','));
+        for directive in directives {
+            let (name, value) = match directive.split_once('=') {
+                Some((name, value)) => (name.trim(), Some(value.trim().trim_matches('"'))),
+                None => (directive.trim(), None),
+            };
+            let seconds = value.and_then(|value| value.parse().ok());
+            match name.to_ascii_lowercase().as_str() {
+                "no-store" => cache_control.no_store = true,
+                "no-cache" => cache_control.no_cache = true,
+                "private" => cache_control.private = true,
+                "public" => cache_control.public = true,
+                "max-age" => cache_control.max_age = seconds,
+                "s-maxage" => cache_control.s_maxage = seconds,
+                _ => {}
+            }
+        }
+        cache_control
+    }
+}
+
+#[derive(Clone)]
+struct CacheConfig {
+    cache: ResponseCache,
+    ttl: Duration,
+    query: bool,
+    headers: Vec<HeaderName>,
+    body_limit: usize,
+}
+
+/// Layer caching successful `GET` responses in a `ResponseCache`.
+///
+/// Responses are keyed by method, path, query and the selected request headers.
+/// The time to live of a response is taken from its `s-maxage` or `max-age` directive, if present.
+/// Responses with `no-store`, `no-cache` or `private` directives, setting cookies,
+/// or with `Vary: *`, are not cached. Other `Vary` headers are honoured.
+/// Requests with `Authorization` or `Cookie` headers only share responses
+/// marked with `public` or `s-maxage`.
+/// Requests with `no-store` bypass the cache, and requests with `no-cache` or a `max-age`
+/// which the cached response is older than, are forwarded and refresh the cache.
+/// Served responses have an `Age` header, and an `X-Cache` header telling whether it was a hit.
+/// # Default Options
+/// - Time to live == 60 seconds
+/// - Query == true
+/// - Headers == none
+/// - Body limit == 1 MB
+/// # Example
+/// ```
+/// use lib::axum::cache::{CacheLayer, ResponseCache};
+/// use std::time::Duration;
+///
+/// let layer = CacheLayer::new(ResponseCache::new(1000))
+///     .ttl(Duration::from_secs(30))
+///     .headers([axum::http::header::ACCEPT_LANGUAGE]);
+/// let _router: axum::Router = lib::routes!(get "/users" => || async {}).layer(layer);
+/// ```
+#[derive(Clone)]
+pub struct CacheLayer {
+    config: Arc<CacheConfig>,
+}
+
+impl CacheLayer {
+    pub fn new(cache: ResponseCache) -> Self {
+        Self {
+            config: Arc::new(CacheConfig {
+                cache,
+                ttl: Duration::from_secs(60),
+                query: true,
+                headers: Vec::new(),
+                body_limit: 1024 * 1024,
+            }),
+        }
+    }
+
+    fn config(mut self, f: impl FnOnce(&mut CacheConfig)) -> Self {
+        f(Arc::make_mut(&mut self.config));
+        self
+    }
+
+    /// Sets how long responses without a `max-age` directive are cached.
+    pub fn ttl(self, ttl: Duration) -> Self {
+        self.config(|config| config.ttl = ttl)
+    }
+
+    /// Sets whether the query is part of the cache key.
+    pub fn query(self, query: bool) -> Self {
+        self.config(|config| config.query = query)
+    }
+
+    /// Sets the request headers that are part of the cache key.
+    pub fn headers(self, headers: impl IntoIterator<Item = HeaderName>) -> Self {
+        self.config(|config| config.headers = headers.into_iter().collect())
+    }
+
+    /// Sets the maximum size of cached bodies. Larger responses are passed through.
+    pub fn body_limit(self, limit: usize) -> Self {
+        self.config(|config| config.body_limit = limit)
+    }
+}
+
+impl<S> Layer<S> for CacheLayer {
+    type Service = CacheService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CacheService {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// Service created by the `CacheLayer`.
+#[derive(Clone)]
+pub struct CacheService<S> {
+    inner: S,
+    config: Arc<CacheConfig>,
+}
+
+impl<S> Service<Request> for CacheService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let config = self.config.clone();
+        req.extensions_mut().insert(config.cache.clone());
+        Box::pin(async move {
+            let request_control = CacheControl::from_headers(req.headers());
+            if req.method() != Method::GET || request_control.no_store {
+                return inner.call(req).await;
+            }
+            // Nested routers strip their prefix from the URI, but not from the original URI
+            let uri = match req.extensions().get::<OriginalUri>() {
+                Some(OriginalUri(uri)) => uri,
+                None => req.uri(),
+            };
+            let key = CacheKey {
+                method: req.method().clone(),
+                path: uri.path().to_string(),
+                query: config
+                    .query
+                    .then(|| uri.query().map(str::to_string))
+                    .flatten(),
+                headers: config
+                    .headers
+                    .iter()
+                    .map(|name| req.headers().get(name).cloned())
+                    .collect(),
+            };
+            let headers = req.headers().clone();
+            let credentials = headers.contains_key(AUTHORIZATION) || headers.contains_key(COOKIE);
+            if !request_control.no_cache {
+                let response =
+                    config
+                        .cache
+                        .get(&key, &headers, credentials, request_control.max_age);
+                if let Some(response) = response {
+                    return Ok(response);
+                }
+            }
+
+            let mut response = inner.call(req).await?;
+            response
+                .headers_mut()
+                .insert(X_CACHE, HeaderValue::from_static("MISS"));
+            let response_control = CacheControl::from_headers(response.headers());
+            let public = response_control.public || response_control.s_maxage.is_some();
+            let vary = vary(response.headers(), &headers);
+            let cacheable = response.status() == StatusCode::OK
+                && !response_control.no_store
+                && !response_control.no_cache
+                && !response_control.private
+                && (public || !credentials)
+                && !response.headers().contains_key(SET_COOKIE)
+                && vary.is_some()
+                && response
+                    .body()
+                    .size_hint()
+                    .upper()
+                    .is_some_and(|size| size <= config.body_limit as u64);
+            let ttl = response_control
+                .s_maxage
+                .or(response_control.max_age)
+                .map_or(config.ttl, Duration::from_secs);
+            if !cacheable || ttl.is_zero() {
+                return Ok(response);
+            }
+
+            let (parts, body) = response.into_parts();
+            let body = match buffer(body, config.body_limit).await {
+                Ok(body) => body,
+                Err(body) => return Ok(Response::from_parts(parts, body)),
+            };
+            let now = Instant::now();
+            config.cache.put(
+                key,
+                CachedResponse {
+                    status: parts.status,
+                    headers: parts.headers.clone(),
+                    body: body.clone(),
+                    vary: vary.unwrap_or_default(),
+                    public,
+                    stored_at: now,
+                    expires_at: now + ttl,
+                },
+            );
+            Ok(Response::from_parts(parts, Body::from(body)))
+        })
+    }
+}
+
+/// The request headers named by the `Vary` header of the response, or `None` for `Vary: *`.
+fn vary(
+    response: &HeaderMap,
+    request: &HeaderMap,
+) -> Option<Vec<(HeaderName, Option<HeaderValue>)>> {
+    let mut vary = Vec::new();
+    let names = response
+        .get_all(VARY)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .filter(|name| !name.is_empty());
+    for name in names {
+        if name == "*" {
+            return None;
+        }
+        if let Ok(name) = HeaderName::try_from(name) {
+            let value = request.get(&name).cloned();
+            vary.push((name, value));
+        }
+    }
+    Some(vary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::to_bytes, http::header::ACCEPT_LANGUAGE, routing::post, Router};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tower::ServiceExt;
+
+    fn app(cache: ResponseCache, counter: Arc<AtomicUsize>) -> Router {
+        let get_counter = counter.clone();
+        crate::routes!(
+            get "/users" => move || async move {
+                get_counter.fetch_add(1, Ordering::SeqCst).to_string()
+            },
+            get "/private" => || async { ([(CACHE_CONTROL, "private")], "secret") },
+            get "/expired" => || async { ([(CACHE_CONTROL, "max-age=0")], "old") },
+            get "/public" => || async { ([(CACHE_CONTROL, "public")], "shared") },
+            get "/vary" => |headers: HeaderMap| async move {
+                let language = headers.get(ACCEPT_LANGUAGE).map(|value| value.as_bytes().to_vec());
+                ([(VARY, "Accept-Language")], language.unwrap_or_default())
+            },
+            get "/vary-all" => || async { ([(VARY, "*")], "any") },
+            get "/large" => || async { "a".repeat(64) }
+        )
+        .route(
+            "/users",
+            post(|cache: ResponseCache| async move { cache.invalidate("/users") }),
+        )
+        .layer(CacheLayer::new(cache).body_limit(32))
+    }
+
+    async fn get(app: &Router, uri: &str, headers: &[(HeaderName, &str)]) -> Response {
+        let mut builder = Request::builder().uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(name, *value);
+        }
+        app.clone()
+            .oneshot(builder.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_cache_control() {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            CACHE_CONTROL,
+            HeaderValue::from_static("No-Cache, max-age=\"10\", s-maxage=20"),
+        );
+        assert_eq!(
+            CacheControl::from_headers(&headers),
+            CacheControl {
+                no_cache: true,
+                max_age: Some(10),
+                s_maxage: Some(20),
+                ..Default::default()
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn test_cache_hit() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let app = app(ResponseCache::new(10), counter.clone());
+        let miss = get(&app, "/users", &[]).await;
+        assert_eq!(miss.headers().get(X_CACHE).unwrap(), "MISS");
+        let hit = get(&app, "/users", &[]).await;
+        assert_eq!(hit.headers().get(X_CACHE).unwrap(), "HIT");
+        assert_eq!(hit.headers().get(AGE).unwrap(), "0");
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+
+        get(&app, "/users?page=2", &[]).await;
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_request_directives() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let app = app(ResponseCache::new(10), counter.clone());
+        get(&app, "/users", &[]).await;
+        get(&app, "/users", &[(CACHE_CONTROL, "no-cache")]).await;
+        get(&app, "/users", &[(CACHE_CONTROL, "no-store")]).await;
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+        let hit = get(&app, "/users", &[(CACHE_CONTROL, "max-age=60")]).await;
+        assert_eq!(hit.headers().get(X_CACHE).unwrap(), "HIT");
+    }
+
+    #[tokio::test]
+    async fn test_response_directives() {
+        let cache = ResponseCache::new(10);
+        let app = app(cache.clone(), Arc::new(AtomicUsize::new(0)));
+        get(&app, "/private", &[]).await;
+        get(&app, "/expired", &[]).await;
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_invalidate() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let cache = ResponseCache::new(10);
+        let app = app(cache.clone(), counter.clone());
+        get(&app, "/users", &[]).await;
+        let request = Request::post("/users").body(Body::empty()).unwrap();
+        app.clone().oneshot(request).await.unwrap();
+        assert!(cache.is_empty());
+        get(&app, "/users", &[]).await;
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_invalidate_nested() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let cache = ResponseCache::new(10);
+        let app = Router::new().nest("/api", app(cache.clone(), counter.clone()));
+        get(&app, "/api/users", &[]).await;
+        cache.invalidate("/users");
+        assert_eq!(cache.len(), 1);
+        cache.invalidate("/api/users");
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_lru_eviction() {
+        let cache = ResponseCache::new(1);
+        let app = app(cache.clone(), Arc::new(AtomicUsize::new(0)));
+        get(&app, "/users", &[]).await;
+        get(&app, "/users?page=2", &[]).await;
+        assert_eq!(cache.len(), 1);
+        let response = get(&app, "/users", &[]).await;
+        assert_eq!(response.headers().get(X_CACHE).unwrap(), "MISS");
+    }
+
+    #[tokio::test]
+    async fn test_credentials() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let cache = ResponseCache::new(10);
+        let app = app(cache.clone(), counter.clone());
+        get(&app, "/users", &[(AUTHORIZATION, "Bearer a")]).await;
+        assert!(cache.is_empty());
+        get(&app, "/users", &[]).await;
+        let response = get(&app, "/users", &[(COOKIE, "session=a")]).await;
+        assert_eq!(response.headers().get(X_CACHE).unwrap(), "MISS");
+        assert_eq!(counter.load(Ordering::SeqCst), 3);
+
+        get(&app, "/public", &[(AUTHORIZATION, "Bearer a")]).await;
+        let response = get(&app, "/public", &[(AUTHORIZATION, "Bearer b")]).await;
+        assert_eq!(response.headers().get(X_CACHE).unwrap(), "HIT");
+    }
+
+    #[tokio::test]
+    async fn test_vary() {
+        let cache = ResponseCache::new(10);
+        let app = app(cache.clone(), Arc::new(AtomicUsize::new(0)));
+        get(&app, "/vary", &[(ACCEPT_LANGUAGE, "fr")]).await;
+        let response = get(&app, "/vary", &[(ACCEPT_LANGUAGE, "de")]).await;
+        assert_eq!(response.headers().get(X_CACHE).unwrap(), "MISS");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "de");
+        let response = get(&app, "/vary", &[(ACCEPT_LANGUAGE, "de")]).await;
+        assert_eq!(response.headers().get(X_CACHE).unwrap(), "HIT");
+        let response = get(&app, "/vary", &[(ACCEPT_LANGUAGE, "fr")]).await;
+        assert_eq!(response.headers().get(X_CACHE).unwrap(), "HIT");
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "fr");
+        assert_eq!(cache.len(), 2);
+
+        cache.clear();
+        get(&app, "/vary-all", &[]).await;
+        assert!(cache.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_large_body() {
+        let cache = ResponseCache::new(10);
+        let app = app(cache.clone(), Arc::new(AtomicUsize::new(0)));
+        let response = get(&app, "/large", &[]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, "a".repeat(64));
+        assert!(cache.is_empty());
+    }
+}
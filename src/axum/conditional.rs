@@ -0,0 +1,557 @@
+use {
+    crate::axum::body::buffer,
+    axum::{
+        async_trait,
+        body::{Body, HttpBody},
+        extract::{FromRequestParts, Request},
+        http::{
+            header::{
+                CACHE_CONTROL, CONTENT_LOCATION, DATE, ETAG, EXPIRES, IF_MATCH, IF_MODIFIED_SINCE,
+                IF_NONE_MATCH, LAST_MODIFIED, VARY,
+            },
+            request::Parts,
+            HeaderMap, HeaderValue, Method, StatusCode,
+        },
+        response::{IntoResponse, IntoResponseParts, Response, ResponseParts},
+        Json,
+    },
+    futures_util::future::BoxFuture,
+    serde::Serialize,
+    serde_json::json,
+    sha2::{Digest, Sha256},
+    std::{
+        convert::Infallible,
+        fmt::{self, Display, Formatter},
+        fs::Metadata,
+        str::FromStr,
+        task::{Context, Poll},
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    thiserror::Error,
+    tower::{Layer, Service},
+};
+
+/// An entity tag, identifying a version of a resource.
+///
+/// Strong tags change whenever the representation changes, and are required for `If-Match`.
+/// Weak tags only change when the meaning of the representation changes.
+/// # Example
+/// ```
+/// use lib::axum::conditional::ETag;
+///
+/// let etag = ETag::from_bytes(b"Hello, World!");
+/// assert!(!etag.is_weak());
+/// assert_eq!(ETag::weak("1").to_string(), "W/\"1\"");
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct ETag {
+    tag: String,
+    weak: bool,
+}
+
+impl ETag {
+    /// Creates a strong tag. The tag must not contain double quotes.
+    pub fn strong(tag: impl Into<String>) -> Self {
+        Self {
+            tag: tag.into(),
+            weak: false,
+        }
+    }
+
+    /// Creates a weak tag. The tag must not contain double quotes.
+    pub fn weak(tag: impl Into<String>) -> Self {
+        Self {
+            tag: tag.into(),
+            weak: true,
+        }
+    }
+
+    /// Creates a strong tag from a hash of the bytes.
+    pub fn from_bytes(bytes: impl AsRef<[u8]>) -> Self {
+        let digest = Sha256::digest(bytes);
+        Self::strong(
+            digest[..16]
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect::<String>(),
+        )
+    }
+
+    /// Creates a strong tag from a hash of the value serialized as JSON.
+    /// Matches the tag computed by the `ConditionalLayer` for a `Json` response of the same value.
+    pub fn from_json<T: Serialize + ?Sized>(value: &T) -> Result<Self, serde_json::Error> {
+        serde_json::to_vec(value).map(Self::from_bytes)
+    }
+
+    /// Creates a weak tag from the size and modification time of a file.
+    pub fn from_metadata(metadata: &Metadata) -> Option<Self> {
+        let modified = metadata.modified().ok()?.duration_since(UNIX_EPOCH).ok()?;
+        Some(Self::weak(format!(
+            "{:x}-{:x}",
+            metadata.len(),
+            modified.as_secs()
+        )))
+    }
+
+    pub fn tag(&self) -> &str {
+        &self.tag
+    }
+
+    pub fn is_weak(&self) -> bool {
+        self.weak
+    }
+
+    /// Strong comparison, where both tags must be strong and equal.
+    pub fn strong_eq(&self, other: &ETag) -> bool {
+        !self.weak && !other.weak && self.tag == other.tag
+    }
+
+    /// Weak comparison, where the tags must be equal, ignoring whether they are weak.
+    pub fn weak_eq(&self, other: &ETag) -> bool {
+        self.tag == other.tag
+    }
+}
+
+impl Display for ETag {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        if self.weak {
+            write!(f, "W/")?;
+        }
+        write!(f, "\"{}\"", self.tag)
+    }
+}
+
+/// Error returned when parsing an invalid entity tag.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid entity tag")]
+pub struct ParseETagError;
+
+impl FromStr for ETag {
+    type Err = ParseETagError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (weak, quoted) = match s.trim().strip_prefix("W/") {
+            Some(quoted) => (true, quoted),
+            None => (false, s.trim()),
+        };
+        let tag = quoted
+            .strip_prefix('"')
+            .and_then(|tag| tag.strip_suffix('"'))
+            .filter(|tag| !tag.contains('"'))
+            .ok_or(ParseETagError)?;
+        Ok(Self {
+            tag: tag.to_string(),
+            weak,
+        })
+    }
+}
+
+impl IntoResponseParts for ETag {
+    type Error = Infallible;
+
+    fn into_response_parts(self, mut res: ResponseParts) -> Result<ResponseParts, Self::Error> {
+        if let Ok(value) = HeaderValue::try_from(self.to_string()) {
+            res.headers_mut().insert(ETAG, value);
+        }
+        Ok(res)
+    }
+}
+
+/// The value of an `If-Match` or `If-None-Match` header.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ETagMatch {
+    /// `*`, matching any current representation.
+    Any,
+    Tags(Vec<ETag>),
+}
+
+impl ETagMatch {
+    /// Parses the header, returning `None` if it is not present.
+    pub fn from_headers(
+        headers: &HeaderMap,
+        name: impl axum::http::header::AsHeaderName,
+    ) -> Result<Option<Self>, ParseETagError> {
+        let Some(value) = headers.get(name) else {
+            return Ok(None);
+        };
+        let value = value.to_str().map_err(|_| ParseETagError)?;
+        if value.trim() == "*" {
+            return Ok(Some(ETagMatch::Any));
+        }
+        value
+            .split(',')
+            .filter(|tag| !tag.trim().is_empty())
+            .map(ETag::from_str)
+            .collect::<Result<_, _>>()
+            .map(|tags| Some(ETagMatch::Tags(tags)))
+    }
+
+    fn matches(&self, etag: &ETag, compare: fn(&ETag, &ETag) -> bool) -> bool {
+        match self {
+            ETagMatch::Any => true,
+            ETagMatch::Tags(tags) => tags.iter().any(|tag| compare(tag, etag)),
+        }
+    }
+}
+
+/// Rejection type for conditional request extractors.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum ConditionalRejection {
+    #[error("Precondition failed")]
+    PreconditionFailed,
+    #[error("Invalid {0} header")]
+    InvalidHeader(&'static str),
+}
+
+impl IntoResponse for ConditionalRejection {
+    fn into_response(self) -> Response {
+        let status = match self {
+            ConditionalRejection::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            ConditionalRejection::InvalidHeader(_) => StatusCode::BAD_REQUEST,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+/// Extractor for the `If-Match` header, used for optimistic concurrency on unsafe methods.
+/// The handler compares the header against the current version of the resource with `check`,
+/// which rejects the request with 412 Precondition Failed if the resource has changed.
+/// # Example
+/// ```
+/// use lib::axum::conditional::{ConditionalRejection, ETag, IfMatch};
+///
+/// async fn update(if_match: IfMatch) -> Result<(), ConditionalRejection> {
+///     let current = ETag::from_json(&"current value").unwrap();
+///     if_match.check(Some(&current))?;
+///     Ok(())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct IfMatch(pub Option<ETagMatch>);
+
+impl IfMatch {
+    /// Checks the precondition against the current tag, or `None` if the resource does not exist.
+    /// Requests without the header always pass.
+    pub fn check(&self, current: Option<&ETag>) -> Result<(), ConditionalRejection> {
+        let Some(condition) = &self.0 else {
+            return Ok(());
+        };
+        match current {
+            Some(current) if condition.matches(current, ETag::strong_eq) => Ok(()),
+            _ => Err(ConditionalRejection::PreconditionFailed),
+        }
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for IfMatch {
+    type Rejection = ConditionalRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        ETagMatch::from_headers(&parts.headers, IF_MATCH)
+            .map(IfMatch)
+            .map_err(|_| ConditionalRejection::InvalidHeader("If-Match"))
+    }
+}
+
+/// Layer answering conditional `GET` and `HEAD` requests with 304 Not Modified.
+///
+/// Successful responses without an `ETag` get one computed from a hash of the body,
+/// if the size of the body is known and within the limit, which is the case for JSON responses.
+/// Streamed responses, like files, should set their own `ETag` or `Last-Modified` header,
+/// see `ETag::from_metadata` and `load_file_response`.
+/// `If-None-Match` takes precedence over `If-Modified-Since`, as required by RFC 9110.
+/// # Default Options
+/// - Weak == false
+/// - Body limit == 2 MB
+/// # Example
+/// ```
+/// use lib::axum::conditional::ConditionalLayer;
+///
+/// let _router: axum::Router = lib::routes!(get "/" => || async { "Hello, World!" })
+///     .layer(ConditionalLayer::new());
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ConditionalLayer {
+    weak: bool,
+    body_limit: usize,
+}
+
+impl Default for ConditionalLayer {
+    fn default() -> Self {
+        Self {
+            weak: false,
+            body_limit: 2 * 1024 * 1024,
+        }
+    }
+}
+
+impl ConditionalLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets whether computed tags are weak.
+    pub fn weak(mut self, weak: bool) -> Self {
+        self.weak = weak;
+        self
+    }
+
+    /// Sets the maximum size of bodies to compute tags for.
+    pub fn body_limit(mut self, limit: usize) -> Self {
+        self.body_limit = limit;
+        self
+    }
+
+    async fn with_etag(&self, response: Response) -> Response {
+        let size = response.body().size_hint().upper();
+        if response.headers().contains_key(ETAG)
+            || size.map_or(true, |size| size > self.body_limit as u64)
+        {
+            return response;
+        }
+        let (mut parts, body) = response.into_parts();
+        let bytes = match buffer(body, self.body_limit).await {
+            Ok(bytes) => bytes,
+            Err(body) => return Response::from_parts(parts, body),
+        };
+        let mut etag = ETag::from_bytes(&bytes);
+        etag.weak = self.weak;
+        if let Ok(value) = HeaderValue::try_from(etag.to_string()) {
+            parts.headers.insert(ETAG, value);
+        }
+        Response::from_parts(parts, Body::from(bytes))
+    }
+}
+
+impl<S> Layer<S> for ConditionalLayer {
+    type Service = ConditionalService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ConditionalService {
+            inner,
+            layer: *self,
+        }
+    }
+}
+
+/// Service created by the `ConditionalLayer`.
+#[derive(Debug, Clone)]
+pub struct ConditionalService<S> {
+    inner: S,
+    layer: ConditionalLayer,
+}
+
+impl<S> Service<Request> for ConditionalService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let layer = self.layer;
+        Box::pin(async move {
+            if req.method() != Method::GET && req.method() != Method::HEAD {
+                return inner.call(req).await;
+            }
+            // Invalid conditions are ignored, and the full response is returned
+            let if_none_match = ETagMatch::from_headers(req.headers(), IF_NONE_MATCH)
+                .ok()
+                .flatten();
+            let if_modified_since = header_date(req.headers(), IF_MODIFIED_SINCE);
+
+            let response = inner.call(req).await?;
+            if response.status() != StatusCode::OK {
+                return Ok(response);
+            }
+            let response = layer.with_etag(response).await;
+
+            let not_modified = match (if_none_match, if_modified_since) {
+                (Some(condition), _) => response_etag(&response)
+                    .is_some_and(|etag| condition.matches(&etag, ETag::weak_eq)),
+                (None, Some(since)) => header_date(response.headers(), LAST_MODIFIED)
+                    .is_some_and(|modified| unix_seconds(modified) <= unix_seconds(since)),
+                (None, None) => false,
+            };
+            Ok(if not_modified {
+                not_modified_response(response.headers())
+            } else {
+                response
+            })
+        })
+    }
+}
+
+fn response_etag(response: &Response) -> Option<ETag> {
+    response.headers().get(ETAG)?.to_str().ok()?.parse().ok()
+}
+
+fn header_date(
+    headers: &HeaderMap,
+    name: impl axum::http::header::AsHeaderName,
+) -> Option<SystemTime> {
+    httpdate::parse_http_date(headers.get(name)?.to_str().ok()?).ok()
+}
+
+fn unix_seconds(time: SystemTime) -> u64 {
+    time.duration_since(UNIX_EPOCH)
+        .map(|duration| duration.as_secs())
+        .unwrap_or_default()
+}
+
+/// Creates a 304 response, keeping the headers a 200 response would have had for caching.
+fn not_modified_response(headers: &HeaderMap) -> Response {
+    let mut response = StatusCode::NOT_MODIFIED.into_response();
+    for name in [
+        CACHE_CONTROL,
+        CONTENT_LOCATION,
+        DATE,
+        ETAG,
+        EXPIRES,
+        LAST_MODIFIED,
+        VARY,
+    ] {
+        for value in headers.get_all(&name) {
+            response.headers_mut().append(name.clone(), value.clone());
+        }
+    }
+    response
+}
+
+/// Formats the time as an HTTP date, for the `Last-Modified` header.
+pub fn http_date(time: SystemTime) -> HeaderValue {
+    HeaderValue::try_from(httpdate::fmt_http_date(time))
+        .expect("HTTP dates are valid header values")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::serde::response::{BaseResponse, Data};
+    use axum::{http::header::IF_MATCH, routing::put, Router};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    fn app() -> Router {
+        crate::routes!(
+            get "/" => || async { BaseResponse::new("", Data(vec!["value"])) },
+            get "/modified" => || async {
+                ([(LAST_MODIFIED, http_date(UNIX_EPOCH + Duration::from_secs(1000)))], "file")
+            },
+            get "/missing" => || async { StatusCode::NOT_FOUND }
+        )
+        .route(
+            "/",
+            put(|if_match: IfMatch| async move {
+                if_match.check(Some(&ETag::from_json(&"value").unwrap()))
+            }),
+        )
+        .layer(ConditionalLayer::new())
+    }
+
+    async fn send(method: Method, uri: &str, headers: &[(&str, &str)]) -> Response {
+        let mut builder = Request::builder().method(method).uri(uri);
+        for (name, value) in headers {
+            builder = builder.header(*name, *value);
+        }
+        app()
+            .oneshot(builder.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse_etag() {
+        assert_eq!("\"a\"".parse(), Ok(ETag::strong("a")));
+        assert_eq!("W/\"a\"".parse(), Ok(ETag::weak("a")));
+        assert_eq!("a".parse::<ETag>(), Err(ParseETagError));
+        assert_eq!(ETag::strong("a").to_string(), "\"a\"");
+    }
+
+    #[test]
+    fn test_compare_etag() {
+        assert!(ETag::strong("a").strong_eq(&ETag::strong("a")));
+        assert!(!ETag::weak("a").strong_eq(&ETag::strong("a")));
+        assert!(ETag::weak("a").weak_eq(&ETag::strong("a")));
+        assert!(!ETag::strong("a").weak_eq(&ETag::strong("b")));
+    }
+
+    #[test]
+    fn test_parse_etag_match() {
+        let mut headers = HeaderMap::new();
+        headers.insert(IF_MATCH, HeaderValue::from_static("\"a\", W/\"b\""));
+        assert_eq!(
+            ETagMatch::from_headers(&headers, IF_MATCH),
+            Ok(Some(ETagMatch::Tags(vec![
+                ETag::strong("a"),
+                ETag::weak("b")
+            ])))
+        );
+        headers.insert(IF_MATCH, HeaderValue::from_static("*"));
+        assert_eq!(
+            ETagMatch::from_headers(&headers, IF_MATCH),
+            Ok(Some(ETagMatch::Any))
+        );
+        assert_eq!(
+            ETagMatch::from_headers(&HeaderMap::new(), IF_MATCH),
+            Ok(None)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_etag_is_computed() {
+        let response = send(Method::GET, "/", &[]).await;
+        let expected = ETag::from_json(&BaseResponse::new("", Data(vec!["value"]))).unwrap();
+        assert_eq!(response_etag(&response), Some(expected));
+    }
+
+    #[tokio::test]
+    async fn test_if_none_match() {
+        let etag = response_etag(&send(Method::GET, "/", &[]).await).unwrap();
+        let response = send(Method::GET, "/", &[("if-none-match", &etag.to_string())]).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response_etag(&response), Some(etag));
+
+        let response = send(Method::GET, "/", &[("if-none-match", "\"other\"")]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_if_modified_since() {
+        let since = httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(1000));
+        let response = send(Method::GET, "/modified", &[("if-modified-since", &since)]).await;
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let since = httpdate::fmt_http_date(UNIX_EPOCH + Duration::from_secs(999));
+        let response = send(Method::GET, "/modified", &[("if-modified-since", &since)]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_error_is_not_conditional() {
+        let response = send(Method::GET, "/missing", &[("if-none-match", "*")]).await;
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_if_match() {
+        let etag = ETag::from_json(&"value").unwrap().to_string();
+        let response = send(Method::PUT, "/", &[("if-match", &etag)]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+
+        let response = send(Method::PUT, "/", &[("if-match", "\"stale\"")]).await;
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        let response = send(Method::PUT, "/", &[]).await;
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,49 @@
+use {
+    axum::http::{header::COOKIE, HeaderMap},
+    base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine},
+    cookie::{Cookie, CookieJar},
+    rand::RngCore,
+};
+
+/// Creates a cookie jar containing all cookies sent in the request headers.
+pub(crate) fn request_jar(headers: &HeaderMap) -> CookieJar {
+    let mut jar = CookieJar::new();
+    headers
+        .get_all(COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| Cookie::split_parse(value.to_string()))
+        .filter_map(Result::ok)
+        .for_each(|cookie| jar.add_original(cookie));
+    jar
+}
+
+/// Generates a random url-safe token with 256 bits of entropy.
+pub(crate) fn random_token() -> String {
+    let mut bytes = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    URL_SAFE_NO_PAD.encode(bytes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn test_request_jar() {
+        let mut headers = HeaderMap::new();
+        headers.append(COOKIE, HeaderValue::from_static("a=1; b=2"));
+        headers.append(COOKIE, HeaderValue::from_static("c=3"));
+        let jar = request_jar(&headers);
+        assert_eq!(jar.get("b").map(Cookie::value), Some("2"));
+        assert_eq!(jar.get("c").map(Cookie::value), Some("3"));
+    }
+
+    #[test]
+    fn test_random_token() {
+        let token = random_token();
+        assert_eq!(token.len(), 43);
+        assert_ne!(token, random_token());
+    }
+}
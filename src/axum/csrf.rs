@@ -0,0 +1,539 @@
+use {
+    crate::axum::cookies::{random_token, request_jar},
+    axum::{
+        async_trait,
+        body::{to_bytes, Body},
+        extract::{FromRequestParts, Request},
+        http::{
+            header::{AUTHORIZATION, CONTENT_TYPE, SET_COOKIE},
+            request::Parts,
+            HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+        },
+        response::{IntoResponse, Response},
+        Json,
+    },
+    cookie::{Cookie, CookieJar, SameSite},
+    futures_util::future::BoxFuture,
+    serde_json::json,
+    std::{
+        collections::HashMap,
+        sync::Arc,
+        task::{Context, Poll},
+    },
+    subtle::ConstantTimeEq,
+    thiserror::Error,
+    tower::{Layer, Service},
+};
+
+pub use cookie::Key;
+
+/// The CSRF token of the current request, to be embedded in forms, or sent in a header by scripts.
+/// Requires a `CsrfLayer` on the router.
+/// # Example
+/// ```
+/// use lib::axum::csrf::CsrfToken;
+///
+/// async fn form(token: CsrfToken) -> axum::response::Html<axum::body::Body> {
+///     lib::load_html!("csrf.rs", "{{csrf_input}}" => &token.hidden_input())
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CsrfToken {
+    token: String,
+    field: Arc<str>,
+}
+
+impl CsrfToken {
+    /// The token value.
+    pub fn as_str(&self) -> &str {
+        &self.token
+    }
+
+    /// A hidden form input containing the token, e.g. `<input type="hidden" name="csrf_token" value="...">`.
+    pub fn hidden_input(&self) -> String {
+        format!(
+            r#"<input type="hidden" name="{}" value="{}">"#,
+            self.field, self.token
+        )
+    }
+}
+
+/// Rejection type for the `CsrfToken` extractor and the `CsrfLayer`.
+/// Responds with a JSON body containing the error message.
+#[derive(Debug, Error)]
+pub enum CsrfRejection {
+    #[error("Missing CSRF token")]
+    MissingToken,
+    #[error("Invalid CSRF token")]
+    InvalidToken,
+    #[error("Failed to read form body: {0}")]
+    BodyError(String),
+    #[error("CSRF layer is missing")]
+    MissingLayer,
+}
+
+impl IntoResponse for CsrfRejection {
+    fn into_response(self) -> Response {
+        let status = match self {
+            CsrfRejection::MissingToken | CsrfRejection::InvalidToken => StatusCode::FORBIDDEN,
+            CsrfRejection::BodyError(_) => StatusCode::BAD_REQUEST,
+            CsrfRejection::MissingLayer => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for CsrfToken
+where
+    S: Send + Sync,
+{
+    type Rejection = CsrfRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<CsrfToken>()
+            .cloned()
+            .ok_or(CsrfRejection::MissingLayer)
+    }
+}
+
+#[derive(Clone)]
+struct CsrfConfig {
+    key: Key,
+    cookie_name: String,
+    header: HeaderName,
+    field: Arc<str>,
+    secure: bool,
+    exempt_bearer: bool,
+    exempt_json: bool,
+    exempt_paths: Vec<String>,
+    body_limit: usize,
+}
+
+/// Layer protecting against cross-site request forgery using signed double-submit cookies.
+///
+/// Every response sets a signed cookie containing a token, if the request did not have one.
+/// Requests with unsafe methods (anything but GET, HEAD, OPTIONS and TRACE) must send the same token,
+/// either in a header or in a field of a urlencoded form, otherwise they are rejected with 403 Forbidden.
+/// Scripts may send the value of the cookie as it is, or the token from `CsrfToken`.
+/// Multipart forms must send the token in the header.
+///
+/// Requests with a bearer token, and requests with a JSON body, are exempt by default,
+/// since browsers cannot send them cross-site without a CORS preflight.
+/// # Default Options
+/// - Cookie name == "csrf_token"
+/// - Header == "X-CSRF-Token"
+/// - Form field == "csrf_token"
+/// - Secure == true
+/// - Exempt bearer == true
+/// - Exempt JSON == true
+/// - Form body limit == 2 MB
+/// # Example
+/// ```
+/// use lib::axum::csrf::{CsrfLayer, Key};
+///
+/// let layer = CsrfLayer::new(Key::generate()).exempt_path("/webhooks");
+/// let _router: axum::Router = lib::routes!(post "/" => || async {}).layer(layer);
+/// ```
+#[derive(Clone)]
+pub struct CsrfLayer {
+    config: Arc<CsrfConfig>,
+}
+
+impl CsrfLayer {
+    /// Creates a layer signing the token cookie with the given key.
+    pub fn new(key: Key) -> Self {
+        Self {
+            config: Arc::new(CsrfConfig {
+                key,
+                cookie_name: "csrf_token".to_string(),
+                header: HeaderName::from_static("x-csrf-token"),
+                field: Arc::from("csrf_token"),
+                secure: true,
+                exempt_bearer: true,
+                exempt_json: true,
+                exempt_paths: vec![],
+                body_limit: 2 * 1024 * 1024,
+            }),
+        }
+    }
+
+    fn config(mut self, f: impl FnOnce(&mut CsrfConfig)) -> Self {
+        f(Arc::make_mut(&mut self.config));
+        self
+    }
+
+    /// Sets the name of the token cookie.
+    pub fn cookie_name(self, name: impl Into<String>) -> Self {
+        self.config(|config| config.cookie_name = name.into())
+    }
+
+    /// Sets the header the token is read from.
+    pub fn header(self, header: HeaderName) -> Self {
+        self.config(|config| config.header = header)
+    }
+
+    /// Sets the form field the token is read from.
+    pub fn form_field(self, field: &str) -> Self {
+        self.config(|config| config.field = Arc::from(field))
+    }
+
+    /// Sets whether the cookie should only be sent over https.
+    pub fn secure(self, secure: bool) -> Self {
+        self.config(|config| config.secure = secure)
+    }
+
+    /// Sets whether requests with an `Authorization: Bearer` header skip verification.
+    pub fn exempt_bearer(self, exempt: bool) -> Self {
+        self.config(|config| config.exempt_bearer = exempt)
+    }
+
+    /// Sets whether requests with an `application/json` body skip verification.
+    pub fn exempt_json(self, exempt: bool) -> Self {
+        self.config(|config| config.exempt_json = exempt)
+    }
+
+    /// Skips verification for the path and all paths below it,
+    /// e.g. `/webhooks` exempts `/webhooks/github` but not `/webhooks-admin`.
+    pub fn exempt_path(self, prefix: impl Into<String>) -> Self {
+        self.config(|config| config.exempt_paths.push(prefix.into()))
+    }
+
+    /// Sets the maximum size of urlencoded form bodies that are read to find the token.
+    pub fn body_limit(self, limit: usize) -> Self {
+        self.config(|config| config.body_limit = limit)
+    }
+}
+
+impl<S> Layer<S> for CsrfLayer {
+    type Service = CsrfService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CsrfService {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// Service created by the `CsrfLayer`.
+#[derive(Clone)]
+pub struct CsrfService<S> {
+    inner: S,
+    config: Arc<CsrfConfig>,
+}
+
+impl<S> Service<Request> for CsrfService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let config = self.config.clone();
+        Box::pin(async move {
+            let existing = config.cookie_token(req.headers());
+            let mut req = if config.requires_verification(&req) {
+                match config.verify(req, existing.as_deref()).await {
+                    Ok(req) => req,
+                    Err(rejection) => return Ok(rejection.into_response()),
+                }
+            } else {
+                req
+            };
+            let token = existing.clone().unwrap_or_else(random_token);
+            req.extensions_mut().insert(CsrfToken {
+                token: token.clone(),
+                field: config.field.clone(),
+            });
+            let mut response = inner.call(req).await?;
+            if existing.is_none() {
+                if let Ok(value) = HeaderValue::from_str(&config.cookie(token).to_string()) {
+                    response.headers_mut().append(SET_COOKIE, value);
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+impl CsrfConfig {
+    fn cookie_token(&self, headers: &HeaderMap) -> Option<String> {
+        let jar = request_jar(headers);
+        let cookie = jar.signed(&self.key).get(&self.cookie_name)?;
+        Some(cookie.value().to_string())
+    }
+
+    fn cookie(&self, token: String) -> Cookie<'static> {
+        // The cookie must be readable by scripts, to send the token in the header
+        let cookie = Cookie::build((self.cookie_name.clone(), token))
+            .path("/")
+            .secure(self.secure)
+            .same_site(SameSite::Strict)
+            .build();
+        let mut jar = CookieJar::new();
+        jar.signed_mut(&self.key).add(cookie);
+        jar.get(&self.cookie_name)
+            .cloned()
+            .expect("Cookie was just added")
+    }
+
+    /// Compares the submitted token with the token of the cookie.
+    /// Scripts read the signed value of the cookie, so a validly signed value is accepted as well.
+    fn matches(&self, submitted: &str, expected: &str) -> bool {
+        let mut jar = CookieJar::new();
+        jar.add_original(Cookie::new(self.cookie_name.clone(), submitted.to_string()));
+        let unsigned = jar.signed(&self.key).get(&self.cookie_name);
+        let token = unsigned.as_ref().map_or(submitted, |cookie| cookie.value());
+        token.as_bytes().ct_eq(expected.as_bytes()).into()
+    }
+
+    fn requires_verification(&self, req: &Request) -> bool {
+        let safe = matches!(
+            *req.method(),
+            Method::GET | Method::HEAD | Method::OPTIONS | Method::TRACE
+        );
+        let path = req.uri().path();
+        let bearer = header_starts_with(req.headers(), &AUTHORIZATION, "bearer ");
+        let json = header_starts_with(req.headers(), &CONTENT_TYPE, "application/json");
+        !(safe
+            || (self.exempt_bearer && bearer)
+            || (self.exempt_json && json)
+            || self
+                .exempt_paths
+                .iter()
+                .any(|prefix| is_below(path, prefix)))
+    }
+
+    /// Verifies the token sent in the request, returning the request with the body restored.
+    async fn verify(&self, req: Request, expected: Option<&str>) -> Result<Request, CsrfRejection> {
+        let expected = expected.ok_or(CsrfRejection::MissingToken)?;
+        if let Some(token) = req.headers().get(&self.header) {
+            let token = token.to_str().map_err(|_| CsrfRejection::InvalidToken)?;
+            return if self.matches(token, expected) {
+                Ok(req)
+            } else {
+                Err(CsrfRejection::InvalidToken)
+            };
+        }
+        if !header_starts_with(
+            req.headers(),
+            &CONTENT_TYPE,
+            "application/x-www-form-urlencoded",
+        ) {
+            return Err(CsrfRejection::MissingToken);
+        }
+        let (parts, body) = req.into_parts();
+        let bytes = to_bytes(body, self.body_limit)
+            .await
+            .map_err(|error| CsrfRejection::BodyError(error.to_string()))?;
+        let form = serde_urlencoded::from_bytes::<HashMap<String, String>>(&bytes)
+            .map_err(|error| CsrfRejection::BodyError(error.to_string()))?;
+        let token = form
+            .get(self.field.as_ref())
+            .ok_or(CsrfRejection::MissingToken)?;
+        if self.matches(token, expected) {
+            Ok(Request::from_parts(parts, Body::from(bytes)))
+        } else {
+            Err(CsrfRejection::InvalidToken)
+        }
+    }
+}
+
+/// Returns true if the path is the prefix, or a path below it.
+fn is_below(path: &str, prefix: &str) -> bool {
+    path.strip_prefix(prefix.trim_end_matches('/'))
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+}
+
+fn header_starts_with(headers: &HeaderMap, name: &HeaderName, prefix: &str) -> bool {
+    headers
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| {
+            value.len() >= prefix.len() && value[..prefix.len()].eq_ignore_ascii_case(prefix)
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{http::header::COOKIE, Form, Router};
+    use tower::ServiceExt;
+
+    fn app(layer: CsrfLayer) -> Router {
+        crate::routes!(
+            get "/" => |token: CsrfToken| async move { token.as_str().to_string() },
+            post "/" => |Form(form): Form<HashMap<String, String>>| async move {
+                form.get("name").cloned().unwrap_or_default()
+            }
+        )
+        .layer(layer)
+    }
+
+    async fn body(response: Response) -> String {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    /// Returns the cookie and the token from a GET request.
+    async fn token(app: &Router) -> (String, String) {
+        let request = Request::builder().uri("/").body(Body::empty()).unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let cookie = response
+            .headers()
+            .get(SET_COOKIE)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        let cookie = Cookie::parse(cookie.to_string())
+            .unwrap()
+            .stripped()
+            .to_string();
+        (cookie, body(response).await)
+    }
+
+    fn post(cookie: &str) -> axum::http::request::Builder {
+        Request::builder()
+            .method("POST")
+            .uri("/")
+            .header(COOKIE, cookie)
+    }
+
+    #[test]
+    fn test_hidden_input() {
+        let token = CsrfToken {
+            token: "abc".to_string(),
+            field: Arc::from("csrf_token"),
+        };
+        assert_eq!(
+            token.hidden_input(),
+            r#"<input type="hidden" name="csrf_token" value="abc">"#
+        );
+    }
+
+    #[tokio::test]
+    async fn test_get_sets_cookie_once() {
+        let app = app(CsrfLayer::new(Key::generate()));
+        let (cookie, token) = token(&app).await;
+        assert_eq!(token.len(), 43);
+
+        let request = Request::builder()
+            .uri("/")
+            .header(COOKIE, &cookie)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert!(response.headers().get(SET_COOKIE).is_none());
+        assert_eq!(body(response).await, token);
+    }
+
+    #[tokio::test]
+    async fn test_post_with_header() {
+        let app = app(CsrfLayer::new(Key::generate()));
+        let (cookie, token) = token(&app).await;
+        let request = post(&cookie)
+            .header("x-csrf-token", token)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from("name=test"))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_post_with_cookie_value_in_header() {
+        let app = app(CsrfLayer::new(Key::generate()));
+        let (cookie, _) = token(&app).await;
+        let (_, value) = cookie.split_once('=').unwrap();
+        let request = post(&cookie)
+            .header("x-csrf-token", value)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from("name=test"))
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+
+        // A signed value with a tampered token is rejected
+        let tampered = format!("{value}x");
+        let request = post(&cookie)
+            .header("x-csrf-token", tampered)
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_post_with_form_field() {
+        let app = app(CsrfLayer::new(Key::generate()));
+        let (cookie, token) = token(&app).await;
+        let request = post(&cookie)
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from(format!("name=test&csrf_token={token}")))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(body(response).await, "test");
+    }
+
+    #[tokio::test]
+    async fn test_post_invalid_token() {
+        let app = app(CsrfLayer::new(Key::generate()));
+        let (cookie, _) = token(&app).await;
+        let request = post(&cookie)
+            .header("x-csrf-token", "wrong")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        let request = post("").body(Body::empty()).unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_post_exempt() {
+        let app = app(CsrfLayer::new(Key::generate()));
+        let request = post("")
+            .header(AUTHORIZATION, "Bearer token")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from("name=test"))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_is_below() {
+        assert!(is_below("/webhooks", "/webhooks"));
+        assert!(is_below("/webhooks/github", "/webhooks"));
+        assert!(is_below("/webhooks/github", "/webhooks/"));
+        assert!(is_below("/webhooks", "/"));
+        assert!(!is_below("/webhooksevil", "/webhooks"));
+        assert!(!is_below("/other", "/webhooks"));
+    }
+
+    #[tokio::test]
+    async fn test_post_exempt_path() {
+        let app = app(CsrfLayer::new(Key::generate()).exempt_path("/"));
+        let request = post("")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from("name=test"))
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
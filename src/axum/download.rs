@@ -0,0 +1,457 @@
+use {
+    axum::{
+        body::{Body, Bytes},
+        http::{
+            header::{
+                ACCEPT_RANGES, CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_RANGE, CONTENT_TYPE,
+                IF_RANGE, LAST_MODIFIED, RANGE,
+            },
+            HeaderMap, HeaderValue, StatusCode,
+        },
+        response::{IntoResponse, Response},
+    },
+    futures_util::{future, stream, Stream, StreamExt, TryStreamExt},
+    httpdate::HttpDate,
+    mime::Mime,
+    rand::RngCore,
+    std::{
+        io,
+        ops::Range,
+        path::{Path, PathBuf},
+        time::SystemTime,
+    },
+    tokio::{
+        fs::File,
+        io::{AsyncReadExt, AsyncSeekExt, SeekFrom},
+    },
+    tokio_util::io::ReaderStream,
+};
+
+/// The maximum number of ranges served for a request. Requests with more ranges get the whole file.
+const MAX_RANGES: usize = 16;
+
+/// A response streaming a file from disk.
+/// Sets the `Content-Type` guessed from the extension of the file, `Content-Length`, `Last-Modified`,
+/// and `Content-Disposition` with the filename, encoded as in RFC 5987 if it is not ASCII.
+///
+/// With the headers of the request passed to `ranges`, the `Range` header is served with 206 Partial Content,
+/// as a `multipart/byteranges` body if there are several ranges.
+/// Ranges beyond the end of the file are rejected with 416 Range Not Satisfiable.
+/// # Example
+/// ```
+/// use axum::http::{HeaderMap, StatusCode};
+/// use lib::axum::download::FileResponse;
+///
+/// async fn download(headers: HeaderMap) -> Result<FileResponse, StatusCode> {
+///     let file = FileResponse::open("Cargo.toml")
+///         .await
+///         .map_err(|_| StatusCode::NOT_FOUND)?;
+///     Ok(file.attachment().ranges(&headers))
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct FileResponse {
+    path: PathBuf,
+    size: u64,
+    content_type: Mime,
+    filename: Option<String>,
+    attachment: bool,
+    last_modified: Option<SystemTime>,
+    range: Option<String>,
+}
+
+impl FileResponse {
+    /// Reads the metadata of the file at the path.
+    /// The file is read when the response is sent.
+    /// # Errors
+    /// If the file does not exist, or is not a file.
+    pub async fn open(path: impl AsRef<Path>) -> io::Result<Self> {
+        let path = path.as_ref().to_path_buf();
+        let metadata = tokio::fs::metadata(&path).await?;
+        if !metadata.is_file() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a file", path.display()),
+            ));
+        }
+        Ok(Self {
+            content_type: mime_guess::from_path(&path).first_or_octet_stream(),
+            filename: path
+                .file_name()
+                .map(|filename| filename.to_string_lossy().to_string()),
+            size: metadata.len(),
+            last_modified: metadata.modified().ok(),
+            attachment: false,
+            range: None,
+            path,
+        })
+    }
+
+    /// Sets the content type of the file.
+    pub fn content_type(mut self, content_type: Mime) -> Self {
+        self.content_type = content_type;
+        self
+    }
+
+    /// Sets the filename sent to the client, instead of the name of the file on disk.
+    pub fn filename(mut self, filename: impl Into<String>) -> Self {
+        self.filename = Some(filename.into());
+        self
+    }
+
+    /// Asks the browser to download the file, instead of displaying it.
+    pub fn attachment(mut self) -> Self {
+        self.attachment = true;
+        self
+    }
+
+    /// Serves the ranges in the `Range` header of the request.
+    /// If the request has an `If-Range` header which does not match the `Last-Modified` date of the file,
+    /// the whole file is sent instead.
+    pub fn ranges(mut self, headers: &HeaderMap) -> Self {
+        let unchanged = match headers.get(IF_RANGE) {
+            Some(if_range) => {
+                let if_range = if_range.to_str().ok().and_then(|date| date.parse().ok());
+                if_range.is_some() && if_range == self.last_modified.map(HttpDate::from)
+            }
+            None => true,
+        };
+        self.range = headers
+            .get(RANGE)
+            .filter(|_| unchanged)
+            .and_then(|range| range.to_str().ok())
+            .map(str::to_string);
+        self
+    }
+
+    fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(ACCEPT_RANGES, HeaderValue::from_static("bytes"));
+        if let Some(last_modified) = self.last_modified {
+            if let Ok(value) = HeaderValue::try_from(httpdate::fmt_http_date(last_modified)) {
+                headers.insert(LAST_MODIFIED, value);
+            }
+        }
+        let kind = if self.attachment {
+            "attachment"
+        } else {
+            "inline"
+        };
+        let disposition = match &self.filename {
+            Some(filename) => content_disposition(kind, filename),
+            None => kind.to_string(),
+        };
+        if let Ok(value) = HeaderValue::try_from(disposition) {
+            headers.insert(CONTENT_DISPOSITION, value);
+        }
+        headers
+    }
+
+    fn content_type_header(&self) -> HeaderValue {
+        HeaderValue::try_from(self.content_type.to_string())
+            .unwrap_or(HeaderValue::from_static("application/octet-stream"))
+    }
+}
+
+impl IntoResponse for FileResponse {
+    fn into_response(self) -> Response {
+        let mut headers = self.headers();
+        let ranges = self
+            .range
+            .as_deref()
+            .and_then(|range| parse_ranges(range, self.size));
+        let (status, length, body) = match ranges.as_deref() {
+            None => {
+                headers.insert(CONTENT_TYPE, self.content_type_header());
+                let body = Body::from_stream(read_range(self.path, 0..self.size));
+                (StatusCode::OK, self.size, body)
+            }
+            Some([]) => {
+                headers.insert(CONTENT_RANGE, content_range(None, self.size));
+                (StatusCode::RANGE_NOT_SATISFIABLE, 0, Body::empty())
+            }
+            Some([range]) => {
+                headers.insert(CONTENT_TYPE, self.content_type_header());
+                headers.insert(CONTENT_RANGE, content_range(Some(range), self.size));
+                let body = Body::from_stream(read_range(self.path, range.clone()));
+                (StatusCode::PARTIAL_CONTENT, range.end - range.start, body)
+            }
+            Some(ranges) => {
+                let mut bytes = [0u8; 16];
+                rand::thread_rng().fill_bytes(&mut bytes);
+                let boundary: String = bytes.iter().map(|byte| format!("{byte:02x}")).collect();
+                if let Ok(value) =
+                    HeaderValue::try_from(format!("multipart/byteranges; boundary={boundary}"))
+                {
+                    headers.insert(CONTENT_TYPE, value);
+                }
+                let mut length = 0;
+                let mut parts = Vec::with_capacity(ranges.len() * 2 + 1);
+                for range in ranges {
+                    let header = format!(
+                        "\r\n--{boundary}\r\nContent-Type: {}\r\nContent-Range: bytes {}-{}/{}\r\n\r\n",
+                        self.content_type,
+                        range.start,
+                        range.end - 1,
+                        self.size
+                    );
+                    length += header.len() as u64 + range.end - range.start;
+                    parts.push(Part::Bytes(Bytes::from(header)));
+                    parts.push(Part::Range(range.clone()));
+                }
+                let end = format!("\r\n--{boundary}--\r\n");
+                length += end.len() as u64;
+                parts.push(Part::Bytes(Bytes::from(end)));
+                let path = self.path;
+                let body = stream::iter(parts)
+                    .map(move |part| match part {
+                        Part::Bytes(bytes) => stream::once(future::ready(Ok(bytes))).boxed(),
+                        Part::Range(range) => read_range(path.clone(), range).boxed(),
+                    })
+                    .flatten();
+                (StatusCode::PARTIAL_CONTENT, length, Body::from_stream(body))
+            }
+        };
+        headers.insert(CONTENT_LENGTH, HeaderValue::from(length));
+        (status, headers, body).into_response()
+    }
+}
+
+enum Part {
+    Bytes(Bytes),
+    Range(Range<u64>),
+}
+
+/// Streams a range of the file at the path.
+fn read_range(path: PathBuf, range: Range<u64>) -> impl Stream<Item = io::Result<Bytes>> {
+    stream::once(async move {
+        let mut file = File::open(path).await?;
+        file.seek(SeekFrom::Start(range.start)).await?;
+        Ok::<_, io::Error>(ReaderStream::new(file.take(range.end - range.start)))
+    })
+    .try_flatten()
+}
+
+/// Parses a `Range` header into the byte ranges of a file with the given size.
+/// Returns `None` if the header is invalid or has too many ranges, in which case it is ignored,
+/// and an empty list if none of the ranges are satisfiable.
+fn parse_ranges(header: &str, size: u64) -> Option<Vec<Range<u64>>> {
+    let specs = header.trim().strip_prefix("bytes=")?;
+    let mut ranges = Vec::new();
+    for spec in specs.split(',').map(str::trim) {
+        let (start, end) = spec.split_once('-')?;
+        let range = match (start.trim(), end.trim()) {
+            ("", suffix) => {
+                let suffix: u64 = suffix.parse().ok()?;
+                size.saturating_sub(suffix)..size
+            }
+            (start, "") => start.parse().ok()?..size,
+            (start, end) => {
+                let (start, end): (u64, u64) = (start.parse().ok()?, end.parse().ok()?);
+                if end < start {
+                    return None;
+                }
+                start..end.saturating_add(1).min(size)
+            }
+        };
+        if range.start < range.end {
+            ranges.push(range);
+        }
+    }
+    (ranges.len() <= MAX_RANGES).then_some(ranges)
+}
+
+fn content_range(range: Option<&Range<u64>>, size: u64) -> HeaderValue {
+    let value = match range {
+        Some(range) => format!("bytes {}-{}/{size}", range.start, range.end - 1),
+        None => format!("bytes */{size}"),
+    };
+    HeaderValue::try_from(value).expect("Content-Range is a valid header value")
+}
+
+/// Creates a `Content-Disposition` value with the filename.
+/// Filenames which are not printable ASCII are sent as `filename*` encoded as in RFC 5987,
+/// with an ASCII fallback for older clients.
+fn content_disposition(kind: &str, filename: &str) -> String {
+    let fallback: String = filename
+        .chars()
+        .map(|char| match char {
+            ' '..='~' if char != '"' && char != '\\' => char,
+            _ => '_',
+        })
+        .collect();
+    if fallback == filename {
+        return format!("{kind}; filename=\"{filename}\"");
+    }
+    let encoded: String = filename
+        .bytes()
+        .map(|byte| match byte {
+            b'a'..=b'z'
+            | b'A'..=b'Z'
+            | b'0'..=b'9'
+            | b'!'
+            | b'#'
+            | b'$'
+            | b'&'
+            | b'+'
+            | b'-'
+            | b'.'
+            | b'^'
+            | b'_'
+            | b'`'
+            | b'|'
+            | b'~' => char::from(byte).to_string(),
+            byte => format!("%{byte:02X}"),
+        })
+        .collect();
+    format!("{kind}; filename=\"{fallback}\"; filename*=UTF-8''{encoded}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::to_bytes;
+    use tempfile::NamedTempFile;
+
+    async fn file() -> (NamedTempFile, FileResponse) {
+        let file = tempfile::Builder::new().suffix(".txt").tempfile().unwrap();
+        std::fs::write(file.path(), "0123456789").unwrap();
+        let response = FileResponse::open(file.path()).await.unwrap();
+        (file, response)
+    }
+
+    fn range(range: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(RANGE, HeaderValue::try_from(range).unwrap());
+        headers
+    }
+
+    async fn body(response: Response) -> String {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    fn parse(header: &str) -> Option<Vec<(u64, u64)>> {
+        parse_ranges(header, 10).map(|ranges| {
+            ranges
+                .into_iter()
+                .map(|range| (range.start, range.end))
+                .collect()
+        })
+    }
+
+    #[test]
+    fn test_parse_ranges() {
+        assert_eq!(parse("bytes=0-4"), Some(vec![(0, 5)]));
+        assert_eq!(parse("bytes=5-"), Some(vec![(5, 10)]));
+        assert_eq!(parse("bytes=-3"), Some(vec![(7, 10)]));
+        assert_eq!(parse("bytes=8-20"), Some(vec![(8, 10)]));
+        assert_eq!(parse("bytes=0-0, 2-3"), Some(vec![(0, 1), (2, 4)]));
+        assert_eq!(parse("bytes=10-"), Some(vec![]));
+        assert_eq!(parse("bytes=5-4"), None);
+        assert_eq!(parse("items=0-4"), None);
+        assert_eq!(parse("bytes=a-b"), None);
+        assert_eq!(parse(&format!("bytes={}", ["0-0"; 17].join(","))), None);
+    }
+
+    #[test]
+    fn test_content_disposition() {
+        assert_eq!(
+            content_disposition("attachment", "report.pdf"),
+            "attachment; filename=\"report.pdf\""
+        );
+        assert_eq!(
+            content_disposition("inline", "résumé \"final\".pdf"),
+            "inline; filename=\"r_sum_ _final_.pdf\"; filename*=UTF-8''r%C3%A9sum%C3%A9%20%22final%22.pdf"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_full_response() {
+        let (_file, response) = file().await;
+        let response = response.attachment().filename("data.txt").into_response();
+        assert_eq!(response.status(), StatusCode::OK);
+        let headers = response.headers();
+        assert_eq!(headers[CONTENT_TYPE], "text/plain");
+        assert_eq!(headers[CONTENT_LENGTH], "10");
+        assert_eq!(headers[ACCEPT_RANGES], "bytes");
+        assert_eq!(
+            headers[CONTENT_DISPOSITION],
+            "attachment; filename=\"data.txt\""
+        );
+        assert!(headers.contains_key(LAST_MODIFIED));
+        assert_eq!(body(response).await, "0123456789");
+    }
+
+    #[tokio::test]
+    async fn test_single_range() {
+        let (_file, response) = file().await;
+        let response = response.ranges(&range("bytes=2-5")).into_response();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        assert_eq!(response.headers()[CONTENT_RANGE], "bytes 2-5/10");
+        assert_eq!(response.headers()[CONTENT_LENGTH], "4");
+        assert_eq!(body(response).await, "2345");
+    }
+
+    #[tokio::test]
+    async fn test_multiple_ranges() {
+        let (_file, response) = file().await;
+        let response = response.ranges(&range("bytes=0-1,-2")).into_response();
+        assert_eq!(response.status(), StatusCode::PARTIAL_CONTENT);
+        let content_type = response.headers()[CONTENT_TYPE].to_str().unwrap();
+        let boundary = content_type
+            .strip_prefix("multipart/byteranges; boundary=")
+            .unwrap()
+            .to_string();
+        let length: usize = response.headers()[CONTENT_LENGTH]
+            .to_str()
+            .unwrap()
+            .parse()
+            .unwrap();
+        let body = body(response).await;
+        assert_eq!(body.len(), length);
+        assert_eq!(
+            body,
+            format!(
+                "\r\n--{boundary}\r\nContent-Type: text/plain\r\nContent-Range: bytes 0-1/10\r\n\r\n01\
+                \r\n--{boundary}\r\nContent-Type: text/plain\r\nContent-Range: bytes 8-9/10\r\n\r\n89\
+                \r\n--{boundary}--\r\n"
+            )
+        );
+    }
+
+    #[tokio::test]
+    async fn test_unsatisfiable_range() {
+        let (_file, response) = file().await;
+        let response = response.ranges(&range("bytes=20-30")).into_response();
+        assert_eq!(response.status(), StatusCode::RANGE_NOT_SATISFIABLE);
+        assert_eq!(response.headers()[CONTENT_RANGE], "bytes */10");
+    }
+
+    #[tokio::test]
+    async fn test_if_range() {
+        let (_file, response) = file().await;
+        let last_modified = response.headers()[LAST_MODIFIED].clone();
+
+        let mut headers = range("bytes=0-1");
+        headers.insert(IF_RANGE, last_modified);
+        let partial = response.clone().ranges(&headers).into_response();
+        assert_eq!(partial.status(), StatusCode::PARTIAL_CONTENT);
+
+        headers.insert(
+            IF_RANGE,
+            HeaderValue::from_static("Wed, 21 Oct 2015 07:28:00 GMT"),
+        );
+        let full = response.clone().ranges(&headers).into_response();
+        assert_eq!(full.status(), StatusCode::OK);
+
+        headers.insert(IF_RANGE, HeaderValue::from_static("\"etag\""));
+        let full = response.ranges(&headers).into_response();
+        assert_eq!(full.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_open_directory() {
+        assert!(FileResponse::open("src").await.is_err());
+    }
+}
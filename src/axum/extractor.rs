@@ -4,11 +4,13 @@ use axum::{
         multipart::{Field, MultipartError, MultipartRejection},
         FromRequest, Multipart, Request,
     },
+    http::StatusCode,
     response::IntoResponse,
 };
 use mime::Mime;
 use std::str::FromStr;
 use thiserror::Error;
+use tracing::error;
 
 /// A file extracted from a multipart request.
 #[derive(Debug, Clone, PartialEq)]
@@ -34,25 +36,54 @@ impl File {
 
     /// Creates a new file from a field in a multipart request.
     pub async fn from_field(field: Field<'_>) -> Result<Self, MultipartFileRejection> {
+        Self::from_field_with_limit(field, u64::MAX).await
+    }
+
+    /// Creates a new file from a field, failing with `FileTooLarge` as soon as
+    /// more than `limit` bytes are read.
+    pub(crate) async fn from_field_with_limit(
+        mut field: Field<'_>,
+        limit: u64,
+    ) -> Result<Self, MultipartFileRejection> {
         let filename = field
             .file_name()
             .ok_or(MultipartFileRejection::MissingFilename)?
             .to_string();
-        let content_type = Mime::from_str(field.content_type().ok_or_else(|| {
-            MultipartFileRejection::FieldError("Missing or illegal content type".to_string())
-        })?)?;
-        let bytes = field.bytes().await?;
+        let content_type = content_type_or_guess(field.content_type(), &filename)?;
+        let mut bytes = Vec::new();
+        while let Some(chunk) = field.chunk().await? {
+            if (bytes.len() + chunk.len()) as u64 > limit {
+                return Err(MultipartFileRejection::FileTooLarge(limit));
+            }
+            bytes.extend_from_slice(&chunk);
+        }
         Ok(File::new(filename, bytes, content_type))
     }
 }
 
+/// Parses the declared content type of a part,
+/// or guesses it from the extension of the filename if the part has none.
+pub(crate) fn content_type_or_guess(
+    content_type: Option<&str>,
+    filename: &str,
+) -> Result<Mime, mime::FromStrError> {
+    match content_type {
+        Some(content_type) => Mime::from_str(content_type),
+        None => Ok(mime_guess::from_path(filename).first_or_octet_stream()),
+    }
+}
+
 /// Extractor for a single file from a multipart request.
 /// Expects exactly one file. A file must have a name, bytes and optionally a content type.
+/// Without a content type, it is guessed from the extension of the filename.
+/// With the `multipart` feature, files are validated by an `UploadPolicy` extension, if added to the router.
 /// This extractor consumes the request and must ble placed last in the handler.
 #[derive(Debug, Clone, PartialEq)]
 pub struct MultipartFile(pub File);
 /// Extractor for multiple files from a multipart request.
 /// Expects at least one file. A file must have a name, bytes and optionally a content type.
+/// Without a content type, it is guessed from the extension of the filename.
+/// With the `multipart` feature, files are validated by an `UploadPolicy` extension, if added to the router.
 /// This extractor consumes the request and must ble placed last in the handler.
 #[derive(Debug, Clone, PartialEq)]
 pub struct MultipartFiles(pub Vec<File>);
@@ -74,6 +105,17 @@ pub enum MultipartFileRejection {
     MissingFilename,
     #[error("Error in body of multipart: {0}")]
     BodyError(String),
+    #[error("File is larger than the limit of {0} bytes")]
+    FileTooLarge(u64),
+    #[error("Multipart body is larger than the limit of {0} bytes")]
+    TooLarge(u64),
+    /// Responds with a generic message, the error is logged.
+    #[error("Failed to store file: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("Unsupported content type `{0}`")]
+    UnsupportedMediaType(String),
+    #[error("Declared content type `{declared}` does not match the detected `{detected}`")]
+    ContentTypeMismatch { declared: String, detected: String },
 }
 
 impl From<MultipartError> for MultipartFileRejection {
@@ -82,30 +124,42 @@ impl From<MultipartError> for MultipartFileRejection {
     }
 }
 
+impl MultipartFileRejection {
+    /// The status code of the rejection.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            MultipartFileRejection::MultipartRejection(rejection) => rejection.status(),
+            MultipartFileRejection::FieldError(_)
+            | MultipartFileRejection::FromStrError(_)
+            | MultipartFileRejection::NoFiles
+            | MultipartFileRejection::SeveralFiles
+            | MultipartFileRejection::MissingFilename
+            | MultipartFileRejection::BodyError(_) => StatusCode::BAD_REQUEST,
+            MultipartFileRejection::FileTooLarge(_) | MultipartFileRejection::TooLarge(_) => {
+                StatusCode::PAYLOAD_TOO_LARGE
+            }
+            MultipartFileRejection::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            MultipartFileRejection::UnsupportedMediaType(_)
+            | MultipartFileRejection::ContentTypeMismatch { .. } => {
+                StatusCode::UNSUPPORTED_MEDIA_TYPE
+            }
+        }
+    }
+}
+
 impl IntoResponse for MultipartFileRejection {
     fn into_response(self) -> axum::response::Response {
+        let status = self.status();
         match self {
             MultipartFileRejection::MultipartRejection(rejection) => rejection.into_response(),
-            MultipartFileRejection::FieldError(error) => {
-                (axum::http::StatusCode::BAD_REQUEST, error).into_response()
-            }
-            MultipartFileRejection::NoFiles => {
-                (axum::http::StatusCode::BAD_REQUEST, "No files found").into_response()
-            }
-            MultipartFileRejection::SeveralFiles => (
-                axum::http::StatusCode::BAD_REQUEST,
-                "Expected one file, got several",
-            )
-                .into_response(),
-            MultipartFileRejection::MissingFilename => {
-                (axum::http::StatusCode::BAD_REQUEST, "Missing filename").into_response()
-            }
-            MultipartFileRejection::BodyError(error) => {
-                (axum::http::StatusCode::BAD_REQUEST, error).into_response()
-            }
-            MultipartFileRejection::FromStrError(error) => {
-                (axum::http::StatusCode::BAD_REQUEST, error.to_string()).into_response()
+            MultipartFileRejection::FieldError(error)
+            | MultipartFileRejection::BodyError(error) => (status, error).into_response(),
+            // The error may contain paths on the server, so it is only logged
+            MultipartFileRejection::Io(error) => {
+                error!("Failed to store file: {error}");
+                (status, "Failed to store file").into_response()
             }
+            _ => (status, self.to_string()).into_response(),
         }
     }
 }
@@ -131,8 +185,16 @@ where
     /// }
     /// ```
     async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        #[cfg(feature = "multipart")]
+        let policy = upload_policy(&req);
+        #[cfg(feature = "multipart")]
+        let limit = policy.as_ref().and_then(|policy| policy.max_size);
+        #[cfg(not(feature = "multipart"))]
+        let limit = None;
         let multipart = Multipart::from_request(req, state).await?;
-        let files = get_files(multipart).await?;
+        let files = get_files(multipart, limit).await?;
+        #[cfg(feature = "multipart")]
+        let files = validate_files(policy, files)?;
         if files.len() > 1 {
             Err(MultipartFileRejection::SeveralFiles)
         } else {
@@ -168,8 +230,16 @@ where
     /// }
     /// ```
     async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        #[cfg(feature = "multipart")]
+        let policy = upload_policy(&req);
+        #[cfg(feature = "multipart")]
+        let limit = policy.as_ref().and_then(|policy| policy.max_size);
+        #[cfg(not(feature = "multipart"))]
+        let limit = None;
         let multipart = Multipart::from_request(req, state).await?;
-        let files = get_files(multipart).await?;
+        let files = get_files(multipart, limit).await?;
+        #[cfg(feature = "multipart")]
+        let files = validate_files(policy, files)?;
         if files.is_empty() {
             Err(MultipartFileRejection::NoFiles)
         } else {
@@ -178,10 +248,14 @@ where
     }
 }
 
-async fn get_files<'a>(mut multipart: Multipart) -> Result<Vec<File>, MultipartFileRejection> {
+/// Reads the files of the request, failing as soon as a file is larger than the limit.
+async fn get_files(
+    mut multipart: Multipart,
+    limit: Option<u64>,
+) -> Result<Vec<File>, MultipartFileRejection> {
     let mut files = vec![];
     while let Some(field) = multipart.next_field().await? {
-        files.push(File::from_field(field).await?);
+        files.push(File::from_field_with_limit(field, limit.unwrap_or(u64::MAX)).await?);
     }
     if files.is_empty() {
         Err(MultipartFileRejection::NoFiles)
@@ -189,3 +263,40 @@ async fn get_files<'a>(mut multipart: Multipart) -> Result<Vec<File>, MultipartF
         Ok(files)
     }
 }
+
+#[cfg(feature = "multipart")]
+fn upload_policy(req: &Request) -> Option<crate::axum::multipart::UploadPolicy> {
+    req.extensions()
+        .get::<crate::axum::multipart::UploadPolicy>()
+        .cloned()
+}
+
+/// Validates the files with the `UploadPolicy` added to the router, if any.
+#[cfg(feature = "multipart")]
+fn validate_files(
+    policy: Option<crate::axum::multipart::UploadPolicy>,
+    mut files: Vec<File>,
+) -> Result<Vec<File>, MultipartFileRejection> {
+    if let Some(policy) = policy {
+        for file in &mut files {
+            policy.validate_file(file)?;
+        }
+    }
+    Ok(files)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_io_rejection_hides_error() {
+        let error = std::io::Error::other("/var/uploads/secret: permission denied");
+        let response = MultipartFileRejection::Io(error).into_response();
+        assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"Failed to store file");
+    }
+}
@@ -0,0 +1,644 @@
+use {
+    super::store::{IdempotencyError, IdempotencyRecord, IdempotencyStore, StoredResponse},
+    crate::axum::body::buffer,
+    axum::{
+        body::{to_bytes, Body},
+        extract::Request,
+        http::{
+            header::{AUTHORIZATION, CONTENT_TYPE},
+            request::Parts,
+            HeaderName, HeaderValue, Method, StatusCode,
+        },
+        response::{IntoResponse, Response},
+        Json,
+    },
+    futures_util::future::BoxFuture,
+    rand::RngCore,
+    serde_json::json,
+    sha2::{Digest, Sha256},
+    std::{
+        sync::Arc,
+        task::{Context, Poll},
+        time::Duration,
+    },
+    tokio::runtime::Handle,
+    tower::{Layer, Service},
+    tracing::error,
+};
+
+/// The header containing the key chosen by the client.
+pub const IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+/// The header added to replayed responses.
+pub const IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+type Scope = Arc<dyn Fn(&Parts) -> Option<String> + Send + Sync>;
+
+#[derive(Clone)]
+struct IdempotencyConfig {
+    store: Arc<dyn IdempotencyStore>,
+    scope: Scope,
+    methods: Vec<Method>,
+    ttl: Duration,
+    lock_timeout: Duration,
+    required: bool,
+    body_limit: usize,
+}
+
+/// Layer making unsafe requests safe to retry, by honouring the `Idempotency-Key` header.
+///
+/// The first response for a key is stored, and replayed for later requests with the same key,
+/// method, path and body, with the `Idempotent-Replayed: true` header.
+/// Keys are scoped to the client sending them, so clients cannot replay each other's responses.
+/// While the first request is in flight, retries are rejected with 409 Conflict.
+/// Reusing a key with a different body is rejected with 422 Unprocessable Entity.
+/// Server errors are not stored, so the request can be retried.
+/// Responses larger than the body limit are passed through, but cannot be replayed:
+/// retries get 409 Conflict, with the status of the original response in the body.
+/// # Default Options
+/// - Scope == the `Principal` of an `AuthLayer` added after this layer, or the `Authorization` header
+/// - Methods == POST, PATCH
+/// - Time to live == 24 hours
+/// - Lock timeout == 1 minute
+/// - Required == false
+/// - Body limit == 2 MB (applies to both requests and stored responses)
+/// # Example
+/// ```
+/// use lib::axum::idempotency::{IdempotencyLayer, MemoryIdempotencyStore};
+///
+/// let layer = IdempotencyLayer::new(MemoryIdempotencyStore::new());
+/// let _router: axum::Router = lib::routes!(post "/users" => || async {}).layer(layer);
+/// ```
+#[derive(Clone)]
+pub struct IdempotencyLayer {
+    config: Arc<IdempotencyConfig>,
+}
+
+impl IdempotencyLayer {
+    pub fn new(store: impl IdempotencyStore) -> Self {
+        Self {
+            config: Arc::new(IdempotencyConfig {
+                store: Arc::new(store),
+                scope: Arc::new(default_scope),
+                methods: vec![Method::POST, Method::PATCH],
+                ttl: Duration::from_secs(24 * 60 * 60),
+                lock_timeout: Duration::from_secs(60),
+                required: false,
+                body_limit: 2 * 1024 * 1024,
+            }),
+        }
+    }
+
+    fn config(mut self, f: impl FnOnce(&mut IdempotencyConfig)) -> Self {
+        f(Arc::make_mut(&mut self.config));
+        self
+    }
+
+    /// Sets the function identifying the client of a request, e.g. the user id of a session.
+    /// Keys are only shared between requests with the same scope,
+    /// and requests without a scope share the keys of all anonymous clients.
+    pub fn scope(self, scope: impl Fn(&Parts) -> Option<String> + Send + Sync + 'static) -> Self {
+        self.config(|config| config.scope = Arc::new(scope))
+    }
+
+    /// Sets the methods the header is honoured for.
+    pub fn methods(self, methods: impl IntoIterator<Item = Method>) -> Self {
+        self.config(|config| config.methods = methods.into_iter().collect())
+    }
+
+    /// Sets how long responses are stored.
+    pub fn ttl(self, ttl: Duration) -> Self {
+        self.config(|config| config.ttl = ttl)
+    }
+
+    /// Sets how long a key stays locked while its first request is in flight.
+    /// The lock is released early when the request fails or is cancelled,
+    /// the timeout only matters if the server stops before that.
+    pub fn lock_timeout(self, timeout: Duration) -> Self {
+        self.config(|config| config.lock_timeout = timeout)
+    }
+
+    /// Sets whether requests without the header are rejected with 400 Bad Request.
+    pub fn required(self, required: bool) -> Self {
+        self.config(|config| config.required = required)
+    }
+
+    /// Sets the maximum size of request and response bodies.
+    pub fn body_limit(self, limit: usize) -> Self {
+        self.config(|config| config.body_limit = limit)
+    }
+}
+
+impl<S> Layer<S> for IdempotencyLayer {
+    type Service = IdempotencyService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IdempotencyService {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// Service created by the `IdempotencyLayer`.
+#[derive(Clone)]
+pub struct IdempotencyService<S> {
+    inner: S,
+    config: Arc<IdempotencyConfig>,
+}
+
+impl<S> Service<Request> for IdempotencyService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    S::Error: Send,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let config = self.config.clone();
+        Box::pin(async move {
+            if !config.methods.contains(req.method()) {
+                return inner.call(req).await;
+            }
+            let Some(key) = req.headers().get(IDEMPOTENCY_KEY).cloned() else {
+                return if config.required {
+                    Ok(rejection(
+                        StatusCode::BAD_REQUEST,
+                        "Missing Idempotency-Key header",
+                    ))
+                } else {
+                    inner.call(req).await
+                };
+            };
+            let Ok(key) = key.to_str() else {
+                return Ok(rejection(
+                    StatusCode::BAD_REQUEST,
+                    "Invalid Idempotency-Key header",
+                ));
+            };
+            let (parts, body) = req.into_parts();
+            // The scope is hashed, so it cannot contain the separator
+            let scope = (config.scope)(&parts).map_or_else(
+                || "-".to_string(),
+                |scope| format!("{:x}", Sha256::digest(scope)),
+            );
+            let key = format!("{scope} {} {} {key}", parts.method, parts.uri.path());
+
+            let Ok(bytes) = to_bytes(body, config.body_limit).await else {
+                return Ok(rejection(
+                    StatusCode::PAYLOAD_TOO_LARGE,
+                    "Request body is too large",
+                ));
+            };
+            let fingerprint = format!("{:x}", Sha256::digest(&bytes));
+
+            let token = lock_token();
+            match config
+                .store
+                .begin(&key, &fingerprint, &token, config.lock_timeout)
+                .await
+            {
+                Ok(None) => {}
+                Ok(Some(record)) if record.fingerprint() != fingerprint => {
+                    return Ok(rejection(
+                        StatusCode::UNPROCESSABLE_ENTITY,
+                        "Idempotency-Key was used with a different request",
+                    ))
+                }
+                Ok(Some(IdempotencyRecord::InFlight { .. })) => {
+                    return Ok(rejection(
+                        StatusCode::CONFLICT,
+                        "A request with this Idempotency-Key is in progress",
+                    ))
+                }
+                Ok(Some(IdempotencyRecord::Completed { response, .. })) => {
+                    let mut response = response.to_response();
+                    response
+                        .headers_mut()
+                        .insert(IDEMPOTENT_REPLAYED, HeaderValue::from_static("true"));
+                    return Ok(response);
+                }
+                Err(error) => return Ok(store_error(error)),
+            }
+            let lock = KeyLock {
+                store: config.store.clone(),
+                key,
+                token,
+                released: false,
+            };
+
+            let response = match inner
+                .call(Request::from_parts(parts, Body::from(bytes)))
+                .await
+            {
+                Ok(response) => response,
+                Err(error) => {
+                    lock.abort().await;
+                    return Err(error);
+                }
+            };
+            if response.status().is_server_error() {
+                lock.abort().await;
+                return Ok(response);
+            }
+            let (parts, body) = response.into_parts();
+            let (stored, body) = match buffer(body, config.body_limit).await {
+                Ok(bytes) => (
+                    StoredResponse {
+                        status: parts.status.as_u16(),
+                        headers: parts
+                            .headers
+                            .iter()
+                            .filter_map(|(name, value)| {
+                                Some((name.to_string(), value.to_str().ok()?.to_string()))
+                            })
+                            .collect(),
+                        body: bytes.to_vec(),
+                    },
+                    Body::from(bytes),
+                ),
+                Err(body) => (not_replayable(parts.status), body),
+            };
+            lock.complete(&stored, config.ttl).await;
+            Ok(Response::from_parts(parts, body))
+        })
+    }
+}
+
+/// The in-flight lock on a key, released if the request fails or is cancelled.
+struct KeyLock {
+    store: Arc<dyn IdempotencyStore>,
+    key: String,
+    token: String,
+    released: bool,
+}
+
+impl KeyLock {
+    async fn abort(mut self) {
+        self.released = true;
+        if let Err(error) = self.store.abort(&self.key, &self.token).await {
+            error!("Idempotency store error: {error}");
+        }
+    }
+
+    /// Stores the response. The side effects of the request have happened,
+    /// so a failure is logged rather than returned.
+    async fn complete(mut self, response: &StoredResponse, ttl: Duration) {
+        self.released = true;
+        if let Err(error) = self
+            .store
+            .complete(&self.key, &self.token, response, ttl)
+            .await
+        {
+            error!("Idempotency store error: {error}");
+        }
+    }
+}
+
+impl Drop for KeyLock {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        if let Ok(handle) = Handle::try_current() {
+            let store = self.store.clone();
+            let key = std::mem::take(&mut self.key);
+            let token = std::mem::take(&mut self.token);
+            handle.spawn(async move {
+                if let Err(error) = store.abort(&key, &token).await {
+                    error!("Idempotency store error: {error}");
+                }
+            });
+        }
+    }
+}
+
+/// Scopes keys to the `Principal` set by an `AuthLayer`, or else to the `Authorization` header.
+fn default_scope(parts: &Parts) -> Option<String> {
+    #[cfg(feature = "auth")]
+    if let Some(principal) = parts.extensions.get::<crate::axum::auth::Principal>() {
+        return Some(format!("principal {}", principal.id));
+    }
+    let authorization = parts.headers.get(AUTHORIZATION)?.to_str().ok()?;
+    Some(format!("authorization {authorization}"))
+}
+
+/// Generates a random token identifying the request holding the lock on a key.
+fn lock_token() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// The response replayed for a key whose response was too large to be stored.
+fn not_replayable(status: StatusCode) -> StoredResponse {
+    let body = json!({
+        "error": "The response for this Idempotency-Key is too large to be replayed",
+        "status": status.as_u16(),
+    });
+    StoredResponse {
+        status: StatusCode::CONFLICT.as_u16(),
+        headers: vec![(CONTENT_TYPE.to_string(), "application/json".to_string())],
+        body: body.to_string().into_bytes(),
+    }
+}
+
+fn rejection(status: StatusCode, message: &str) -> Response {
+    (status, Json(json!({ "error": message }))).into_response()
+}
+
+fn store_error(error: IdempotencyError) -> Response {
+    error!("Idempotency store error: {error}");
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::axum::idempotency::MemoryIdempotencyStore;
+    use axum::Router;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::sync::Notify;
+    use tower::ServiceExt;
+
+    /// Holds requests to `/slow` until they are released.
+    #[derive(Default)]
+    struct Gate {
+        entered: Notify,
+        release: Notify,
+    }
+
+    fn app(counter: Arc<AtomicUsize>) -> Router {
+        app_with(
+            counter,
+            Arc::default(),
+            IdempotencyLayer::new(MemoryIdempotencyStore::new()),
+        )
+    }
+
+    fn app_with(counter: Arc<AtomicUsize>, gate: Arc<Gate>, layer: IdempotencyLayer) -> Router {
+        crate::routes!(
+            post "/users" => move |body: String| async move {
+                let count = counter.fetch_add(1, Ordering::SeqCst) + 1;
+                (StatusCode::CREATED, format!("{body} {count}"))
+            },
+            post "/slow" => move || async move {
+                gate.entered.notify_one();
+                gate.release.notified().await;
+            },
+            post "/error" => || async { StatusCode::INTERNAL_SERVER_ERROR },
+            post "/large" => || async { "a".repeat(64) }
+        )
+        .layer(layer.body_limit(32))
+    }
+
+    fn request(uri: &str, key: Option<&str>, body: &str) -> Request {
+        let builder = Request::builder().method("POST").uri(uri);
+        match key {
+            Some(key) => builder.header(IDEMPOTENCY_KEY, key),
+            None => builder,
+        }
+        .body(Body::from(body.to_string()))
+        .unwrap()
+    }
+
+    async fn body(response: Response) -> String {
+        let bytes = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_replay() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let app = app(counter.clone());
+        let first = app
+            .clone()
+            .oneshot(request("/users", Some("a"), "user"))
+            .await
+            .unwrap();
+        assert_eq!(first.status(), StatusCode::CREATED);
+        assert_eq!(body(first).await, "user 1");
+
+        let retry = app
+            .oneshot(request("/users", Some("a"), "user"))
+            .await
+            .unwrap();
+        assert_eq!(retry.status(), StatusCode::CREATED);
+        assert_eq!(retry.headers().get(IDEMPOTENT_REPLAYED).unwrap(), "true");
+        assert_eq!(body(retry).await, "user 1");
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn test_without_key() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let app = app(counter.clone());
+        app.clone()
+            .oneshot(request("/users", None, "user"))
+            .await
+            .unwrap();
+        app.oneshot(request("/users", None, "user")).await.unwrap();
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_different_body() {
+        let app = app(Arc::new(AtomicUsize::new(0)));
+        app.clone()
+            .oneshot(request("/users", Some("a"), "user"))
+            .await
+            .unwrap();
+        let response = app
+            .oneshot(request("/users", Some("a"), "other"))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+    }
+
+    #[tokio::test]
+    async fn test_scoped_keys() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let app = app(counter.clone());
+        for authorization in ["Bearer a", "Bearer b"] {
+            let mut request = request("/users", Some("a"), "user");
+            request
+                .headers_mut()
+                .insert(AUTHORIZATION, HeaderValue::from_static(authorization));
+            let response = app.clone().oneshot(request).await.unwrap();
+            assert!(response.headers().get(IDEMPOTENT_REPLAYED).is_none());
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_custom_scope() {
+        let counter = Arc::new(AtomicUsize::new(0));
+        let layer = IdempotencyLayer::new(MemoryIdempotencyStore::new())
+            .scope(|parts| Some(parts.headers.get("x-user")?.to_str().ok()?.to_string()));
+        let app = app_with(counter.clone(), Arc::default(), layer);
+        for user in ["a", "b", "a"] {
+            let mut request = request("/users", Some("a"), "user");
+            request
+                .headers_mut()
+                .insert("x-user", HeaderValue::from_static(user));
+            app.clone().oneshot(request).await.unwrap();
+        }
+        assert_eq!(counter.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_in_flight() {
+        let gate = Arc::new(Gate::default());
+        let app = app_with(
+            Arc::default(),
+            gate.clone(),
+            IdempotencyLayer::new(MemoryIdempotencyStore::new()),
+        );
+        let first = tokio::spawn(app.clone().oneshot(request("/slow", Some("a"), "")));
+        gate.entered.notified().await;
+        let response = app.oneshot(request("/slow", Some("a"), "")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+        gate.release.notify_one();
+        assert_eq!(first.await.unwrap().unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_server_error_is_not_stored() {
+        let app = app(Arc::new(AtomicUsize::new(0)));
+        for _ in 0..2 {
+            let response = app
+                .clone()
+                .oneshot(request("/error", Some("a"), ""))
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+            assert!(response.headers().get(IDEMPOTENT_REPLAYED).is_none());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_required() {
+        let app: Router = crate::routes!(post "/" => || async {})
+            .layer(IdempotencyLayer::new(MemoryIdempotencyStore::new()).required(true));
+        let response = app.oneshot(request("/", None, "")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_large_response() {
+        let app = app(Arc::new(AtomicUsize::new(0)));
+        let first = app
+            .clone()
+            .oneshot(request("/large", Some("a"), ""))
+            .await
+            .unwrap();
+        assert_eq!(first.status(), StatusCode::OK);
+        assert_eq!(body(first).await, "a".repeat(64));
+
+        let retry = app.oneshot(request("/large", Some("a"), "")).await.unwrap();
+        assert_eq!(retry.status(), StatusCode::CONFLICT);
+        assert_eq!(retry.headers().get(IDEMPOTENT_REPLAYED).unwrap(), "true");
+    }
+
+    /// A memory store notifying when a key is released.
+    #[derive(Clone, Default)]
+    struct AbortStore {
+        store: MemoryIdempotencyStore,
+        aborted: Arc<Notify>,
+    }
+
+    #[axum::async_trait]
+    impl IdempotencyStore for AbortStore {
+        async fn begin(
+            &self,
+            key: &str,
+            fingerprint: &str,
+            token: &str,
+            ttl: Duration,
+        ) -> Result<Option<IdempotencyRecord>, IdempotencyError> {
+            self.store.begin(key, fingerprint, token, ttl).await
+        }
+
+        async fn complete(
+            &self,
+            key: &str,
+            token: &str,
+            response: &StoredResponse,
+            ttl: Duration,
+        ) -> Result<(), IdempotencyError> {
+            self.store.complete(key, token, response, ttl).await
+        }
+
+        async fn abort(&self, key: &str, token: &str) -> Result<(), IdempotencyError> {
+            self.store.abort(key, token).await?;
+            self.aborted.notify_one();
+            Ok(())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_cancelled_request_releases_key() {
+        let gate = Arc::new(Gate::default());
+        let store = AbortStore::default();
+        let app = app_with(
+            Arc::default(),
+            gate.clone(),
+            IdempotencyLayer::new(store.clone()),
+        );
+        let first = tokio::spawn(app.clone().oneshot(request("/slow", Some("a"), "")));
+        gate.entered.notified().await;
+        first.abort();
+        let _ = first.await;
+        store.aborted.notified().await;
+        let second = tokio::spawn(app.oneshot(request("/slow", Some("a"), "")));
+        gate.entered.notified().await;
+        gate.release.notify_one();
+        assert_eq!(second.await.unwrap().unwrap().status(), StatusCode::OK);
+    }
+
+    struct FailingStore(MemoryIdempotencyStore);
+
+    #[axum::async_trait]
+    impl IdempotencyStore for FailingStore {
+        async fn begin(
+            &self,
+            key: &str,
+            fingerprint: &str,
+            token: &str,
+            ttl: Duration,
+        ) -> Result<Option<IdempotencyRecord>, IdempotencyError> {
+            self.0.begin(key, fingerprint, token, ttl).await
+        }
+
+        async fn complete(
+            &self,
+            _key: &str,
+            _token: &str,
+            _response: &StoredResponse,
+            _ttl: Duration,
+        ) -> Result<(), IdempotencyError> {
+            Err(IdempotencyError::Store("unavailable".to_string()))
+        }
+
+        async fn abort(&self, key: &str, token: &str) -> Result<(), IdempotencyError> {
+            self.0.abort(key, token).await
+        }
+    }
+
+    #[tokio::test]
+    async fn test_failed_complete_returns_response() {
+        let app: Router = crate::routes!(post "/" => || async { StatusCode::CREATED }).layer(
+            IdempotencyLayer::new(FailingStore(MemoryIdempotencyStore::new())),
+        );
+        let response = app.oneshot(request("/", Some("a"), "")).await.unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+    }
+}
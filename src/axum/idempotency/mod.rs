@@ -0,0 +1,11 @@
+pub mod layer;
+#[cfg(feature = "diesel")]
+pub mod postgres;
+pub mod store;
+
+pub use layer::{IdempotencyLayer, IDEMPOTENCY_KEY, IDEMPOTENT_REPLAYED};
+#[cfg(feature = "diesel")]
+pub use postgres::PgIdempotencyStore;
+pub use store::{
+    IdempotencyError, IdempotencyRecord, IdempotencyStore, MemoryIdempotencyStore, StoredResponse,
+};
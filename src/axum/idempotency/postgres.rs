@@ -0,0 +1,184 @@
+use {
+    super::store::{IdempotencyError, IdempotencyRecord, IdempotencyStore, StoredResponse},
+    crate::diesel::{pool::PgPool, unix_timestamp},
+    axum::async_trait,
+    diesel::{
+        sql_types::{BigInt, Binary, Integer, Nullable, Text},
+        QueryableByName,
+    },
+    diesel_async::RunQueryDsl,
+    std::time::{Duration, SystemTime},
+};
+
+/// An idempotency store persisting records in a PostgreSQL table.
+/// The table can be created with `PgIdempotencyStore::migrate`, or with a migration:
+/// ```sql
+/// CREATE TABLE idempotency_keys (
+///     key         TEXT   PRIMARY KEY,
+///     fingerprint TEXT   NOT NULL,
+///     token       TEXT,
+///     status      INT,
+///     headers     TEXT,
+///     body        BYTEA,
+///     expires_at  BIGINT NOT NULL
+/// );
+/// ```
+#[derive(Clone)]
+pub struct PgIdempotencyStore {
+    pool: PgPool,
+    table: String,
+}
+
+#[derive(QueryableByName)]
+struct IdempotencyRow {
+    #[diesel(sql_type = Text)]
+    fingerprint: String,
+    #[diesel(sql_type = Nullable<Integer>)]
+    status: Option<i32>,
+    #[diesel(sql_type = Nullable<Text>)]
+    headers: Option<String>,
+    #[diesel(sql_type = Nullable<Binary>)]
+    body: Option<Vec<u8>>,
+}
+
+impl PgIdempotencyStore {
+    /// Creates a store using the `idempotency_keys` table.
+    pub fn new(pool: PgPool) -> Self {
+        Self::with_table(pool, "idempotency_keys")
+    }
+
+    /// Creates a store using the given table.
+    /// The table name is not escaped, and must not come from user input.
+    pub fn with_table(pool: PgPool, table: impl Into<String>) -> Self {
+        Self {
+            pool,
+            table: table.into(),
+        }
+    }
+
+    /// Creates the idempotency table, if it does not exist.
+    pub async fn migrate(&self) -> Result<(), IdempotencyError> {
+        let query = format!(
+            "CREATE TABLE IF NOT EXISTS {} (key TEXT PRIMARY KEY, fingerprint TEXT NOT NULL, \
+             token TEXT, status INT, headers TEXT, body BYTEA, expires_at BIGINT NOT NULL)",
+            self.table
+        );
+        let mut conn = self.pool.get().await.map_err(store_error)?;
+        diesel::sql_query(query)
+            .execute(&mut conn)
+            .await
+            .map_err(store_error)?;
+        Ok(())
+    }
+
+    /// Deletes all expired records, returning the number of deleted records.
+    pub async fn delete_expired(&self) -> Result<usize, IdempotencyError> {
+        let query = format!("DELETE FROM {} WHERE expires_at <= $1", self.table);
+        let mut conn = self.pool.get().await.map_err(store_error)?;
+        diesel::sql_query(query)
+            .bind::<BigInt, _>(unix_timestamp(SystemTime::now()))
+            .execute(&mut conn)
+            .await
+            .map_err(store_error)
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for PgIdempotencyStore {
+    async fn begin(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        token: &str,
+        ttl: Duration,
+    ) -> Result<Option<IdempotencyRecord>, IdempotencyError> {
+        let now = SystemTime::now();
+        let mut conn = self.pool.get().await.map_err(store_error)?;
+        // Replaces an expired record, but keeps a live one, in a single statement
+        let query = format!(
+            "INSERT INTO {0} (key, fingerprint, token, expires_at) VALUES ($1, $2, $3, $4) \
+             ON CONFLICT (key) DO UPDATE SET fingerprint = EXCLUDED.fingerprint, \
+             token = EXCLUDED.token, status = NULL, headers = NULL, body = NULL, \
+             expires_at = EXCLUDED.expires_at WHERE {0}.expires_at <= $5",
+            self.table
+        );
+        let inserted = diesel::sql_query(query)
+            .bind::<Text, _>(key)
+            .bind::<Text, _>(fingerprint)
+            .bind::<Text, _>(token)
+            .bind::<BigInt, _>(unix_timestamp(now + ttl))
+            .bind::<BigInt, _>(unix_timestamp(now))
+            .execute(&mut conn)
+            .await
+            .map_err(store_error)?;
+        if inserted == 1 {
+            return Ok(None);
+        }
+        let query = format!(
+            "SELECT fingerprint, status, headers, body FROM {} WHERE key = $1",
+            self.table
+        );
+        let row = diesel::sql_query(query)
+            .bind::<Text, _>(key)
+            .get_results::<IdempotencyRow>(&mut conn)
+            .await
+            .map_err(store_error)?
+            .pop()
+            .ok_or_else(|| IdempotencyError::Store("Record was deleted".to_string()))?;
+        Ok(Some(match (row.status, row.headers, row.body) {
+            (Some(status), Some(headers), Some(body)) => IdempotencyRecord::Completed {
+                fingerprint: row.fingerprint,
+                response: StoredResponse {
+                    status: status as u16,
+                    headers: serde_json::from_str(&headers)?,
+                    body,
+                },
+            },
+            _ => IdempotencyRecord::InFlight {
+                fingerprint: row.fingerprint,
+            },
+        }))
+    }
+
+    async fn complete(
+        &self,
+        key: &str,
+        token: &str,
+        response: &StoredResponse,
+        ttl: Duration,
+    ) -> Result<(), IdempotencyError> {
+        let query = format!(
+            "UPDATE {} SET token = NULL, status = $3, headers = $4, body = $5, expires_at = $6 \
+             WHERE key = $1 AND token = $2",
+            self.table
+        );
+        let mut conn = self.pool.get().await.map_err(store_error)?;
+        diesel::sql_query(query)
+            .bind::<Text, _>(key)
+            .bind::<Text, _>(token)
+            .bind::<Integer, _>(response.status as i32)
+            .bind::<Text, _>(serde_json::to_string(&response.headers)?)
+            .bind::<Binary, _>(&response.body)
+            .bind::<BigInt, _>(unix_timestamp(SystemTime::now() + ttl))
+            .execute(&mut conn)
+            .await
+            .map_err(store_error)?;
+        Ok(())
+    }
+
+    async fn abort(&self, key: &str, token: &str) -> Result<(), IdempotencyError> {
+        let query = format!("DELETE FROM {} WHERE key = $1 AND token = $2", self.table);
+        let mut conn = self.pool.get().await.map_err(store_error)?;
+        diesel::sql_query(query)
+            .bind::<Text, _>(key)
+            .bind::<Text, _>(token)
+            .execute(&mut conn)
+            .await
+            .map_err(store_error)?;
+        Ok(())
+    }
+}
+
+fn store_error(error: impl ToString) -> IdempotencyError {
+    IdempotencyError::Store(error.to_string())
+}
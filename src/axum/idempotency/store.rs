@@ -0,0 +1,294 @@
+use {
+    axum::{
+        async_trait,
+        body::Body,
+        http::{HeaderName, HeaderValue, StatusCode},
+        response::Response,
+    },
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex},
+        time::{Duration, Instant},
+    },
+    thiserror::Error,
+};
+
+/// A response stored to be replayed for retries of the same request.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredResponse {
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    pub body: Vec<u8>,
+}
+
+impl StoredResponse {
+    /// Converts the stored response back into a response.
+    /// Headers that are no longer valid are skipped.
+    pub fn to_response(&self) -> Response {
+        let mut response = Response::new(Body::from(self.body.clone()));
+        *response.status_mut() =
+            StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        let headers = response.headers_mut();
+        for (name, value) in &self.headers {
+            if let (Ok(name), Ok(value)) = (
+                HeaderName::try_from(name.as_str()),
+                HeaderValue::try_from(value.as_str()),
+            ) {
+                headers.append(name, value);
+            }
+        }
+        response
+    }
+}
+
+/// The state of a previously seen idempotency key.
+/// The fingerprint identifies the request body the key was first used with.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum IdempotencyRecord {
+    /// The first request with the key is still being handled.
+    InFlight { fingerprint: String },
+    /// The first request with the key has completed with the given response.
+    Completed {
+        fingerprint: String,
+        response: StoredResponse,
+    },
+}
+
+impl IdempotencyRecord {
+    pub fn fingerprint(&self) -> &str {
+        match self {
+            IdempotencyRecord::InFlight { fingerprint }
+            | IdempotencyRecord::Completed { fingerprint, .. } => fingerprint,
+        }
+    }
+}
+
+/// Error type for idempotency stores.
+#[derive(Debug, Error)]
+pub enum IdempotencyError {
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+    #[error("Idempotency store error: {0}")]
+    Store(String),
+}
+
+/// Storage for idempotency keys and their responses.
+/// Records expire after the given time to live, after which the key can be reused.
+///
+/// A key reserved with `begin` is locked with a token, unique to the request holding it.
+/// `complete` and `abort` only change the record while it is locked with the same token,
+/// so a request whose lock has expired cannot overwrite or release the lock of a later request.
+///
+/// Implementing the trait requires the `async_trait` macro.
+#[async_trait]
+pub trait IdempotencyStore: Send + Sync + 'static {
+    /// Atomically marks the key as in flight and locks it with the token, if it is not already known.
+    /// Returns `None` if the key was reserved, or the existing record otherwise.
+    async fn begin(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        token: &str,
+        ttl: Duration,
+    ) -> Result<Option<IdempotencyRecord>, IdempotencyError>;
+    /// Stores the response for a key reserved with `begin`, if it is still locked with the token.
+    async fn complete(
+        &self,
+        key: &str,
+        token: &str,
+        response: &StoredResponse,
+        ttl: Duration,
+    ) -> Result<(), IdempotencyError>;
+    /// Releases a key reserved with `begin`, if it is still locked with the token,
+    /// so the request can be retried.
+    async fn abort(&self, key: &str, token: &str) -> Result<(), IdempotencyError>;
+}
+
+/// An idempotency store keeping all records in memory.
+/// Records are lost when the application restarts, and are not shared between instances.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryIdempotencyStore {
+    records: Arc<Mutex<HashMap<String, MemoryRecord>>>,
+}
+
+#[derive(Debug)]
+struct MemoryRecord {
+    record: IdempotencyRecord,
+    /// The token of the request holding the lock, while the record is in flight.
+    token: Option<String>,
+    expires_at: Instant,
+}
+
+impl MemoryRecord {
+    fn is_locked_by(&self, token: &str) -> bool {
+        self.token.as_deref() == Some(token)
+    }
+}
+
+impl MemoryIdempotencyStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes all expired records from the store.
+    pub fn delete_expired(&self) {
+        let now = Instant::now();
+        self.records
+            .lock()
+            .expect("Idempotency store lock poisoned")
+            .retain(|_, record| record.expires_at > now);
+    }
+}
+
+#[async_trait]
+impl IdempotencyStore for MemoryIdempotencyStore {
+    async fn begin(
+        &self,
+        key: &str,
+        fingerprint: &str,
+        token: &str,
+        ttl: Duration,
+    ) -> Result<Option<IdempotencyRecord>, IdempotencyError> {
+        let mut records = self
+            .records
+            .lock()
+            .expect("Idempotency store lock poisoned");
+        match records.get(key) {
+            Some(record) if record.expires_at > Instant::now() => Ok(Some(record.record.clone())),
+            _ => {
+                let record = MemoryRecord {
+                    record: IdempotencyRecord::InFlight {
+                        fingerprint: fingerprint.to_string(),
+                    },
+                    token: Some(token.to_string()),
+                    expires_at: Instant::now() + ttl,
+                };
+                records.insert(key.to_string(), record);
+                Ok(None)
+            }
+        }
+    }
+
+    async fn complete(
+        &self,
+        key: &str,
+        token: &str,
+        response: &StoredResponse,
+        ttl: Duration,
+    ) -> Result<(), IdempotencyError> {
+        let mut records = self
+            .records
+            .lock()
+            .expect("Idempotency store lock poisoned");
+        if let Some(record) = records
+            .get_mut(key)
+            .filter(|record| record.is_locked_by(token))
+        {
+            record.record = IdempotencyRecord::Completed {
+                fingerprint: record.record.fingerprint().to_string(),
+                response: response.clone(),
+            };
+            record.token = None;
+            record.expires_at = Instant::now() + ttl;
+        }
+        Ok(())
+    }
+
+    async fn abort(&self, key: &str, token: &str) -> Result<(), IdempotencyError> {
+        let mut records = self
+            .records
+            .lock()
+            .expect("Idempotency store lock poisoned");
+        if records
+            .get(key)
+            .is_some_and(|record| record.is_locked_by(token))
+        {
+            records.remove(key);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TTL: Duration = Duration::from_secs(60);
+
+    fn response() -> StoredResponse {
+        StoredResponse {
+            status: 201,
+            headers: vec![("content-type".to_string(), "text/plain".to_string())],
+            body: b"created".to_vec(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = MemoryIdempotencyStore::new();
+        assert_eq!(store.begin("key", "a", "1", TTL).await.unwrap(), None);
+        assert_eq!(
+            store.begin("key", "a", "2", TTL).await.unwrap(),
+            Some(IdempotencyRecord::InFlight {
+                fingerprint: "a".to_string()
+            })
+        );
+        store.complete("key", "1", &response(), TTL).await.unwrap();
+        assert_eq!(
+            store.begin("key", "a", "2", TTL).await.unwrap(),
+            Some(IdempotencyRecord::Completed {
+                fingerprint: "a".to_string(),
+                response: response()
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_abort() {
+        let store = MemoryIdempotencyStore::new();
+        store.begin("key", "a", "1", TTL).await.unwrap();
+        store.abort("key", "1").await.unwrap();
+        assert_eq!(store.begin("key", "b", "2", TTL).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_expired_lock() {
+        let store = MemoryIdempotencyStore::new();
+        store.begin("key", "a", "1", Duration::ZERO).await.unwrap();
+        store.begin("key", "a", "2", TTL).await.unwrap();
+        // The first request's lock expired, so it cannot release or complete the second's
+        store.abort("key", "1").await.unwrap();
+        store.complete("key", "1", &response(), TTL).await.unwrap();
+        assert_eq!(
+            store.begin("key", "a", "3", TTL).await.unwrap(),
+            Some(IdempotencyRecord::InFlight {
+                fingerprint: "a".to_string()
+            })
+        );
+        store.abort("key", "2").await.unwrap();
+        assert_eq!(store.begin("key", "a", "3", TTL).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_expired() {
+        let store = MemoryIdempotencyStore::new();
+        store.begin("key", "a", "1", Duration::ZERO).await.unwrap();
+        assert_eq!(store.begin("key", "a", "2", TTL).await.unwrap(), None);
+        store
+            .begin("other", "a", "3", Duration::ZERO)
+            .await
+            .unwrap();
+        store.delete_expired();
+        assert_eq!(store.records.lock().unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_to_response() {
+        let response = response().to_response();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            response.headers().get("content-type").unwrap(),
+            "text/plain"
+        );
+    }
+}
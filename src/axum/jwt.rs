@@ -0,0 +1,727 @@
+use {
+    axum::{
+        async_trait,
+        extract::{FromRequestParts, Request},
+        http::{
+            header::{AUTHORIZATION, WWW_AUTHENTICATE},
+            request::Parts,
+            HeaderMap, HeaderValue, StatusCode,
+        },
+        response::{IntoResponse, Response},
+        Json,
+    },
+    futures_util::future::BoxFuture,
+    jsonwebtoken::{
+        decode, decode_header,
+        errors::ErrorKind,
+        jwk::{Jwk, JwkSet, KeyAlgorithm},
+        Algorithm, DecodingKey, TokenData, Validation,
+    },
+    serde::de::DeserializeOwned,
+    serde_json::json,
+    std::{
+        marker::PhantomData,
+        path::Path,
+        sync::{Arc, RwLock},
+        task::{Context, Poll},
+        time::{Duration, Instant},
+    },
+    thiserror::Error,
+    tower::{Layer, Service},
+};
+
+/// Fetches a JSON Web Key Set from a remote location, e.g. the `jwks_uri` of an identity provider.
+///
+/// Implementing the trait requires the `async_trait` macro.
+/// # Example
+/// ```
+/// use axum::async_trait;
+/// use jsonwebtoken::jwk::JwkSet;
+/// use lib::axum::jwt::{JwksFetcher, JwtError};
+///
+/// struct StaticFetcher(JwkSet);
+///
+/// #[async_trait]
+/// impl JwksFetcher for StaticFetcher {
+///     async fn fetch(&self) -> Result<JwkSet, JwtError> {
+///         Ok(self.0.clone())
+///     }
+/// }
+/// ```
+#[async_trait]
+pub trait JwksFetcher: Send + Sync + 'static {
+    async fn fetch(&self) -> Result<JwkSet, JwtError>;
+}
+
+/// Error type for configuring a `JwtAuth`, or for fetching keys.
+#[derive(Debug, Error)]
+pub enum JwtError {
+    #[error(transparent)]
+    Io(#[from] std::io::Error),
+    #[error(transparent)]
+    Json(#[from] serde_json::Error),
+    #[error(transparent)]
+    Jwt(#[from] jsonwebtoken::errors::Error),
+    #[error("Failed to fetch keys: {0}")]
+    Fetch(String),
+}
+
+/// Rejection type for the `Claims` extractor and the `JwtLayer`.
+/// Responds with a JSON body containing the error message.
+#[derive(Debug, Error)]
+pub enum JwtRejection {
+    #[error("Missing bearer token")]
+    MissingToken,
+    #[error("Invalid token: {0}")]
+    InvalidToken(String),
+    #[error("Token has expired")]
+    Expired,
+    #[error("Token is not valid yet")]
+    NotYetValid,
+    #[error("No key found for the token")]
+    UnknownKey,
+    #[error("Token is not valid for this audience")]
+    InvalidAudience,
+    #[error("Token was not issued by a trusted issuer")]
+    InvalidIssuer,
+    #[error("Authentication is not configured")]
+    MissingConfiguration,
+    #[error("Unable to load keys: {0}")]
+    KeyUnavailable(String),
+}
+
+impl JwtRejection {
+    /// The status code of the rejection.
+    /// Tokens that are valid, but not meant for this service, are forbidden, other errors are unauthorized.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            JwtRejection::InvalidAudience | JwtRejection::InvalidIssuer => StatusCode::FORBIDDEN,
+            JwtRejection::MissingConfiguration | JwtRejection::KeyUnavailable(_) => {
+                StatusCode::INTERNAL_SERVER_ERROR
+            }
+            _ => StatusCode::UNAUTHORIZED,
+        }
+    }
+}
+
+impl From<jsonwebtoken::errors::Error> for JwtRejection {
+    fn from(error: jsonwebtoken::errors::Error) -> Self {
+        match error.kind() {
+            ErrorKind::ExpiredSignature => JwtRejection::Expired,
+            ErrorKind::ImmatureSignature => JwtRejection::NotYetValid,
+            ErrorKind::InvalidAudience => JwtRejection::InvalidAudience,
+            ErrorKind::InvalidIssuer => JwtRejection::InvalidIssuer,
+            _ => JwtRejection::InvalidToken(error.to_string()),
+        }
+    }
+}
+
+impl IntoResponse for JwtRejection {
+    fn into_response(self) -> Response {
+        let status = self.status();
+        let body = Json(json!({ "error": self.to_string() }));
+        if status == StatusCode::UNAUTHORIZED {
+            let challenge = match self {
+                JwtRejection::MissingToken => HeaderValue::from_static("Bearer"),
+                _ => HeaderValue::from_static("Bearer error=\"invalid_token\""),
+            };
+            (status, [(WWW_AUTHENTICATE, challenge)], body).into_response()
+        } else {
+            (status, body).into_response()
+        }
+    }
+}
+
+enum KeySource {
+    Static(DecodingKey),
+    Jwks(JwkSet),
+    Remote(RemoteJwks),
+}
+
+struct RemoteJwks {
+    fetcher: Box<dyn JwksFetcher>,
+    cache: RwLock<Option<(JwkSet, Instant)>>,
+    refresh_interval: Duration,
+}
+
+impl RemoteJwks {
+    /// Finds the key with the given id, refreshing the key set if the key is unknown
+    /// and the cache is older than the refresh interval.
+    async fn find(&self, kid: &str) -> Result<Jwk, JwtRejection> {
+        let stale = {
+            let cache = self.cache.read().expect("JWKS cache lock poisoned");
+            match cache.as_ref() {
+                Some((set, fetched_at)) => match set.find(kid) {
+                    Some(jwk) => return Ok(jwk.clone()),
+                    None => fetched_at.elapsed() >= self.refresh_interval,
+                },
+                None => true,
+            }
+        };
+        if !stale {
+            return Err(JwtRejection::UnknownKey);
+        }
+        let set = self
+            .fetcher
+            .fetch()
+            .await
+            .map_err(|error| JwtRejection::KeyUnavailable(error.to_string()))?;
+        let jwk = set.find(kid).cloned();
+        *self.cache.write().expect("JWKS cache lock poisoned") = Some((set, Instant::now()));
+        jwk.ok_or(JwtRejection::UnknownKey)
+    }
+}
+
+/// Configuration for validating bearer tokens.
+/// Add it to the router with `Extension` to use the `Claims` extractor,
+/// or wrap it in a `JwtLayer` to protect all routes of a router.
+///
+/// By default, the `exp` claim is required, and `exp` and `nbf` are validated with a leeway of 60 seconds.
+/// The `aud` and `iss` claims are only validated if an audience or issuer is given.
+/// # Example
+/// ```
+/// use axum::Extension;
+/// use lib::axum::jwt::JwtAuth;
+///
+/// let auth = JwtAuth::hs256("secret").audience(["my-api"]).leeway(30);
+/// let _router: axum::Router = lib::routes!(get "/" => || async {}).layer(Extension(auth));
+/// ```
+#[derive(Clone)]
+pub struct JwtAuth {
+    keys: Arc<KeySource>,
+    validation: Validation,
+}
+
+impl JwtAuth {
+    fn new(keys: KeySource, algorithms: Vec<Algorithm>) -> Self {
+        let mut validation = Validation::new(algorithms[0]);
+        validation.algorithms = algorithms;
+        validation.validate_nbf = true;
+        validation.validate_aud = false;
+        Self {
+            keys: Arc::new(keys),
+            validation,
+        }
+    }
+
+    /// Validates tokens signed with HS256 using the given shared secret.
+    pub fn hs256(secret: impl AsRef<[u8]>) -> Self {
+        Self::new(
+            KeySource::Static(DecodingKey::from_secret(secret.as_ref())),
+            vec![Algorithm::HS256],
+        )
+    }
+
+    /// Validates tokens signed with RS256 using the given PEM encoded RSA public key.
+    pub fn rs256(public_key_pem: impl AsRef<[u8]>) -> Result<Self, JwtError> {
+        Ok(Self::new(
+            KeySource::Static(DecodingKey::from_rsa_pem(public_key_pem.as_ref())?),
+            vec![Algorithm::RS256],
+        ))
+    }
+
+    /// Validates tokens signed with ES256 using the given PEM encoded EC public key.
+    pub fn es256(public_key_pem: impl AsRef<[u8]>) -> Result<Self, JwtError> {
+        Ok(Self::new(
+            KeySource::Static(DecodingKey::from_ec_pem(public_key_pem.as_ref())?),
+            vec![Algorithm::ES256],
+        ))
+    }
+
+    /// Validates tokens using the key in the set matching the `kid` header of the token.
+    /// Accepts RS256 and ES256 by default.
+    pub fn jwks(set: JwkSet) -> Self {
+        Self::new(
+            KeySource::Jwks(set),
+            vec![Algorithm::RS256, Algorithm::ES256],
+        )
+    }
+
+    /// Loads a JSON Web Key Set from the given file path.
+    /// # Errors
+    /// If the file cannot be read, or does not contain a valid key set.
+    pub async fn jwks_from_file(path: impl AsRef<Path>) -> Result<Self, JwtError> {
+        let content = tokio::fs::read(path).await?;
+        Ok(Self::jwks(serde_json::from_slice(&content)?))
+    }
+
+    /// Validates tokens using keys fetched by the given fetcher.
+    /// The keys are fetched on first use, and refetched when a token references an unknown key,
+    /// at most once every 5 minutes.
+    pub fn jwks_fetcher(fetcher: impl JwksFetcher) -> Self {
+        Self::jwks_fetcher_with_refresh(fetcher, Duration::from_secs(300))
+    }
+
+    /// Validates tokens using keys fetched by the given fetcher.
+    /// Unknown keys trigger a refetch at most once per `refresh_interval`.
+    pub fn jwks_fetcher_with_refresh(
+        fetcher: impl JwksFetcher,
+        refresh_interval: Duration,
+    ) -> Self {
+        Self::new(
+            KeySource::Remote(RemoteJwks {
+                fetcher: Box::new(fetcher),
+                cache: RwLock::new(None),
+                refresh_interval,
+            }),
+            vec![Algorithm::RS256, Algorithm::ES256],
+        )
+    }
+
+    /// Sets the accepted algorithms, replacing the defaults.
+    pub fn algorithms(mut self, algorithms: impl IntoIterator<Item = Algorithm>) -> Self {
+        self.validation.algorithms = algorithms.into_iter().collect();
+        self
+    }
+
+    /// Requires the `aud` claim to contain one of the given audiences.
+    pub fn audience<T: ToString>(mut self, audience: impl IntoIterator<Item = T>) -> Self {
+        let audience = audience.into_iter().collect::<Vec<_>>();
+        self.validation.set_audience(&audience);
+        self.validation.validate_aud = true;
+        self.require_claim("aud")
+    }
+
+    /// Requires the `iss` claim to be one of the given issuers.
+    pub fn issuer<T: ToString>(mut self, issuer: impl IntoIterator<Item = T>) -> Self {
+        let issuer = issuer.into_iter().collect::<Vec<_>>();
+        self.validation.set_issuer(&issuer);
+        self.require_claim("iss")
+    }
+
+    /// Sets the leeway in seconds used when validating `exp` and `nbf`. Default is 60.
+    pub fn leeway(mut self, seconds: u64) -> Self {
+        self.validation.leeway = seconds;
+        self
+    }
+
+    /// Sets whether the `nbf` claim should be validated. Default is true.
+    pub fn validate_nbf(mut self, validate_nbf: bool) -> Self {
+        self.validation.validate_nbf = validate_nbf;
+        self
+    }
+
+    fn require_claim(mut self, claim: &str) -> Self {
+        self.validation
+            .required_spec_claims
+            .insert(claim.to_string());
+        self
+    }
+
+    /// Decodes and validates the given token.
+    pub async fn decode<T: DeserializeOwned>(
+        &self,
+        token: &str,
+    ) -> Result<TokenData<T>, JwtRejection> {
+        let header = decode_header(token)?;
+        if !self.validation.algorithms.contains(&header.alg) {
+            return Err(JwtRejection::InvalidToken(
+                "Algorithm is not allowed".to_string(),
+            ));
+        }
+        let key = match self.keys.as_ref() {
+            KeySource::Static(key) => return Ok(decode(token, key, &self.validation)?),
+            KeySource::Jwks(set) => {
+                let kid = header.kid.as_deref().ok_or(JwtRejection::UnknownKey)?;
+                set.find(kid).cloned().ok_or(JwtRejection::UnknownKey)?
+            }
+            KeySource::Remote(remote) => {
+                let kid = header.kid.as_deref().ok_or(JwtRejection::UnknownKey)?;
+                remote.find(kid).await?
+            }
+        };
+        if let Some(algorithm) = key.common.key_algorithm {
+            if signing_algorithm(algorithm) != Some(header.alg) {
+                return Err(JwtRejection::InvalidToken(
+                    "Algorithm does not match the key".to_string(),
+                ));
+            }
+        }
+        let mut validation = self.validation.clone();
+        validation.algorithms = vec![header.alg];
+        Ok(decode(token, &DecodingKey::from_jwk(&key)?, &validation)?)
+    }
+
+    /// Extracts the bearer token from the `Authorization` header, and decodes it.
+    pub async fn authorize<T: DeserializeOwned>(
+        &self,
+        headers: &HeaderMap,
+    ) -> Result<T, JwtRejection> {
+        let token = bearer_token(headers).ok_or(JwtRejection::MissingToken)?;
+        self.decode(token).await.map(|data| data.claims)
+    }
+}
+
+/// Returns the signing algorithm of a JWK `alg`, or `None` for encryption algorithms.
+fn signing_algorithm(algorithm: KeyAlgorithm) -> Option<Algorithm> {
+    match algorithm {
+        KeyAlgorithm::HS256 => Some(Algorithm::HS256),
+        KeyAlgorithm::HS384 => Some(Algorithm::HS384),
+        KeyAlgorithm::HS512 => Some(Algorithm::HS512),
+        KeyAlgorithm::ES256 => Some(Algorithm::ES256),
+        KeyAlgorithm::ES384 => Some(Algorithm::ES384),
+        KeyAlgorithm::RS256 => Some(Algorithm::RS256),
+        KeyAlgorithm::RS384 => Some(Algorithm::RS384),
+        KeyAlgorithm::RS512 => Some(Algorithm::RS512),
+        KeyAlgorithm::PS256 => Some(Algorithm::PS256),
+        KeyAlgorithm::PS384 => Some(Algorithm::PS384),
+        KeyAlgorithm::PS512 => Some(Algorithm::PS512),
+        KeyAlgorithm::EdDSA => Some(Algorithm::EdDSA),
+        KeyAlgorithm::RSA1_5 | KeyAlgorithm::RSA_OAEP | KeyAlgorithm::RSA_OAEP_256 => None,
+    }
+}
+
+/// Returns the token of an `Authorization: Bearer <token>` header, if present.
+pub fn bearer_token(headers: &HeaderMap) -> Option<&str> {
+    let value = headers.get(AUTHORIZATION)?.to_str().ok()?;
+    let (scheme, token) = value.split_once(' ')?;
+    if scheme.eq_ignore_ascii_case("bearer") && !token.trim().is_empty() {
+        Some(token.trim())
+    } else {
+        None
+    }
+}
+
+/// Extractor for the validated claims of a bearer token.
+/// Requires a `JwtAuth` added as an `Extension`, or a `JwtLayer` on the router.
+/// Use `Option<Claims<T>>` for routes where authentication is optional.
+/// # Example
+/// ```
+/// use lib::axum::jwt::Claims;
+/// use serde::Deserialize;
+///
+/// #[derive(Clone, Deserialize)]
+/// struct User {
+///     sub: String,
+/// }
+///
+/// async fn me(Claims(user): Claims<User>) -> String {
+///     user.sub
+/// }
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct Claims<T>(pub T);
+
+#[async_trait]
+impl<S, T> FromRequestParts<S> for Claims<T>
+where
+    S: Send + Sync,
+    T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    type Rejection = JwtRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        if let Some(claims) = parts.extensions.get::<Claims<T>>() {
+            return Ok(claims.clone());
+        }
+        let auth = parts
+            .extensions
+            .get::<JwtAuth>()
+            .cloned()
+            .ok_or(JwtRejection::MissingConfiguration)?;
+        auth.authorize(&parts.headers).await.map(Claims)
+    }
+}
+
+/// Layer that rejects all requests without a valid bearer token.
+/// The decoded claims are available to handlers through the `Claims` extractor.
+/// # Example
+/// ```
+/// use lib::axum::jwt::{JwtAuth, JwtLayer};
+/// use lib::router;
+/// use serde::Deserialize;
+///
+/// #[derive(Clone, Deserialize)]
+/// struct User {
+///     sub: String,
+/// }
+///
+/// router!(get "/" => || async {});
+///
+/// let _router: axum::Router = router().layer(JwtLayer::<User>::new(JwtAuth::hs256("secret")));
+/// ```
+pub struct JwtLayer<T> {
+    auth: JwtAuth,
+    _claims: PhantomData<fn() -> T>,
+}
+
+impl<T> JwtLayer<T> {
+    pub fn new(auth: JwtAuth) -> Self {
+        Self {
+            auth,
+            _claims: PhantomData,
+        }
+    }
+}
+
+impl<T> Clone for JwtLayer<T> {
+    fn clone(&self) -> Self {
+        Self::new(self.auth.clone())
+    }
+}
+
+impl<S, T> Layer<S> for JwtLayer<T> {
+    type Service = JwtService<S, T>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        JwtService {
+            inner,
+            auth: self.auth.clone(),
+            _claims: PhantomData,
+        }
+    }
+}
+
+/// Service created by the `JwtLayer`.
+pub struct JwtService<S, T> {
+    inner: S,
+    auth: JwtAuth,
+    _claims: PhantomData<fn() -> T>,
+}
+
+impl<S: Clone, T> Clone for JwtService<S, T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            auth: self.auth.clone(),
+            _claims: PhantomData,
+        }
+    }
+}
+
+impl<S, T> Service<Request> for JwtService<S, T>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+    T: DeserializeOwned + Clone + Send + Sync + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let auth = self.auth.clone();
+        Box::pin(async move {
+            match auth.authorize::<T>(req.headers()).await {
+                Ok(claims) => {
+                    req.extensions_mut().insert(Claims(claims));
+                    req.extensions_mut().insert(auth);
+                    inner.call(req).await
+                }
+                Err(rejection) => Ok(rejection.into_response()),
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{body::Body, http::Request, Extension, Router};
+    use jsonwebtoken::{encode, get_current_timestamp, EncodingKey, Header};
+    use serde::{Deserialize, Serialize};
+    use tower::ServiceExt;
+
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    struct TestClaims {
+        sub: String,
+        exp: u64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        aud: Option<String>,
+    }
+
+    fn claims(exp: u64) -> TestClaims {
+        TestClaims {
+            sub: "user".to_string(),
+            exp,
+            aud: None,
+        }
+    }
+
+    fn token(claims: &TestClaims, header: &Header) -> String {
+        encode(header, claims, &EncodingKey::from_secret(b"secret")).unwrap()
+    }
+
+    fn request(token: Option<&str>) -> Request<Body> {
+        let builder = Request::builder().uri("/");
+        match token {
+            Some(token) => builder.header(AUTHORIZATION, format!("Bearer {token}")),
+            None => builder,
+        }
+        .body(Body::empty())
+        .unwrap()
+    }
+
+    fn app(auth: JwtAuth) -> Router {
+        Router::new()
+            .route(
+                "/",
+                axum::routing::get(|Claims(claims): Claims<TestClaims>| async move { claims.sub }),
+            )
+            .layer(Extension(auth))
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let mut headers = HeaderMap::new();
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("bearer abc"));
+        assert_eq!(bearer_token(&headers), Some("abc"));
+        headers.insert(AUTHORIZATION, HeaderValue::from_static("Basic abc"));
+        assert_eq!(bearer_token(&headers), None);
+    }
+
+    #[tokio::test]
+    async fn test_decode_hs256() {
+        let claims = claims(get_current_timestamp() + 60);
+        let token = token(&claims, &Header::default());
+        let data = JwtAuth::hs256("secret")
+            .decode::<TestClaims>(&token)
+            .await
+            .unwrap();
+        assert_eq!(data.claims, claims);
+    }
+
+    #[tokio::test]
+    async fn test_decode_wrong_secret() {
+        let token = token(&claims(get_current_timestamp() + 60), &Header::default());
+        let result = JwtAuth::hs256("other").decode::<TestClaims>(&token).await;
+        assert!(matches!(result, Err(JwtRejection::InvalidToken(_))));
+    }
+
+    #[tokio::test]
+    async fn test_decode_expired_with_leeway() {
+        let token = token(&claims(get_current_timestamp() - 30), &Header::default());
+        let auth = JwtAuth::hs256("secret");
+        assert!(auth.clone().decode::<TestClaims>(&token).await.is_ok());
+        let result = auth.leeway(0).decode::<TestClaims>(&token).await;
+        assert!(matches!(result, Err(JwtRejection::Expired)));
+    }
+
+    #[tokio::test]
+    async fn test_decode_audience() {
+        let mut claims = claims(get_current_timestamp() + 60);
+        claims.aud = Some("other".to_string());
+        let token = token(&claims, &Header::default());
+        let result = JwtAuth::hs256("secret")
+            .audience(["api"])
+            .decode::<TestClaims>(&token)
+            .await;
+        assert!(matches!(result, Err(JwtRejection::InvalidAudience)));
+    }
+
+    #[tokio::test]
+    async fn test_decode_jwks() {
+        let set: JwkSet = serde_json::from_value(json!({
+            "keys": [{ "kty": "oct", "kid": "key-1", "alg": "HS256", "k": "c2VjcmV0" }]
+        }))
+        .unwrap();
+        let mut header = Header {
+            kid: Some("key-1".to_string()),
+            ..Header::default()
+        };
+        let token = token(&claims(get_current_timestamp() + 60), &header);
+        let auth = JwtAuth::jwks(set).algorithms([Algorithm::HS256]);
+        assert!(auth.decode::<TestClaims>(&token).await.is_ok());
+
+        header.kid = Some("key-2".to_string());
+        let token = self::token(&claims(get_current_timestamp() + 60), &header);
+        let result = auth.decode::<TestClaims>(&token).await;
+        assert!(matches!(result, Err(JwtRejection::UnknownKey)));
+    }
+
+    #[tokio::test]
+    async fn test_decode_jwks_algorithm_mismatch() {
+        let set: JwkSet = serde_json::from_value(json!({
+            "keys": [{ "kty": "oct", "kid": "key-1", "alg": "HS384", "k": "c2VjcmV0" }]
+        }))
+        .unwrap();
+        let header = Header {
+            kid: Some("key-1".to_string()),
+            ..Header::default()
+        };
+        let token = token(&claims(get_current_timestamp() + 60), &header);
+        let auth = JwtAuth::jwks(set).algorithms([Algorithm::HS256, Algorithm::HS384]);
+        let result = auth.decode::<TestClaims>(&token).await;
+        assert!(matches!(result, Err(JwtRejection::InvalidToken(_))));
+    }
+
+    #[tokio::test]
+    async fn test_decode_jwks_fetcher() {
+        struct Fetcher;
+
+        #[async_trait]
+        impl JwksFetcher for Fetcher {
+            async fn fetch(&self) -> Result<JwkSet, JwtError> {
+                Ok(serde_json::from_value(json!({
+                    "keys": [{ "kty": "oct", "kid": "key-1", "k": "c2VjcmV0" }]
+                }))?)
+            }
+        }
+
+        let header = Header {
+            kid: Some("key-1".to_string()),
+            ..Header::default()
+        };
+        let token = token(&claims(get_current_timestamp() + 60), &header);
+        let auth = JwtAuth::jwks_fetcher(Fetcher).algorithms([Algorithm::HS256]);
+        assert!(auth.decode::<TestClaims>(&token).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_jwks_from_file_not_found() {
+        assert!(JwtAuth::jwks_from_file("not_found.json").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_extractor() {
+        let token = token(&claims(get_current_timestamp() + 60), &Header::default());
+        let response = app(JwtAuth::hs256("secret"))
+            .oneshot(request(Some(&token)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_extractor_missing_token() {
+        let response = app(JwtAuth::hs256("secret"))
+            .oneshot(request(None))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+        assert!(response.headers().contains_key(WWW_AUTHENTICATE));
+    }
+
+    #[tokio::test]
+    async fn test_extractor_invalid_audience() {
+        let mut claims = claims(get_current_timestamp() + 60);
+        claims.aud = Some("other".to_string());
+        let token = token(&claims, &Header::default());
+        let response = app(JwtAuth::hs256("secret").audience(["api"]))
+            .oneshot(request(Some(&token)))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_layer() {
+        let router: Router = crate::routes!(
+            get "/" => |Claims(claims): Claims<TestClaims>| async move { claims.sub }
+        )
+        .layer(JwtLayer::<TestClaims>::new(JwtAuth::hs256("secret")));
+
+        let response = router.clone().oneshot(request(None)).await.unwrap();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        let token = token(&claims(get_current_timestamp() + 60), &Header::default());
+        let response = router.oneshot(request(Some(&token))).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+    }
+}
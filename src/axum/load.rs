@@ -1,3 +1,14 @@
+#[cfg(all(feature = "io", feature = "conditional"))]
+use {
+    crate::axum::conditional::{http_date, ETag},
+    axum::{
+        http::{
+            header::{CONTENT_LENGTH, ETAG, LAST_MODIFIED},
+            HeaderValue,
+        },
+        response::{IntoResponse, Response},
+    },
+};
 #[cfg(feature = "io")]
 use {
     crate::io::file,
@@ -22,6 +33,8 @@ where
     load_file(file_path).await.map(Html)
 }
 
+/// Load a file from the given file path, as a streamed body without any headers.
+/// With the `download` feature, `FileResponse` also sets the content type, the filename and supports range requests.
 #[cfg(feature = "io")]
 pub async fn load_file<Path>(file_path: Path) -> Result<Body, io::Error>
 where
@@ -30,6 +43,33 @@ where
     file::load_file(file_path).await.map(Body::from_stream)
 }
 
+/// Load a file from the given file path, as a response with validators for conditional requests.
+/// The `ETag` and `Last-Modified` headers are set from the file metadata,
+/// so the `ConditionalLayer` can answer with 304 Not Modified without reading the file.
+/// # Examples
+/// ```
+/// let response = async { lib::axum::load::load_file_response("Cargo.toml").await.unwrap() };
+/// ```
+#[cfg(all(feature = "io", feature = "conditional"))]
+pub async fn load_file_response<Path>(file_path: Path) -> Result<Response, io::Error>
+where
+    Path: AsRef<std::path::Path>,
+{
+    let metadata = tokio::fs::metadata(&file_path).await?;
+    let mut response = load_file(file_path).await?.into_response();
+    let headers = response.headers_mut();
+    headers.insert(CONTENT_LENGTH, HeaderValue::from(metadata.len()));
+    if let Some(etag) = ETag::from_metadata(&metadata) {
+        if let Ok(value) = HeaderValue::try_from(etag.to_string()) {
+            headers.insert(ETAG, value);
+        }
+    }
+    if let Ok(modified) = metadata.modified() {
+        headers.insert(LAST_MODIFIED, http_date(modified));
+    }
+    Ok(response)
+}
+
 /// Load an HTML file from the given file path, relative to the resource directory.
 /// The file is loading on compile time as a string literal.
 /// # Arguments
@@ -88,6 +128,14 @@ mod tests {
             assert!(load_file("Cargo.toml").await.is_ok());
         }
 
+        #[cfg(feature = "conditional")]
+        #[tokio::test]
+        async fn test_load_file_response() {
+            let response = load_file_response("Cargo.toml").await.unwrap();
+            assert!(response.headers().contains_key(ETAG));
+            assert!(response.headers().contains_key(LAST_MODIFIED));
+        }
+
         #[tokio::test]
         async fn test_load_file_not_found() {
             assert!(load_file("not_found.rs").await.is_err());
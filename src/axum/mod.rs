@@ -1,8 +1,45 @@
 pub mod app;
+#[cfg(feature = "auth")]
+pub mod auth;
+#[cfg(any(feature = "cache", feature = "conditional", feature = "idempotency"))]
+mod body;
+#[cfg(feature = "cache")]
+pub mod cache;
+#[cfg(feature = "conditional")]
+pub mod conditional;
+#[cfg(any(feature = "session", feature = "csrf"))]
+mod cookies;
+#[cfg(feature = "csrf")]
+pub mod csrf;
+#[cfg(feature = "download")]
+pub mod download;
 pub mod extractor;
+#[cfg(feature = "idempotency")]
+pub mod idempotency;
+#[cfg(feature = "jwt")]
+pub mod jwt;
 pub mod load;
+#[cfg(feature = "multipart")]
+pub mod multipart;
+#[cfg(feature = "openapi")]
+pub mod openapi;
+#[cfg(feature = "problem")]
+pub mod problem;
+#[cfg(feature = "route-macros")]
+pub mod registry;
 #[cfg(feature = "serde")]
 pub mod response;
 pub mod router;
-#[cfg(feature = "serde")]
+#[cfg(feature = "session")]
+pub mod session;
+#[cfg(feature = "storage")]
+pub mod storage;
+#[cfg(feature = "tus")]
+pub mod tus;
+pub mod url;
+#[cfg(feature = "validation")]
+pub mod validation;
+#[cfg(feature = "versioning")]
+pub mod versioning;
+#[cfg(all(feature = "serde", feature = "derive"))]
 pub mod wrappers;
@@ -0,0 +1,530 @@
+use {
+    crate::axum::{
+        extractor::{content_type_or_guess, File, MultipartFileRejection},
+        multipart::{
+            spool::{write_field, SpoolConfig, SpooledFile},
+            UploadPolicy,
+        },
+    },
+    axum::{
+        extract::{FromRequest, Multipart, Request},
+        http::StatusCode,
+        response::{IntoResponse, Response},
+        Json,
+    },
+    serde_json::json,
+    std::{fmt::Display, str::FromStr},
+    thiserror::Error,
+};
+
+/// Rejection type for structs deriving `MultipartForm`.
+/// Errors in a field are returned as JSON with the name of the field.
+#[derive(Debug, Error)]
+pub enum MultipartFormRejection {
+    #[error(transparent)]
+    Multipart(#[from] MultipartFileRejection),
+    #[error("Missing field `{0}`")]
+    MissingField(String),
+    #[error("Invalid field `{field}`: {message}")]
+    InvalidField { field: String, message: String },
+    #[error("Field `{field}` is larger than the limit of {limit} bytes")]
+    TooLarge { field: String, limit: u64 },
+    #[error("Field `{field}` has unsupported content type `{content_type}`")]
+    UnsupportedContentType { field: String, content_type: String },
+}
+
+impl MultipartFormRejection {
+    /// The name of the field causing the rejection, if any.
+    pub fn field(&self) -> Option<&str> {
+        match self {
+            MultipartFormRejection::Multipart(_) => None,
+            MultipartFormRejection::MissingField(field)
+            | MultipartFormRejection::InvalidField { field, .. }
+            | MultipartFormRejection::TooLarge { field, .. }
+            | MultipartFormRejection::UnsupportedContentType { field, .. } => Some(field),
+        }
+    }
+
+    /// The status code of the rejection.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            MultipartFormRejection::Multipart(rejection) => rejection.status(),
+            MultipartFormRejection::MissingField(_)
+            | MultipartFormRejection::InvalidField { .. } => StatusCode::BAD_REQUEST,
+            MultipartFormRejection::TooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+            MultipartFormRejection::UnsupportedContentType { .. } => {
+                StatusCode::UNSUPPORTED_MEDIA_TYPE
+            }
+        }
+    }
+
+    fn invalid(field: &str, message: impl Display) -> Self {
+        MultipartFormRejection::InvalidField {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for MultipartFormRejection {
+    fn into_response(self) -> Response {
+        if let MultipartFormRejection::Multipart(rejection) = self {
+            return rejection.into_response();
+        }
+        let body = json!({ "error": self.to_string(), "field": self.field() });
+        (self.status(), Json(body)).into_response()
+    }
+}
+
+/// How a form field is read, chosen by the `MultipartForm` derive from the type of the field.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FieldKind {
+    Text,
+    File,
+    SpooledFile,
+}
+
+/// A field of a struct deriving `MultipartForm`.
+#[doc(hidden)]
+#[derive(Debug, Clone, Copy)]
+pub struct FieldSpec {
+    name: &'static str,
+    kind: FieldKind,
+    limit: Option<u64>,
+    content_type: Option<&'static str>,
+}
+
+impl FieldSpec {
+    pub fn new(name: &'static str, kind: FieldKind) -> Self {
+        Self {
+            name,
+            kind,
+            limit: None,
+            content_type: None,
+        }
+    }
+
+    pub fn limit(mut self, limit: u64) -> Self {
+        self.limit = Some(limit);
+        self
+    }
+
+    pub fn content_type(mut self, content_type: &'static str) -> Self {
+        self.content_type = Some(content_type);
+        self
+    }
+
+    fn check_content_type(&self, content_type: Option<&str>) -> Result<(), MultipartFormRejection> {
+        let Some(allowed) = self.content_type else {
+            return Ok(());
+        };
+        let content_type = content_type.unwrap_or(match self.kind {
+            FieldKind::Text => "text/plain",
+            FieldKind::File | FieldKind::SpooledFile => "application/octet-stream",
+        });
+        if content_type_matches(allowed, content_type) {
+            Ok(())
+        } else {
+            Err(MultipartFormRejection::UnsupportedContentType {
+                field: self.name.to_string(),
+                content_type: content_type.to_string(),
+            })
+        }
+    }
+}
+
+/// Checks the content type against a comma separated list like `image/*, application/pdf`.
+pub(crate) fn content_type_matches(allowed: &str, content_type: &str) -> bool {
+    let essence = content_type
+        .split(';')
+        .next()
+        .unwrap_or_default()
+        .trim()
+        .to_ascii_lowercase();
+    allowed
+        .split(',')
+        .map(str::trim)
+        .any(|pattern| match pattern.strip_suffix('*') {
+            Some("*/") => true,
+            Some(prefix) => essence.starts_with(&prefix.to_ascii_lowercase()),
+            None => pattern.eq_ignore_ascii_case(&essence),
+        })
+}
+
+#[derive(Debug)]
+enum FormValue {
+    Text(String),
+    File(File),
+    SpooledFile(SpooledFile),
+}
+
+/// The fields of a multipart request, read according to the fields of a struct deriving `MultipartForm`.
+#[doc(hidden)]
+#[derive(Debug)]
+pub struct FormParts {
+    values: Vec<(&'static str, FormValue)>,
+}
+
+impl FormParts {
+    pub async fn from_request<S>(
+        req: Request,
+        state: &S,
+        specs: &[FieldSpec],
+    ) -> Result<Self, MultipartFormRejection>
+    where
+        S: Send + Sync,
+    {
+        let config = req
+            .extensions()
+            .get::<SpoolConfig>()
+            .cloned()
+            .unwrap_or_default();
+        let policy = req.extensions().get::<UploadPolicy>().cloned();
+        let mut multipart = Multipart::from_request(req, state)
+            .await
+            .map_err(MultipartFileRejection::from)?;
+        let mut values = Vec::new();
+        let policy_limit = policy
+            .as_ref()
+            .and_then(|policy| policy.max_size)
+            .unwrap_or(u64::MAX);
+        let mut remaining = config.max_total_size;
+        while let Some(mut field) = multipart
+            .next_field()
+            .await
+            .map_err(MultipartFileRejection::from)?
+        {
+            let Some(spec) = field
+                .name()
+                .and_then(|name| specs.iter().find(|spec| spec.name == name))
+            else {
+                continue;
+            };
+            spec.check_content_type(field.content_type())?;
+            let max_size = match spec.kind {
+                FieldKind::Text => spec.limit.unwrap_or(config.max_field_size),
+                FieldKind::File | FieldKind::SpooledFile => {
+                    spec.limit.unwrap_or(config.max_file_size).min(policy_limit)
+                }
+            };
+            let limit = max_size.min(remaining);
+            let too_large = |error| match error {
+                MultipartFileRejection::FileTooLarge(_) if limit < max_size => {
+                    MultipartFileRejection::TooLarge(config.max_total_size).into()
+                }
+                MultipartFileRejection::FileTooLarge(limit) => MultipartFormRejection::TooLarge {
+                    field: spec.name.to_string(),
+                    limit,
+                },
+                error => error.into(),
+            };
+            let value = match spec.kind {
+                FieldKind::Text => {
+                    let mut bytes = Vec::new();
+                    let written = write_field(&mut field, &mut bytes, limit)
+                        .await
+                        .map_err(too_large)?;
+                    remaining -= written.size;
+                    let text = String::from_utf8(bytes).map_err(|_| {
+                        MultipartFormRejection::invalid(spec.name, "Field is not UTF-8")
+                    })?;
+                    FormValue::Text(text)
+                }
+                FieldKind::File => {
+                    let filename = field
+                        .file_name()
+                        .ok_or_else(|| {
+                            MultipartFormRejection::invalid(spec.name, "Expected a file")
+                        })?
+                        .to_string();
+                    let content_type = content_type_or_guess(field.content_type(), &filename)
+                        .map_err(|error| MultipartFormRejection::invalid(spec.name, error))?;
+                    let mut bytes = Vec::new();
+                    let written = write_field(&mut field, &mut bytes, limit)
+                        .await
+                        .map_err(too_large)?;
+                    remaining -= written.size;
+                    let mut file = File::new(filename, bytes, content_type);
+                    if let Some(policy) = &policy {
+                        policy.validate_file(&mut file).map_err(too_large)?;
+                    }
+                    FormValue::File(file)
+                }
+                FieldKind::SpooledFile => {
+                    let mut file = SpooledFile::from_field(&mut field, &config, limit)
+                        .await
+                        .map_err(|error| match error {
+                            MultipartFileRejection::MissingFilename => {
+                                MultipartFormRejection::invalid(spec.name, "Expected a file")
+                            }
+                            error => too_large(error),
+                        })?;
+                    remaining -= file.size;
+                    if let Some(policy) = &policy {
+                        policy
+                            .validate_spooled(&mut file)
+                            .await
+                            .map_err(too_large)?;
+                    }
+                    FormValue::SpooledFile(file)
+                }
+            };
+            values.push((spec.name, value));
+        }
+        Ok(Self { values })
+    }
+
+    fn take(&mut self, name: &str) -> impl Iterator<Item = FormValue> {
+        let (taken, rest): (Vec<_>, Vec<_>) = std::mem::take(&mut self.values)
+            .into_iter()
+            .partition(|(field, _)| *field == name);
+        self.values = rest;
+        taken.into_iter().map(|(_, value)| value)
+    }
+
+    /// Parses the text values of the field.
+    pub fn parse<T>(&mut self, name: &str) -> Result<Vec<T>, MultipartFormRejection>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.take(name)
+            .filter_map(|value| match value {
+                FormValue::Text(text) => Some(text),
+                _ => None,
+            })
+            .map(|text| {
+                text.parse()
+                    .map_err(|error| MultipartFormRejection::invalid(name, error))
+            })
+            .collect()
+    }
+
+    /// Deserializes the text values of the field as JSON.
+    #[cfg(feature = "serde")]
+    pub fn json<T>(&mut self, name: &str) -> Result<Vec<T>, MultipartFormRejection>
+    where
+        T: serde::de::DeserializeOwned,
+    {
+        self.take(name)
+            .filter_map(|value| match value {
+                FormValue::Text(text) => Some(text),
+                _ => None,
+            })
+            .map(|text| {
+                serde_json::from_str(&text)
+                    .map_err(|error| MultipartFormRejection::invalid(name, error))
+            })
+            .collect()
+    }
+
+    pub fn files(&mut self, name: &str) -> Vec<File> {
+        self.take(name)
+            .filter_map(|value| match value {
+                FormValue::File(file) => Some(file),
+                _ => None,
+            })
+            .collect()
+    }
+
+    pub fn spooled_files(&mut self, name: &str) -> Vec<SpooledFile> {
+        self.take(name)
+            .filter_map(|value| match value {
+                FormValue::SpooledFile(file) => Some(file),
+                _ => None,
+            })
+            .collect()
+    }
+}
+
+/// Expects exactly one value of a required field.
+#[doc(hidden)]
+pub fn one<T>(name: &str, values: Vec<T>) -> Result<T, MultipartFormRejection> {
+    optional(name, values)?.ok_or_else(|| MultipartFormRejection::MissingField(name.to_string()))
+}
+
+/// Expects at most one value of an optional field.
+#[doc(hidden)]
+pub fn optional<T>(name: &str, values: Vec<T>) -> Result<Option<T>, MultipartFormRejection> {
+    if values.len() > 1 {
+        return Err(MultipartFormRejection::invalid(
+            name,
+            "Expected one value, got several",
+        ));
+    }
+    Ok(values.into_iter().next())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_content_type_matches() {
+        assert!(content_type_matches("image/*", "image/png"));
+        assert!(content_type_matches(
+            "image/png, application/pdf",
+            "application/PDF"
+        ));
+        assert!(content_type_matches(
+            "text/plain",
+            "text/plain; charset=utf-8"
+        ));
+        assert!(content_type_matches("*/*", "video/mp4"));
+        assert!(!content_type_matches("image/*", "video/mp4"));
+    }
+
+    #[test]
+    fn test_cardinality() {
+        assert_eq!(one("a", vec![1]).unwrap(), 1);
+        assert!(matches!(
+            one::<u8>("a", vec![]),
+            Err(MultipartFormRejection::MissingField(field)) if field == "a"
+        ));
+        assert_eq!(optional::<u8>("a", vec![]).unwrap(), None);
+        assert!(optional("a", vec![1, 2]).is_err());
+    }
+
+    #[cfg(feature = "derive")]
+    mod derive {
+        use crate::axum::{
+            extractor::{File, MultipartFileRejection},
+            multipart::{MultipartForm, MultipartFormRejection, SpoolConfig, SpooledFile},
+        };
+        use axum::{
+            body::{to_bytes, Body},
+            extract::{FromRequest, Request},
+            http::{header::CONTENT_TYPE, StatusCode},
+            response::IntoResponse,
+        };
+        use serde_json::Value;
+
+        #[derive(MultipartForm)]
+        struct Upload {
+            title: String,
+            #[multipart(rename = "tag")]
+            tags: Vec<String>,
+            year: Option<u16>,
+            #[multipart(limit = 16, content_type = "image/*")]
+            image: File,
+            attachments: Vec<SpooledFile>,
+            #[multipart(json)]
+            meta: Option<Vec<u8>>,
+        }
+
+        /// Name, filename and content type if it is a file, and the content of a part.
+        type Part<'a> = (&'a str, Option<(&'a str, &'a str)>, &'a str);
+
+        fn request(parts: &[Part]) -> Request {
+            let mut body = String::new();
+            for (name, file, content) in parts {
+                body.push_str("--boundary\r\n");
+                match file {
+                    Some((filename, content_type)) => body.push_str(&format!(
+                        "Content-Disposition: form-data; name=\"{name}\"; filename=\"{filename}\"\r\n\
+                        Content-Type: {content_type}\r\n\r\n"
+                    )),
+                    None => body.push_str(&format!(
+                        "Content-Disposition: form-data; name=\"{name}\"\r\n\r\n"
+                    )),
+                }
+                body.push_str(content);
+                body.push_str("\r\n");
+            }
+            body.push_str("--boundary--\r\n");
+            Request::post("/")
+                .header(CONTENT_TYPE, "multipart/form-data; boundary=boundary")
+                .body(Body::from(body))
+                .unwrap()
+        }
+
+        async fn error(rejection: MultipartFormRejection) -> (StatusCode, Value) {
+            let response = rejection.into_response();
+            let status = response.status();
+            let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+            (status, serde_json::from_slice(&body).unwrap())
+        }
+
+        #[tokio::test]
+        async fn test_derive() {
+            let request = request(&[
+                ("title", None, "Holiday"),
+                ("tag", None, "summer"),
+                ("tag", None, "beach"),
+                ("image", Some(("beach.png", "image/png")), "png"),
+                ("attachments", Some(("notes.txt", "text/plain")), "notes"),
+                ("meta", None, "[1, 2]"),
+                ("unknown", None, "ignored"),
+            ]);
+            let upload = Upload::from_request(request, &()).await.unwrap();
+            assert_eq!(upload.title, "Holiday");
+            assert_eq!(upload.tags, vec!["summer", "beach"]);
+            assert_eq!(upload.year, None);
+            assert_eq!(upload.image.filename, "beach.png");
+            assert_eq!(upload.image.bytes, b"png");
+            assert_eq!(upload.attachments.len(), 1);
+            assert_eq!(upload.attachments[0].size, 5);
+            assert_eq!(upload.meta, Some(vec![1, 2]));
+        }
+
+        #[tokio::test]
+        async fn test_missing_field() {
+            let request = request(&[("image", Some(("beach.png", "image/png")), "png")]);
+            let rejection = Upload::from_request(request, &()).await.err().unwrap();
+            let (status, body) = error(rejection).await;
+            assert_eq!(status, StatusCode::BAD_REQUEST);
+            assert_eq!(body["field"], "title");
+        }
+
+        #[tokio::test]
+        async fn test_invalid_field() {
+            let request = request(&[("title", None, "Holiday"), ("year", None, "last year")]);
+            let rejection = Upload::from_request(request, &()).await.err().unwrap();
+            assert!(matches!(
+                &rejection,
+                MultipartFormRejection::InvalidField { field, .. } if field == "year"
+            ));
+        }
+
+        #[tokio::test]
+        async fn test_content_type_and_limit() {
+            let video = request(&[("image", Some(("beach.mp4", "video/mp4")), "mp4")]);
+            let rejection = Upload::from_request(video, &()).await.err().unwrap();
+            let (status, body) = error(rejection).await;
+            assert_eq!(status, StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            assert_eq!(body["field"], "image");
+
+            let large = request(&[(
+                "image",
+                Some(("beach.png", "image/png")),
+                "a very large image",
+            )]);
+            let rejection = Upload::from_request(large, &()).await.err().unwrap();
+            assert_eq!(error(rejection).await.0, StatusCode::PAYLOAD_TOO_LARGE);
+        }
+
+        #[tokio::test]
+        async fn test_default_limits() {
+            let mut large = request(&[("title", None, "Holiday")]);
+            large
+                .extensions_mut()
+                .insert(SpoolConfig::new().max_field_size(3));
+            let rejection = Upload::from_request(large, &()).await.err().unwrap();
+            assert!(matches!(
+                &rejection,
+                MultipartFormRejection::TooLarge { field, limit: 3 } if field == "title"
+            ));
+
+            let mut large = request(&[("title", None, "Holiday"), ("tag", None, "summer")]);
+            large
+                .extensions_mut()
+                .insert(SpoolConfig::new().max_total_size(10));
+            let rejection = Upload::from_request(large, &()).await.err().unwrap();
+            assert!(matches!(
+                rejection,
+                MultipartFormRejection::Multipart(MultipartFileRejection::TooLarge(10))
+            ));
+        }
+    }
+}
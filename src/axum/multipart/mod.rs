@@ -0,0 +1,9 @@
+pub mod form;
+pub mod spool;
+pub mod validate;
+
+pub use form::{one, optional, FieldKind, FieldSpec, FormParts, MultipartFormRejection};
+#[cfg(feature = "derive")]
+pub use multipart_form_derive::MultipartForm;
+pub use spool::{write_field, SpoolConfig, SpooledFile, SpooledMultipart, WrittenField};
+pub use validate::{Mismatch, UploadPolicy, ValidatedUpload};
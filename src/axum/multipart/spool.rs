@@ -0,0 +1,489 @@
+use {
+    crate::axum::{
+        extractor::{content_type_or_guess, MultipartFileRejection},
+        multipart::UploadPolicy,
+    },
+    axum::{
+        async_trait,
+        extract::{multipart::Field, FromRequest, Multipart, Request},
+    },
+    mime::Mime,
+    sha2::{Digest, Sha256},
+    std::{
+        io,
+        path::{Path, PathBuf},
+    },
+    tempfile::{NamedTempFile, TempPath},
+    tokio::io::{AsyncWrite, AsyncWriteExt},
+};
+
+/// Limits and storage location for the `SpooledMultipart` extractor.
+/// Add it to the router with `Extension`, otherwise the default options are used.
+/// # Default Options
+/// - Directory == the system temp directory
+/// - Max file size == 100 MB
+/// - Max total size == 1 GB
+/// - Max field size == 64 KB, for fields which are not files
+/// # Example
+/// ```
+/// use axum::{extract::DefaultBodyLimit, Extension};
+/// use lib::axum::multipart::SpoolConfig;
+///
+/// let config = SpoolConfig::new().max_file_size(2 * 1024 * 1024 * 1024);
+/// let _router: axum::Router = lib::routes!(post "/upload" => || async {})
+///     .layer(Extension(config))
+///     .layer(DefaultBodyLimit::disable());
+/// ```
+#[derive(Debug, Clone)]
+pub struct SpoolConfig {
+    dir: Option<PathBuf>,
+    pub(super) max_file_size: u64,
+    pub(super) max_total_size: u64,
+    pub(super) max_field_size: u64,
+}
+
+impl Default for SpoolConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            max_file_size: 100 * 1024 * 1024,
+            max_total_size: 1024 * 1024 * 1024,
+            max_field_size: 64 * 1024,
+        }
+    }
+}
+
+impl SpoolConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the directory temporary files are written to.
+    /// Files are moved most efficiently by `SpooledFile::persist` within the same file system.
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Sets the maximum size of a single file.
+    pub fn max_file_size(mut self, size: u64) -> Self {
+        self.max_file_size = size;
+        self
+    }
+
+    /// Sets the maximum size of all files and fields in the request.
+    pub fn max_total_size(mut self, size: u64) -> Self {
+        self.max_total_size = size;
+        self
+    }
+
+    /// Sets the maximum size of a field which is not a file.
+    pub fn max_field_size(mut self, size: u64) -> Self {
+        self.max_field_size = size;
+        self
+    }
+}
+
+/// The size and hash of a field written by `write_field`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct WrittenField {
+    pub size: u64,
+    /// Hex encoded SHA-256 hash of the content.
+    pub sha256: String,
+}
+
+/// Streams the content of a field into the writer, chunk by chunk,
+/// for handlers using `Multipart` directly to send files elsewhere than to disk.
+/// # Errors
+/// `FileTooLarge` if the field is larger than the limit, in which case the writer has received
+/// part of the field, or an error if reading the field or writing to the writer fails.
+/// # Example
+/// ```
+/// use axum::extract::Multipart;
+/// use lib::axum::{extractor::MultipartFileRejection, multipart::write_field};
+///
+/// async fn upload(mut multipart: Multipart) -> Result<String, MultipartFileRejection> {
+///     let mut hashes = Vec::new();
+///     while let Some(mut field) = multipart.next_field().await? {
+///         let written = write_field(&mut field, &mut tokio::io::sink(), 1024 * 1024).await?;
+///         hashes.push(written.sha256);
+///     }
+///     Ok(hashes.join("\n"))
+/// }
+/// ```
+pub async fn write_field<W>(
+    field: &mut Field<'_>,
+    writer: &mut W,
+    limit: u64,
+) -> Result<WrittenField, MultipartFileRejection>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut hasher = Sha256::new();
+    let mut size = 0;
+    while let Some(chunk) = field.chunk().await? {
+        size += chunk.len() as u64;
+        if size > limit {
+            return Err(MultipartFileRejection::FileTooLarge(limit));
+        }
+        hasher.update(&chunk);
+        writer.write_all(&chunk).await?;
+    }
+    writer.flush().await?;
+    Ok(WrittenField {
+        size,
+        sha256: hasher
+            .finalize()
+            .iter()
+            .map(|byte| format!("{byte:02x}"))
+            .collect(),
+    })
+}
+
+/// A file from a multipart request, written to a temporary file.
+/// The temporary file is deleted when this is dropped, unless it is persisted.
+#[derive(Debug)]
+pub struct SpooledFile {
+    /// Name of the form field.
+    pub name: String,
+    pub filename: String,
+    /// Content type of the part, `application/octet-stream` if not given.
+    pub content_type: Mime,
+    pub size: u64,
+    /// Hex encoded SHA-256 hash of the content.
+    pub sha256: String,
+    file: NamedTempFile,
+}
+
+impl SpooledFile {
+    /// The path of the temporary file.
+    pub fn path(&self) -> &Path {
+        self.file.path()
+    }
+
+    /// Opens the temporary file for reading.
+    pub async fn open(&self) -> io::Result<tokio::fs::File> {
+        tokio::fs::File::open(self.path()).await
+    }
+
+    /// Moves the file to the path, copying it if the path is on another file system.
+    pub async fn persist(self, path: impl AsRef<Path>) -> io::Result<()> {
+        let temp = self.file.into_temp_path();
+        if tokio::fs::rename(&temp, &path).await.is_ok() {
+            // The file was moved, so there is nothing to delete
+            return temp.keep().map(drop).map_err(|error| error.error);
+        }
+        tokio::fs::copy(&temp, path).await.map(drop)
+    }
+
+    /// Takes ownership of the temporary file, which is deleted when the `TempPath` is dropped.
+    pub fn into_temp_path(self) -> TempPath {
+        self.file.into_temp_path()
+    }
+
+    pub(crate) async fn from_field(
+        field: &mut Field<'_>,
+        config: &SpoolConfig,
+        limit: u64,
+    ) -> Result<Self, MultipartFileRejection> {
+        let name = field.name().unwrap_or_default().to_string();
+        let filename = field
+            .file_name()
+            .ok_or(MultipartFileRejection::MissingFilename)?
+            .to_string();
+        let content_type = content_type_or_guess(field.content_type(), &filename)?;
+        let file = match &config.dir {
+            Some(dir) => NamedTempFile::new_in(dir)?,
+            None => NamedTempFile::new()?,
+        };
+        let mut writer = tokio::fs::File::from_std(file.reopen()?);
+        let written = write_field(field, &mut writer, limit).await?;
+        Ok(Self {
+            name,
+            filename,
+            content_type,
+            size: written.size,
+            sha256: written.sha256,
+            file,
+        })
+    }
+}
+
+/// Extractor streaming the files of a multipart request to temporary files,
+/// instead of buffering them in memory like `MultipartFiles`.
+/// Fields without a filename are read as text.
+///
+/// Limits are set with a `SpoolConfig` extension, and exceeding them rejects the request
+/// with 413 Payload Too Large. The default body limit of axum still applies to multipart requests,
+/// so it should be disabled or raised with `DefaultBodyLimit` for routes accepting large files.
+/// This extractor consumes the request and must be placed last in the handler.
+/// # Example
+/// ```
+/// use lib::axum::{extractor::MultipartFileRejection, multipart::SpooledMultipart};
+///
+/// async fn upload(mut multipart: SpooledMultipart) -> Result<String, MultipartFileRejection> {
+///     let Some(file) = multipart.take_file("video") else {
+///         return Err(MultipartFileRejection::NoFiles);
+///     };
+///     let hash = file.sha256.clone();
+///     file.persist(std::env::temp_dir().join(&hash)).await?;
+///     Ok(hash)
+/// }
+/// ```
+#[derive(Debug, Default)]
+pub struct SpooledMultipart {
+    pub files: Vec<SpooledFile>,
+    /// Names and values of the fields which are not files, in order.
+    pub fields: Vec<(String, String)>,
+}
+
+impl SpooledMultipart {
+    /// Returns the first file of the field.
+    pub fn file(&self, name: &str) -> Option<&SpooledFile> {
+        self.files.iter().find(|file| file.name == name)
+    }
+
+    /// Removes and returns the first file of the field.
+    pub fn take_file(&mut self, name: &str) -> Option<SpooledFile> {
+        let index = self.files.iter().position(|file| file.name == name)?;
+        Some(self.files.remove(index))
+    }
+
+    /// Returns the first value of the field.
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.fields
+            .iter()
+            .find(|(field, _)| field == name)
+            .map(|(_, value)| value.as_str())
+    }
+}
+
+#[async_trait]
+impl<S> FromRequest<S> for SpooledMultipart
+where
+    S: Send + Sync,
+{
+    type Rejection = MultipartFileRejection;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let config = req
+            .extensions()
+            .get::<SpoolConfig>()
+            .cloned()
+            .unwrap_or_default();
+        let policy = req.extensions().get::<UploadPolicy>().cloned();
+        let mut multipart = Multipart::from_request(req, state).await?;
+        let mut spooled = SpooledMultipart::default();
+        let mut remaining = config.max_total_size;
+        while let Some(mut field) = multipart.next_field().await? {
+            let max_size = if field.file_name().is_some() {
+                let policy_limit = policy.as_ref().and_then(|policy| policy.max_size);
+                config.max_file_size.min(policy_limit.unwrap_or(u64::MAX))
+            } else {
+                config.max_field_size
+            };
+            let limit = max_size.min(remaining);
+            let result = if field.file_name().is_some() {
+                match SpooledFile::from_field(&mut field, &config, limit).await {
+                    Ok(mut file) => {
+                        remaining -= file.size;
+                        if let Some(policy) = &policy {
+                            policy.validate_spooled(&mut file).await?;
+                        }
+                        spooled.files.push(file);
+                        Ok(())
+                    }
+                    Err(error) => Err(error),
+                }
+            } else {
+                let mut value = Vec::new();
+                write_field(&mut field, &mut value, limit)
+                    .await
+                    .and_then(|written| {
+                        remaining -= written.size;
+                        let value = String::from_utf8(value).map_err(|_| {
+                            MultipartFileRejection::FieldError("Field is not UTF-8".to_string())
+                        })?;
+                        let name = field.name().unwrap_or_default().to_string();
+                        spooled.fields.push((name, value));
+                        Ok(())
+                    })
+            };
+            match result {
+                Err(MultipartFileRejection::FileTooLarge(_)) if limit < max_size => {
+                    return Err(MultipartFileRejection::TooLarge(config.max_total_size));
+                }
+                result => result?,
+            }
+        }
+        Ok(spooled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::{
+        body::Body,
+        http::{header::CONTENT_TYPE, StatusCode},
+        response::IntoResponse,
+        Extension,
+    };
+
+    const BOUNDARY: &str = "boundary";
+
+    fn request(parts: &[(&str, Option<&str>, &str)]) -> Request {
+        let mut body = String::new();
+        for (name, filename, content) in parts {
+            body.push_str(&format!("--{BOUNDARY}\r\n"));
+            match filename {
+                Some(filename) => body.push_str(&format!(
+                    "Content-Disposition: form-data; name=\"{name}\"; filename=\"{filename}\"\r\n\
+                    Content-Type: text/plain\r\n\r\n"
+                )),
+                None => body.push_str(&format!(
+                    "Content-Disposition: form-data; name=\"{name}\"\r\n\r\n"
+                )),
+            }
+            body.push_str(content);
+            body.push_str("\r\n");
+        }
+        body.push_str(&format!("--{BOUNDARY}--\r\n"));
+        Request::post("/")
+            .header(
+                CONTENT_TYPE,
+                format!("multipart/form-data; boundary={BOUNDARY}"),
+            )
+            .body(Body::from(body))
+            .unwrap()
+    }
+
+    async fn extract(
+        mut request: Request,
+        config: SpoolConfig,
+    ) -> Result<SpooledMultipart, MultipartFileRejection> {
+        request.extensions_mut().insert(config);
+        SpooledMultipart::from_request(request, &()).await
+    }
+
+    #[tokio::test]
+    async fn test_spool_files() {
+        let request = request(&[
+            ("title", None, "Holiday"),
+            ("video", Some("video.mp4"), "Hello, World!"),
+        ]);
+        let multipart = extract(request, SpoolConfig::new()).await.unwrap();
+        assert_eq!(multipart.field("title"), Some("Holiday"));
+        let file = multipart.file("video").unwrap();
+        assert_eq!(file.filename, "video.mp4");
+        assert_eq!(file.content_type, mime::TEXT_PLAIN);
+        assert_eq!(file.size, 13);
+        assert_eq!(
+            file.sha256,
+            "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f"
+        );
+        assert_eq!(
+            tokio::fs::read_to_string(file.path()).await.unwrap(),
+            "Hello, World!"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_persist_and_cleanup() {
+        let request = request(&[
+            ("a", Some("a.txt"), "first"),
+            ("b", Some("b.txt"), "second"),
+        ]);
+        let mut multipart = extract(request, SpoolConfig::new()).await.unwrap();
+        let target = NamedTempFile::new().unwrap().into_temp_path();
+        let file = multipart.take_file("a").unwrap();
+        file.persist(&target).await.unwrap();
+        assert_eq!(tokio::fs::read_to_string(&target).await.unwrap(), "first");
+
+        let path = multipart.file("b").unwrap().path().to_path_buf();
+        assert!(path.exists());
+        drop(multipart);
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_file_too_large() {
+        let request = request(&[("video", Some("video.mp4"), "Hello, World!")]);
+        let error = extract(request, SpoolConfig::new().max_file_size(5))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, MultipartFileRejection::FileTooLarge(5)));
+        assert_eq!(
+            error.into_response().status(),
+            StatusCode::PAYLOAD_TOO_LARGE
+        );
+    }
+
+    #[tokio::test]
+    async fn test_total_too_large() {
+        let request = request(&[("a", Some("a.txt"), "Hello"), ("b", Some("b.txt"), "World")]);
+        let error = extract(request, SpoolConfig::new().max_total_size(8))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, MultipartFileRejection::TooLarge(8)));
+    }
+
+    #[tokio::test]
+    async fn test_field_too_large() {
+        let request = request(&[("title", None, "Holiday")]);
+        let error = extract(request, SpoolConfig::new().max_field_size(3))
+            .await
+            .unwrap_err();
+        assert!(matches!(error, MultipartFileRejection::FileTooLarge(3)));
+    }
+
+    #[tokio::test]
+    async fn test_extension_config() {
+        use tower::ServiceExt;
+        let router: axum::Router = crate::routes!(
+            post "/" => |multipart: SpooledMultipart| async move { multipart.files.len().to_string() }
+        )
+        .layer(Extension(SpoolConfig::new().max_file_size(1)));
+        let response = router
+            .oneshot(request(&[("a", Some("a.txt"), "Hello")]))
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+    }
+
+    #[tokio::test]
+    async fn test_upload_policy() {
+        let mut pdf = request(&[("doc", Some("doc.txt"), "%PDF-1.7")]);
+        pdf.extensions_mut().insert(UploadPolicy::new());
+        let error = extract(pdf, SpoolConfig::new()).await.unwrap_err();
+        assert_eq!(
+            error.into_response().status(),
+            StatusCode::UNSUPPORTED_MEDIA_TYPE
+        );
+
+        let mut text = request(&[("doc", Some("doc.txt"), "Hello")]);
+        text.extensions_mut()
+            .insert(UploadPolicy::new().allow("text/*"));
+        let multipart = extract(text, SpoolConfig::new()).await.unwrap();
+        assert_eq!(
+            multipart.file("doc").unwrap().content_type,
+            mime::TEXT_PLAIN
+        );
+    }
+
+    #[tokio::test]
+    async fn test_upload_policy_max_size() {
+        let policy = UploadPolicy::new().max_size(3);
+        let mut spooled = request(&[("doc", Some("doc.txt"), "Hello")]);
+        spooled.extensions_mut().insert(policy.clone());
+        let error = extract(spooled, SpoolConfig::new()).await.unwrap_err();
+        assert!(matches!(error, MultipartFileRejection::FileTooLarge(3)));
+
+        let mut buffered = request(&[("doc", Some("doc.txt"), "Hello")]);
+        buffered.extensions_mut().insert(policy);
+        let error = crate::axum::extractor::MultipartFiles::from_request(buffered, &())
+            .await
+            .unwrap_err();
+        assert!(matches!(error, MultipartFileRejection::FileTooLarge(3)));
+    }
+}
@@ -0,0 +1,301 @@
+use {
+    crate::axum::{
+        extractor::{File, MultipartFileRejection},
+        multipart::{form::content_type_matches, spool::SpooledFile},
+    },
+    mime::Mime,
+    tokio::io::AsyncReadExt,
+    tracing::warn,
+};
+
+/// Number of bytes read from the start of a file to detect its type.
+const SNIFF_LENGTH: usize = 8192;
+
+/// What to do when the type detected from the content of an upload differs from the declared type.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Mismatch {
+    /// Rejects the upload with 415 Unsupported Media Type.
+    #[default]
+    Reject,
+    /// Replaces the declared type with the detected type, and logs a warning.
+    UseDetected,
+    /// Keeps the declared type, and logs a warning.
+    /// The declared type is then checked against the allowed types.
+    Allow,
+}
+
+/// The outcome of validating an upload with an `UploadPolicy`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ValidatedUpload {
+    /// The type of the file after validation.
+    pub content_type: Mime,
+    /// The type sent by the client, or guessed from the extension of the filename.
+    pub declared: Option<Mime>,
+    /// The type detected from the magic bytes of the content, if known.
+    pub detected: Option<Mime>,
+    /// Whether the declared and detected types differ.
+    pub mismatch: bool,
+}
+
+/// Rules for uploaded files: allowed content types, a size limit,
+/// and detection of the real type from the magic bytes at the start of the content.
+///
+/// Add it to the router with `Extension` to validate the files of the `MultipartFile`,
+/// `MultipartFiles`, `SpooledMultipart` and `MultipartForm` extractors,
+/// or call `validate_file` and `validate_spooled` directly.
+/// Text formats have no magic bytes, in which case the declared type is used.
+/// Parts without a content type get one guessed from the extension of the filename.
+/// # Default Options
+/// - Allowed types == all
+/// - Max size == unlimited
+/// - Sniff == true
+/// - Mismatch == `Mismatch::Reject`
+/// # Example
+/// ```
+/// use axum::{routing::post, Extension};
+/// use lib::axum::{extractor::MultipartFile, multipart::UploadPolicy};
+///
+/// let policy = UploadPolicy::new()
+///     .allow("image/png, image/jpeg")
+///     .allow("application/pdf")
+///     .max_size(10 * 1024 * 1024);
+/// let _router: axum::Router = lib::routes!(post "/upload" => |_: MultipartFile| async {})
+///     .layer(Extension(policy));
+/// ```
+#[derive(Debug, Clone)]
+pub struct UploadPolicy {
+    allowed: Vec<String>,
+    pub(crate) max_size: Option<u64>,
+    sniff: bool,
+    mismatch: Mismatch,
+}
+
+impl Default for UploadPolicy {
+    fn default() -> Self {
+        Self {
+            allowed: Vec::new(),
+            max_size: None,
+            sniff: true,
+            mismatch: Mismatch::default(),
+        }
+    }
+}
+
+impl UploadPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Allows content types matching the pattern, like `image/*`, or a comma separated list of patterns.
+    pub fn allow(mut self, pattern: impl Into<String>) -> Self {
+        self.allowed.push(pattern.into());
+        self
+    }
+
+    /// Sets the maximum size of a file in bytes.
+    /// The extractors stop reading a file as soon as it is larger.
+    pub fn max_size(mut self, size: u64) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    /// Sets whether the type is detected from the content.
+    pub fn sniff(mut self, sniff: bool) -> Self {
+        self.sniff = sniff;
+        self
+    }
+
+    /// Sets what to do when the detected type differs from the declared type.
+    pub fn mismatch(mut self, mismatch: Mismatch) -> Self {
+        self.mismatch = mismatch;
+        self
+    }
+
+    /// Validates an upload from its filename, declared type, the start of its content and its size.
+    /// # Errors
+    /// - `FileTooLarge` if the file is larger than the maximum size.
+    /// - `ContentTypeMismatch` if the types differ and mismatches are rejected.
+    /// - `UnsupportedMediaType` if the type is not allowed.
+    pub fn validate(
+        &self,
+        filename: &str,
+        declared: Option<&Mime>,
+        head: &[u8],
+        size: u64,
+    ) -> Result<ValidatedUpload, MultipartFileRejection> {
+        if let Some(max_size) = self.max_size.filter(|max_size| size > *max_size) {
+            return Err(MultipartFileRejection::FileTooLarge(max_size));
+        }
+        let declared = declared
+            .filter(|declared| **declared != mime::APPLICATION_OCTET_STREAM)
+            .cloned()
+            .or_else(|| mime_guess::from_path(filename).first());
+        let detected = if self.sniff {
+            infer::get(head).and_then(|kind| kind.mime_type().parse::<Mime>().ok())
+        } else {
+            None
+        };
+        let mismatch = match (&declared, &detected) {
+            (Some(declared), Some(detected)) => declared.essence_str() != detected.essence_str(),
+            _ => false,
+        };
+        let content_type = match (&declared, &detected) {
+            (Some(declared), Some(detected)) if mismatch => {
+                if self.mismatch == Mismatch::Reject {
+                    return Err(MultipartFileRejection::ContentTypeMismatch {
+                        declared: declared.to_string(),
+                        detected: detected.to_string(),
+                    });
+                }
+                warn!(
+                    "Upload {filename} is declared as {declared}, but was detected as {detected}"
+                );
+                match self.mismatch {
+                    Mismatch::Allow => declared.clone(),
+                    _ => detected.clone(),
+                }
+            }
+            _ => detected
+                .clone()
+                .or_else(|| declared.clone())
+                .unwrap_or(mime::APPLICATION_OCTET_STREAM),
+        };
+        if !self.allowed.is_empty()
+            && !self
+                .allowed
+                .iter()
+                .any(|pattern| content_type_matches(pattern, content_type.essence_str()))
+        {
+            return Err(MultipartFileRejection::UnsupportedMediaType(
+                content_type.to_string(),
+            ));
+        }
+        Ok(ValidatedUpload {
+            content_type,
+            declared,
+            detected,
+            mismatch,
+        })
+    }
+
+    /// Validates a file in memory, and sets its content type to the validated type.
+    pub fn validate_file(
+        &self,
+        file: &mut File,
+    ) -> Result<ValidatedUpload, MultipartFileRejection> {
+        let head = &file.bytes[..file.bytes.len().min(SNIFF_LENGTH)];
+        let validated = self.validate(
+            &file.filename,
+            Some(&file.content_type),
+            head,
+            file.bytes.len() as u64,
+        )?;
+        file.content_type = validated.content_type.clone();
+        Ok(validated)
+    }
+
+    /// Validates a spooled file, reading the start of the temporary file,
+    /// and sets its content type to the validated type.
+    pub async fn validate_spooled(
+        &self,
+        file: &mut SpooledFile,
+    ) -> Result<ValidatedUpload, MultipartFileRejection> {
+        let mut head = Vec::with_capacity(SNIFF_LENGTH);
+        if self.sniff {
+            file.open()
+                .await?
+                .take(SNIFF_LENGTH as u64)
+                .read_to_end(&mut head)
+                .await?;
+        }
+        let validated =
+            self.validate(&file.filename, Some(&file.content_type), &head, file.size)?;
+        file.content_type = validated.content_type.clone();
+        Ok(validated)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PNG: &[u8] = b"\x89PNG\r\n\x1a\n\0\0\0\rIHDR";
+    const PDF: &[u8] = b"%PDF-1.7\n";
+
+    #[test]
+    fn test_detects_type() {
+        let validated = UploadPolicy::new()
+            .validate("image.png", Some(&mime::IMAGE_PNG), PNG, 16)
+            .unwrap();
+        assert_eq!(validated.content_type, mime::IMAGE_PNG);
+        assert_eq!(validated.detected, Some(mime::IMAGE_PNG));
+        assert!(!validated.mismatch);
+    }
+
+    #[test]
+    fn test_guesses_type_from_extension() {
+        let validated = UploadPolicy::new()
+            .validate("notes.txt", None, b"Hello", 5)
+            .unwrap();
+        assert_eq!(validated.content_type, mime::TEXT_PLAIN);
+        assert_eq!(validated.detected, None);
+    }
+
+    #[test]
+    fn test_mismatch() {
+        let error = UploadPolicy::new()
+            .validate("image.png", Some(&mime::IMAGE_PNG), PDF, 9)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            MultipartFileRejection::ContentTypeMismatch { .. }
+        ));
+
+        let validated = UploadPolicy::new()
+            .mismatch(Mismatch::UseDetected)
+            .validate("image.png", None, PDF, 9)
+            .unwrap();
+        assert!(validated.mismatch);
+        assert_eq!(validated.content_type, mime::APPLICATION_PDF);
+
+        let validated = UploadPolicy::new()
+            .mismatch(Mismatch::Allow)
+            .validate("image.png", None, PDF, 9)
+            .unwrap();
+        assert_eq!(validated.content_type, mime::IMAGE_PNG);
+    }
+
+    #[test]
+    fn test_allowlist_and_size() {
+        let policy = UploadPolicy::new().allow("image/*").max_size(10);
+        let error = policy
+            .validate("file.pdf", Some(&mime::APPLICATION_PDF), PDF, 9)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            MultipartFileRejection::UnsupportedMediaType(_)
+        ));
+        let error = policy
+            .validate("image.png", Some(&mime::IMAGE_PNG), PNG, 11)
+            .unwrap_err();
+        assert!(matches!(error, MultipartFileRejection::FileTooLarge(10)));
+        // A disguised file is checked by its detected type
+        let error = UploadPolicy::new()
+            .allow("image/*")
+            .mismatch(Mismatch::UseDetected)
+            .validate("image.png", Some(&mime::IMAGE_PNG), PDF, 9)
+            .unwrap_err();
+        assert!(matches!(
+            error,
+            MultipartFileRejection::UnsupportedMediaType(_)
+        ));
+    }
+
+    #[test]
+    fn test_validate_file() {
+        let mut file = File::new("upload", PNG, mime::APPLICATION_OCTET_STREAM);
+        let validated = UploadPolicy::new().validate_file(&mut file).unwrap();
+        assert_eq!(validated.declared, None);
+        assert_eq!(file.content_type, mime::IMAGE_PNG);
+    }
+}
@@ -0,0 +1,401 @@
+pub use schemars;
+use {
+    crate::{
+        axum::router::{RouteInfo, RouteTable},
+        serde::response::{BaseResponse, SerializeObject},
+    },
+    axum::{http::Method, response::Html, routing::get, Json, Router},
+    schemars::{
+        gen::{SchemaGenerator, SchemaSettings},
+        schema::Schema,
+        JsonSchema,
+    },
+    serde_json::{json, Map, Value},
+    std::collections::{HashMap, HashSet},
+    tracing::warn,
+};
+
+/// A type which can be documented as the JSON body of a response.
+///
+/// Implemented for `Json<T>`, `BaseResponse<T>`, the wrappers, and types deriving `IntoResponse`
+/// with `#[into_response(openapi)]`, in which case the documented body includes the `version`
+/// field of the `BaseResponse`.
+pub trait ResponseBody {
+    /// Returns the schema of the body, usually a reference to a schema in the components.
+    fn body_schema(generator: &mut SchemaGenerator) -> Schema;
+}
+
+impl<T: JsonSchema> ResponseBody for Json<T> {
+    fn body_schema(generator: &mut SchemaGenerator) -> Schema {
+        generator.subschema_for::<T>()
+    }
+}
+
+impl<T: JsonSchema + SerializeObject> ResponseBody for BaseResponse<T> {
+    fn body_schema(generator: &mut SchemaGenerator) -> Schema {
+        generator.subschema_for::<Self>()
+    }
+}
+
+/// Builder for an OpenAPI 3.0 document.
+///
+/// Paths and operations are generated from the `RouteTable` given to `routes`,
+/// recorded while creating the router with `RouteTable::record`.
+/// The response bodies of operations are documented with `response`,
+/// and their schemas are added to the components.
+/// # Example
+/// ```
+/// use lib::axum::{openapi::OpenApi, router::RouteTable};
+/// use lib::serde::response::{BaseResponse, Data};
+///
+/// async fn count_users() -> BaseResponse<Data<u64>> {
+///     lib::from!(Data(1))
+/// }
+///
+/// let (router, routes) = RouteTable::record(|| -> axum::Router {
+///     lib::routes!(get "/users/count" => count_users)
+/// });
+/// let openapi = OpenApi::new("Users", "1.0.0")
+///     .routes(routes)
+///     .response::<BaseResponse<Data<u64>>>("GET", "/users/count");
+/// let router = router.merge(openapi.router("/openapi.json"));
+/// ```
+pub struct OpenApi {
+    title: String,
+    version: String,
+    description: Option<String>,
+    routes: RouteTable,
+    responses: HashMap<(String, String), Schema>,
+    generator: SchemaGenerator,
+}
+
+impl OpenApi {
+    pub fn new(title: impl Into<String>, version: impl Into<String>) -> Self {
+        Self {
+            title: title.into(),
+            version: version.into(),
+            description: None,
+            routes: RouteTable::default(),
+            responses: HashMap::new(),
+            generator: SchemaSettings::openapi3().into_generator(),
+        }
+    }
+
+    pub fn description(mut self, description: impl Into<String>) -> Self {
+        self.description = Some(description.into());
+        self
+    }
+
+    /// Sets the documented routes. Without routes, the document has no paths.
+    pub fn routes(mut self, routes: RouteTable) -> Self {
+        self.routes = routes;
+        self
+    }
+
+    /// Documents the body of the successful response for the route.
+    /// The path is the template given to `routes!`, like `/users/:id`.
+    pub fn response<T: ResponseBody>(mut self, method: &str, path: &str) -> Self {
+        let schema = T::body_schema(&mut self.generator);
+        self.responses
+            .insert((method.to_uppercase(), path.to_string()), schema);
+        self
+    }
+
+    /// Adds the schema of the type to the components, without using it in a response.
+    pub fn schema<T: JsonSchema>(mut self) -> Self {
+        self.generator.subschema_for::<T>();
+        self
+    }
+
+    /// Builds the document as JSON.
+    /// Logs a warning for each documented response without a route in the table.
+    pub fn to_json(&self) -> Value {
+        for (method, path) in self.responses.keys() {
+            if !self
+                .routes
+                .find(path)
+                .any(|route| route.method.as_method().map(Method::as_str) == Some(method))
+            {
+                warn!("OpenAPI response for {method} {path} does not match any route");
+            }
+        }
+        let mut paths = Map::new();
+        let mut operation_ids = HashSet::new();
+        // Routes for any method and fallbacks are not operations
+        for (route, method) in self.routes.iter().filter_map(|route| {
+            route
+                .method
+                .as_method()
+                .filter(|method| METHODS.contains(method))
+                .map(|method| (route, method))
+        }) {
+            let operation = self.operation(route, method, &mut operation_ids);
+            if let Value::Object(path) = paths
+                .entry(openapi_path(&route.path))
+                .or_insert_with(|| json!({}))
+            {
+                path.insert(method.as_str().to_lowercase(), operation);
+            }
+        }
+        let mut info = json!({ "title": self.title, "version": self.version });
+        if let Some(description) = &self.description {
+            info["description"] = json!(description);
+        }
+        json!({
+            "openapi": "3.0.3",
+            "info": info,
+            "paths": paths,
+            "components": { "schemas": self.generator.definitions() },
+        })
+    }
+
+    /// Creates a router serving the document as JSON at the path.
+    pub fn router<S>(&self, path: &str) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        let document = self.to_json();
+        Router::new().route(path, get(|| async move { Json(document) }))
+    }
+
+    fn operation(
+        &self,
+        route: &RouteInfo,
+        method: &Method,
+        operation_ids: &mut HashSet<String>,
+    ) -> Value {
+        let mut response = json!({ "description": "Successful response" });
+        if let Some(schema) = self
+            .responses
+            .get(&(method.as_str().to_string(), route.path.clone()))
+        {
+            response["content"] = json!({ "application/json": { "schema": schema } });
+        }
+        let mut operation = json!({ "responses": { "200": response } });
+        let parameters: Vec<_> = path_parameters(&route.path)
+            .map(|name| {
+                json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": { "type": "string" },
+                })
+            })
+            .collect();
+        if !parameters.is_empty() {
+            operation["parameters"] = json!(parameters);
+        }
+        // Operation ids must be unique, so only the first route using a named handler gets one
+        if let Some(handler) = handler_name(&route.handler) {
+            if operation_ids.insert(handler.to_string()) {
+                operation["operationId"] = json!(handler);
+            }
+        }
+        operation
+    }
+}
+
+/// Creates a router serving a Swagger UI page at the path, displaying the document at the url.
+/// The Swagger UI assets are loaded from a CDN.
+/// # Example
+/// ```
+/// let _router: axum::Router = lib::axum::openapi::swagger_ui("/docs", "/openapi.json");
+/// ```
+pub fn swagger_ui<S>(path: &str, document_url: &str) -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let html = SWAGGER_UI.replace("{{url}}", document_url);
+    Router::new().route(path, get(|| async move { Html(html) }))
+}
+
+const SWAGGER_UI: &str = r##"<!DOCTYPE html>
+<html lang="en">
+<head>
+    <meta charset="utf-8" />
+    <title>Swagger UI</title>
+    <link rel="stylesheet" href="https://unpkg.com/swagger-ui-dist@5/swagger-ui.css" />
+</head>
+<body>
+<div id="swagger-ui"></div>
+<script src="https://unpkg.com/swagger-ui-dist@5/swagger-ui-bundle.js" crossorigin></script>
+<script>
+    window.onload = () => {
+        window.ui = SwaggerUIBundle({ url: "{{url}}", dom_id: "#swagger-ui" });
+    };
+</script>
+</body>
+</html>
+"##;
+
+const METHODS: [Method; 8] = [
+    Method::GET,
+    Method::PUT,
+    Method::POST,
+    Method::DELETE,
+    Method::OPTIONS,
+    Method::HEAD,
+    Method::PATCH,
+    Method::TRACE,
+];
+
+/// Converts an axum path template to an OpenAPI path template, `/users/:id` to `/users/{id}`.
+fn openapi_path(path: &str) -> String {
+    path.split('/')
+        .map(|segment| match segment.strip_prefix([':', '*']) {
+            Some(name) => format!("{{{name}}}"),
+            None => segment.to_string(),
+        })
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+fn path_parameters(path: &str) -> impl Iterator<Item = &str> {
+    path.split('/')
+        .filter_map(|segment| segment.strip_prefix([':', '*']))
+}
+
+/// Returns the name of the handler, if it is a function and not a closure.
+fn handler_name(handler: &str) -> Option<&str> {
+    let name = handler.rsplit("::").next()?.trim();
+    (!name.is_empty()
+        && name
+            .chars()
+            .all(|char| char.is_alphanumeric() || char == '_'))
+    .then_some(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::axum::router::RouteMethod;
+    use crate::serde::response::Data;
+    use serde::Serialize;
+
+    #[derive(Serialize, JsonSchema)]
+    struct User {
+        name: String,
+    }
+
+    fn document() -> Value {
+        let routes = RouteTable::from(vec![
+            RouteInfo {
+                path: "/users".to_string(),
+                method: RouteMethod::Method(Method::GET),
+                handler: "users::list".to_string(),
+                name: None,
+            },
+            RouteInfo {
+                path: "/users/:id".to_string(),
+                method: RouteMethod::Method(Method::GET),
+                handler: "|| async {}".to_string(),
+                name: None,
+            },
+            RouteInfo {
+                path: "/users/:id".to_string(),
+                method: RouteMethod::Fallback,
+                handler: "not_allowed".to_string(),
+                name: None,
+            },
+            RouteInfo {
+                path: "/users/count".to_string(),
+                method: RouteMethod::Method(Method::GET),
+                handler: "count".to_string(),
+                name: None,
+            },
+        ]);
+        OpenApi::new("Test", "1.0.0")
+            .routes(routes)
+            .response::<BaseResponse<Data<Vec<User>>>>("get", "/users")
+            .response::<Json<User>>("GET", "/users/:id")
+            .response::<BaseResponse<Data<u64>>>("GET", "/users/count")
+            .to_json()
+    }
+
+    #[test]
+    fn test_openapi_path() {
+        assert_eq!(openapi_path("/users/:id/*rest"), "/users/{id}/{rest}");
+        assert_eq!(openapi_path("/"), "/");
+    }
+
+    #[test]
+    fn test_handler_name() {
+        assert_eq!(handler_name("users::list"), Some("list"));
+        assert_eq!(handler_name("|| async {}"), None);
+    }
+
+    #[test]
+    fn test_paths() {
+        let document = document();
+        assert_eq!(document["openapi"], "3.0.3");
+        let list = &document["paths"]["/users"]["get"];
+        assert_eq!(list["operationId"], "list");
+        let get = &document["paths"]["/users/{id}"]["get"];
+        assert_eq!(get["parameters"][0]["name"], "id");
+        assert_eq!(
+            document["paths"]["/users/{id}"].as_object().unwrap().len(),
+            1
+        );
+        assert_eq!(
+            get["responses"]["200"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/User"
+        );
+    }
+
+    #[test]
+    fn test_envelope_schema() {
+        let document = document();
+        let schemas = &document["components"]["schemas"];
+        let count = schemas
+            .as_object()
+            .unwrap()
+            .iter()
+            .find(|(name, _)| name.starts_with("BaseResponse") && name.contains("uint64"))
+            .unwrap()
+            .1;
+        assert_eq!(count["properties"]["version"]["type"], "string");
+        assert_eq!(count["properties"]["data"]["type"], "integer");
+        assert!(schemas
+            .as_object()
+            .unwrap()
+            .keys()
+            .any(|name| name.contains("Array_of_User")));
+    }
+
+    #[cfg(feature = "derive")]
+    #[test]
+    fn test_wrapper_schemas() {
+        use crate::axum::wrappers::{Array, Count};
+        let document = OpenApi::new("Test", "1.0.0")
+            .response::<Array<User>>("GET", "/users")
+            .response::<Count>("GET", "/users/count")
+            .to_json();
+        let schemas = document["components"]["schemas"].as_object().unwrap();
+        let count = schemas
+            .iter()
+            .find(|(name, _)| name.starts_with("BaseResponse") && name.contains("Count"))
+            .unwrap()
+            .1;
+        assert_eq!(count["properties"]["count"]["type"], "integer");
+        assert!(schemas.keys().any(|name| name.contains("Array")));
+    }
+
+    #[tokio::test]
+    async fn test_router() {
+        use tower::ServiceExt;
+        let router: Router = OpenApi::new("Test", "1.0.0")
+            .routes(RouteTable::default())
+            .router("/openapi.json")
+            .merge(swagger_ui("/docs", "/openapi.json"));
+        let request = axum::extract::Request::get("/openapi.json")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = router.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+        let request = axum::extract::Request::get("/docs")
+            .body(axum::body::Body::empty())
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), axum::http::StatusCode::OK);
+    }
+}
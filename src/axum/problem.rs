@@ -0,0 +1,371 @@
+use {
+    crate::{axum::extractor::MultipartFileRejection, serde::response::BaseResponse},
+    axum::{
+        extract::{
+            multipart::MultipartRejection,
+            rejection::{FormRejection, JsonRejection, PathRejection, QueryRejection},
+        },
+        http::{header::CONTENT_TYPE, HeaderValue, StatusCode},
+        response::{IntoResponse, Response},
+    },
+    serde::{Deserialize, Serialize},
+    serde_json::{Map, Value},
+    std::fmt::{self, Display, Formatter},
+    tracing::error,
+};
+
+/// The media type of a serialized `Problem`.
+pub const APPLICATION_PROBLEM_JSON: &str = "application/problem+json";
+const ABOUT_BLANK: &str = "about:blank";
+const SERVER_ERROR_DETAIL: &str = "The server failed to handle the request";
+
+/// The result of a handler returning a `BaseResponse`, or a `Problem` on errors.
+pub type ProblemResult<T> = Result<BaseResponse<T>, Problem>;
+
+/// An error response as described by RFC 7807, rendered as `application/problem+json`.
+/// Without a type, the problem is described by its status code alone, and the title defaults to the reason phrase.
+/// Extension members are serialized next to the standard members.
+/// # Example
+/// ```
+/// use axum::http::StatusCode;
+/// use lib::axum::problem::{Problem, ProblemResult};
+///
+/// #[derive(serde::Serialize)]
+/// struct Balance {
+///     amount: u64,
+/// }
+///
+/// impl lib::serde::response::SerializeObject for Balance {}
+///
+/// async fn withdraw() -> ProblemResult<Balance> {
+///     Err(Problem::new(StatusCode::FORBIDDEN)
+///         .type_uri("https://example.com/probs/out-of-credit")
+///         .title("You do not have enough credit")
+///         .detail("Your current balance is 30, but that costs 50")
+///         .extension("balance", 30))
+/// }
+/// let _router: axum::Router = lib::routes!(post "/withdraw" => withdraw);
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Problem {
+    /// A URI identifying the problem type, `about:blank` by default.
+    #[serde(rename = "type", default = "about_blank")]
+    pub type_uri: String,
+    pub title: String,
+    pub status: u16,
+    /// An explanation specific to this occurrence of the problem.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    /// A URI identifying this occurrence of the problem.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub instance: Option<String>,
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+}
+
+fn about_blank() -> String {
+    ABOUT_BLANK.to_string()
+}
+
+impl Problem {
+    /// Creates a problem with the status code, titled with its reason phrase.
+    pub fn new(status: StatusCode) -> Self {
+        Self {
+            type_uri: about_blank(),
+            title: status.canonical_reason().unwrap_or_default().to_string(),
+            status: status.as_u16(),
+            detail: None,
+            instance: None,
+            extensions: Map::new(),
+        }
+    }
+
+    pub fn type_uri(mut self, type_uri: impl Into<String>) -> Self {
+        self.type_uri = type_uri.into();
+        self
+    }
+
+    pub fn title(mut self, title: impl Into<String>) -> Self {
+        self.title = title.into();
+        self
+    }
+
+    pub fn detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+
+    pub fn instance(mut self, instance: impl Into<String>) -> Self {
+        self.instance = Some(instance.into());
+        self
+    }
+
+    /// Adds an extension member. Values which cannot be serialized are stored as `null`.
+    pub fn extension(mut self, name: impl Into<String>, value: impl Serialize) -> Self {
+        let value = serde_json::to_value(value).unwrap_or_default();
+        self.extensions.insert(name.into(), value);
+        self
+    }
+
+    /// Creates a problem with the error as detail.
+    /// Server errors get a generic detail instead, and the error is logged,
+    /// so internal details like database messages or paths are not sent to the client.
+    pub fn from_error(status: StatusCode, error: impl Display) -> Self {
+        if status.is_server_error() {
+            error!("Responding with {status}: {error}");
+            Problem::new(status).detail(SERVER_ERROR_DETAIL)
+        } else {
+            Problem::new(status).detail(error.to_string())
+        }
+    }
+
+    /// The status code, or 500 Internal Server Error if the status is invalid.
+    pub fn status(&self) -> StatusCode {
+        StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match &self.detail {
+            Some(detail) => write!(f, "{}: {detail}", self.title),
+            None => write!(f, "{}", self.title),
+        }
+    }
+}
+
+impl std::error::Error for Problem {}
+
+impl From<StatusCode> for Problem {
+    fn from(status: StatusCode) -> Self {
+        Problem::new(status)
+    }
+}
+
+impl IntoResponse for Problem {
+    fn into_response(self) -> Response {
+        match serde_json::to_vec(&self) {
+            Ok(body) => (
+                self.status(),
+                [(
+                    CONTENT_TYPE,
+                    HeaderValue::from_static(APPLICATION_PROBLEM_JSON),
+                )],
+                body,
+            )
+                .into_response(),
+            Err(error) => (StatusCode::INTERNAL_SERVER_ERROR, error.to_string()).into_response(),
+        }
+    }
+}
+
+/// Converts the rejections of the axum extractors, which all have a status and a message.
+macro_rules! from_rejection {
+    ($($rejection:ty),*) => {
+        $(
+            impl From<$rejection> for Problem {
+                fn from(rejection: $rejection) -> Self {
+                    Problem::from_error(rejection.status(), rejection.body_text())
+                }
+            }
+        )*
+    };
+}
+
+from_rejection!(
+    FormRejection,
+    JsonRejection,
+    MultipartRejection,
+    PathRejection,
+    QueryRejection
+);
+
+impl From<MultipartFileRejection> for Problem {
+    fn from(rejection: MultipartFileRejection) -> Self {
+        match rejection {
+            MultipartFileRejection::MultipartRejection(rejection) => rejection.into(),
+            rejection => Problem::from_error(rejection.status(), rejection),
+        }
+    }
+}
+
+#[cfg(feature = "multipart")]
+impl From<crate::axum::multipart::MultipartFormRejection> for Problem {
+    fn from(rejection: crate::axum::multipart::MultipartFormRejection) -> Self {
+        use crate::axum::multipart::MultipartFormRejection;
+        match rejection {
+            MultipartFormRejection::Multipart(rejection) => rejection.into(),
+            rejection => {
+                let field = rejection.field().map(str::to_string);
+                Problem::from_error(rejection.status(), rejection).extension("field", field)
+            }
+        }
+    }
+}
+
+#[cfg(feature = "validation")]
+impl From<crate::axum::validation::ValidationErrors> for Problem {
+    fn from(errors: crate::axum::validation::ValidationErrors) -> Self {
+        Problem::new(StatusCode::UNPROCESSABLE_ENTITY)
+            .detail("Validation failed")
+            .extension("fields", errors.errors())
+    }
+}
+
+#[cfg(feature = "validation")]
+impl<R: Into<Problem>> From<crate::axum::validation::ValidRejection<R>> for Problem {
+    fn from(rejection: crate::axum::validation::ValidRejection<R>) -> Self {
+        use crate::axum::validation::ValidRejection;
+        match rejection {
+            ValidRejection::Extractor(rejection) => rejection.into(),
+            ValidRejection::Invalid(errors) => errors.into(),
+        }
+    }
+}
+
+#[cfg(feature = "storage")]
+impl From<crate::axum::storage::StorageError> for Problem {
+    fn from(error: crate::axum::storage::StorageError) -> Self {
+        Problem::from_error(error.status(), error)
+    }
+}
+
+#[cfg(feature = "jwt")]
+impl From<crate::axum::jwt::JwtRejection> for Problem {
+    fn from(rejection: crate::axum::jwt::JwtRejection) -> Self {
+        Problem::from_error(rejection.status(), rejection)
+    }
+}
+
+#[cfg(feature = "diesel")]
+impl From<diesel_crud_trait::CrudError> for Problem {
+    fn from(error: diesel_crud_trait::CrudError) -> Self {
+        match error {
+            diesel_crud_trait::CrudError::NotFound => {
+                Problem::new(StatusCode::NOT_FOUND).detail(error.to_string())
+            }
+            error => Problem::from_error(StatusCode::INTERNAL_SERVER_ERROR, error),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use {
+        super::*,
+        axum::{body::to_bytes, extract::Query},
+        serde_json::json,
+    };
+
+    async fn body(problem: Problem) -> (StatusCode, Option<HeaderValue>, Value) {
+        let response = problem.into_response();
+        let status = response.status();
+        let content_type = response.headers().get(CONTENT_TYPE).cloned();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        (status, content_type, serde_json::from_slice(&body).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_into_response() {
+        let problem = Problem::new(StatusCode::FORBIDDEN)
+            .type_uri("https://example.com/probs/out-of-credit")
+            .title("You do not have enough credit")
+            .detail("Your current balance is 30, but that costs 50")
+            .instance("/account/12345/msgs/abc")
+            .extension("balance", 30);
+        let (status, content_type, body) = body(problem).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        assert_eq!(
+            content_type,
+            Some(HeaderValue::from_static(APPLICATION_PROBLEM_JSON))
+        );
+        assert_eq!(
+            body,
+            json!({
+                "type": "https://example.com/probs/out-of-credit",
+                "title": "You do not have enough credit",
+                "status": 403,
+                "detail": "Your current balance is 30, but that costs 50",
+                "instance": "/account/12345/msgs/abc",
+                "balance": 30
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_defaults() {
+        let (_, _, body) = body(StatusCode::NOT_FOUND.into()).await;
+        assert_eq!(
+            body,
+            json!({ "type": "about:blank", "title": "Not Found", "status": 404 })
+        );
+    }
+
+    #[test]
+    fn test_deserialize() {
+        let problem: Problem =
+            serde_json::from_value(json!({ "title": "Gone", "status": 410, "reason": "removed" }))
+                .unwrap();
+        assert_eq!(
+            problem,
+            Problem::new(StatusCode::GONE).extension("reason", "removed")
+        );
+    }
+
+    #[test]
+    fn test_from_rejection() {
+        #[derive(Debug, Deserialize)]
+        #[allow(dead_code)]
+        struct Params {
+            page: u32,
+        }
+        let uri = "/?page=first".parse().unwrap();
+        let rejection = Query::<Params>::try_from_uri(&uri).unwrap_err();
+        let problem = Problem::from(rejection);
+        assert_eq!(problem.status(), StatusCode::BAD_REQUEST);
+        assert!(problem
+            .detail
+            .unwrap()
+            .starts_with("Failed to deserialize query string"));
+
+        let problem = Problem::from(MultipartFileRejection::NoFiles);
+        assert_eq!(problem.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(problem.detail.as_deref(), Some("No files found"));
+    }
+
+    #[cfg(feature = "validation")]
+    #[tokio::test]
+    async fn test_from_validation_errors() {
+        let mut errors = crate::axum::validation::ValidationErrors::new();
+        errors.add("name", "must not be empty");
+        let (status, _, body) = body(errors.into()).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body["fields"],
+            json!([{ "field": "name", "message": "must not be empty" }])
+        );
+    }
+
+    #[test]
+    fn test_server_error_detail() {
+        let error = std::io::Error::other("/var/lib/app/secret.db is locked");
+        let problem = Problem::from(MultipartFileRejection::Io(error));
+        assert_eq!(problem.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(problem.detail.as_deref(), Some(SERVER_ERROR_DETAIL));
+    }
+
+    #[cfg(feature = "diesel")]
+    #[test]
+    fn test_from_crud_error() {
+        use diesel_crud_trait::CrudError;
+        let problem = Problem::from(CrudError::NotFound);
+        assert_eq!(problem.status(), StatusCode::NOT_FOUND);
+        assert_eq!(problem.detail.as_deref(), Some("Resource not found"));
+
+        let problem = Problem::from(CrudError::PoolError(
+            "password authentication failed".into(),
+        ));
+        assert_eq!(problem.status(), StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(problem.detail.as_deref(), Some(SERVER_ERROR_DETAIL));
+    }
+}
@@ -0,0 +1,210 @@
+pub use inventory;
+pub use route_macros::{delete, get, head, options, patch, post, put, trace};
+use {
+    crate::axum::router::record_route,
+    axum::Router,
+    std::any::{Any, TypeId},
+};
+
+/// A route registered by one of the route attributes, like `#[get("/path")]`.
+#[doc(hidden)]
+pub struct RouteRegistration {
+    method: &'static str,
+    path: &'static str,
+    handler: &'static str,
+    name: Option<&'static str>,
+    state: fn() -> TypeId,
+    register: fn(Box<dyn Any>) -> Box<dyn Any>,
+}
+
+impl RouteRegistration {
+    pub const fn new(
+        method: &'static str,
+        path: &'static str,
+        handler: &'static str,
+        name: Option<&'static str>,
+        state: fn() -> TypeId,
+        register: fn(Box<dyn Any>) -> Box<dyn Any>,
+    ) -> Self {
+        Self {
+            method,
+            path,
+            handler,
+            name,
+            state,
+            register,
+        }
+    }
+}
+
+inventory::collect!(RouteRegistration);
+
+/// Creates a router from all routes registered with the route attributes for the state type.
+/// Prefer the `collect_routes!` macro.
+/// # Panics
+/// If the same method and path is registered by more than one handler.
+pub fn collect<S>() -> Router<S>
+where
+    S: Clone + Send + Sync + 'static,
+{
+    let mut registrations: Vec<_> = inventory::iter::<RouteRegistration>()
+        .filter(|registration| (registration.state)() == TypeId::of::<S>())
+        .collect();
+    registrations.sort_by_key(|registration| (registration.path, registration.method));
+    for pair in registrations.windows(2) {
+        if (pair[0].method, pair[0].path) == (pair[1].method, pair[1].path) {
+            panic!(
+                "Route {} {} is registered by both `{}` and `{}`",
+                pair[0].method, pair[0].path, pair[0].handler, pair[1].handler
+            );
+        }
+    }
+    registrations
+        .into_iter()
+        .fold(Router::new(), |router, registration| {
+            record_route(
+                registration.method,
+                registration.path,
+                registration.handler,
+                registration.name,
+            );
+            *(registration.register)(Box::new(router))
+                .downcast()
+                .expect("Route registration returned a different router")
+        })
+}
+
+/// Creates a router from all handlers annotated with the route attributes, like `#[get("/path")]`.
+/// Routes for handlers using state are collected by passing the state type,
+/// which must be the same type as given to the `state` argument of the attribute.
+///
+/// The attributes take the path, and optionally a name, the state type and any number of layers
+/// for the route, added with `route_layer` like in `routes!`.
+/// Paths are validated at compile time, and registering the same method and path twice
+/// in a module fails the build. Duplicates across modules panic when the routes are collected,
+/// naming both handlers.
+/// # Examples
+/// ```
+/// use lib::axum::registry::{get, post};
+///
+/// #[get("/users/:id")]
+/// async fn show_user() {}
+///
+/// #[post("/users", layer = tower_http::cors::CorsLayer::permissive())]
+/// async fn create_user() {}
+///
+/// let _router: axum::Router = lib::collect_routes!();
+/// ```
+/// ```
+/// use lib::axum::registry::get;
+///
+/// #[derive(Clone)]
+/// struct AppState;
+///
+/// #[get("/", state = AppState)]
+/// async fn index(_state: axum::extract::State<AppState>) {}
+///
+/// let _router: axum::Router<AppState> = lib::collect_routes!(AppState);
+/// ```
+#[macro_export]
+macro_rules! collect_routes {
+    () => {
+        $crate::axum::registry::collect::<()>()
+    };
+    ($state:ty) => {
+        $crate::axum::registry::collect::<$state>()
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::axum::router::RouteTable;
+    use axum::{body::Body, extract::Request, extract::State, http::StatusCode};
+    use tower::ServiceExt;
+
+    #[get("/registry/:id", name = "registry.show")]
+    async fn show() -> &'static str {
+        "show"
+    }
+
+    #[post("/registry/:id", layer = tower_http::cors::CorsLayer::permissive())]
+    async fn update() -> &'static str {
+        "update"
+    }
+
+    #[derive(Clone)]
+    struct RegistryState(&'static str);
+
+    #[get("/stateful", state = RegistryState)]
+    async fn with_state(State(state): State<RegistryState>) -> &'static str {
+        state.0
+    }
+
+    #[derive(Clone)]
+    struct DuplicateState;
+
+    mod first {
+        use super::*;
+
+        #[get("/duplicate", state = DuplicateState)]
+        async fn first() {}
+    }
+
+    mod second {
+        use super::*;
+
+        #[get("/duplicate", state = DuplicateState)]
+        async fn second() {}
+    }
+
+    async fn send(router: Router, method: &str, uri: &str) -> (StatusCode, String) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        (status, String::from_utf8(body.to_vec()).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_collect_routes() {
+        let (router, routes) = RouteTable::record(|| -> Router { crate::collect_routes!() });
+        assert_eq!(
+            send(router.clone(), "GET", "/registry/1").await,
+            (StatusCode::OK, "show".to_string())
+        );
+        assert_eq!(
+            send(router.clone(), "POST", "/registry/1").await,
+            (StatusCode::OK, "update".to_string())
+        );
+        assert_eq!(
+            send(router, "GET", "/stateful").await.0,
+            StatusCode::NOT_FOUND
+        );
+        assert!(routes
+            .find("/registry/:id")
+            .any(|route| route.handler == "update"));
+        assert_eq!(routes.named("registry.show").unwrap().handler, "show");
+    }
+
+    #[tokio::test]
+    async fn test_collect_routes_with_state() {
+        let router = crate::collect_routes!(RegistryState).with_state(RegistryState("state"));
+        assert_eq!(
+            send(router, "GET", "/stateful").await,
+            (StatusCode::OK, "state".to_string())
+        );
+    }
+
+    #[test]
+    #[should_panic(expected = "Route GET /duplicate is registered by both")]
+    fn test_duplicate_routes() {
+        let _router: Router<DuplicateState> = crate::collect_routes!(DuplicateState);
+    }
+}
@@ -1,33 +1,283 @@
 use {
-    crate::serde::response::BaseResponse,
+    crate::serde::response::{BaseResponse, Links, SerializeObject},
     axum::{
+        extract::{OriginalUri, Request},
+        http::{header::LOCATION, HeaderName, HeaderValue, StatusCode},
         response::{IntoResponse, Response},
         Json,
     },
-    serde::Serialize,
+    futures_util::future::BoxFuture,
+    std::{
+        mem,
+        task::{Context, Poll},
+    },
+    tower::{Layer, Service},
+    tower_http::request_id::RequestId,
+    tracing::error,
 };
 
-impl<T: Serialize> IntoResponse for BaseResponse<T> {
+const X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+tokio::task_local! {
+    static RESPONSE_META: RequestMeta;
+}
+
+impl<T: SerializeObject> BaseResponse<T> {
+    /// Sets the status of the response.
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+
+    /// Adds a header to the response, keeping previous values of the header.
+    pub fn with_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.append(name, value);
+        self
+    }
+
+    /// Responds with 201 Created and the URL of the created resource in the `Location` header.
+    /// If the location is not a valid header value, the header is skipped and an error is logged.
+    /// Use `url_for` to build a percent-encoded URL.
+    /// # Example
+    /// ```
+    /// use lib::from;
+    ///
+    /// #[derive(serde::Serialize)]
+    /// struct User {
+    ///     id: u32,
+    /// }
+    ///
+    /// impl lib::serde::response::SerializeObject for User {}
+    ///
+    /// async fn create_user() -> impl axum::response::IntoResponse {
+    ///     let user = User { id: 1 };
+    ///     let location = format!("/users/{}", user.id);
+    ///     from!(user).created(location)
+    /// }
+    /// ```
+    pub fn created(self, location: impl AsRef<str>) -> Self {
+        let response = self.with_status(StatusCode::CREATED);
+        match HeaderValue::try_from(location.as_ref()) {
+            Ok(location) => response.with_header(LOCATION, location),
+            Err(_) => {
+                error!(
+                    "Location `{}` isn't a valid header value",
+                    location.as_ref()
+                );
+                response
+            }
+        }
+    }
+
+    /// Responds with 202 Accepted, for requests which are processed later.
+    pub fn accepted(self) -> Self {
+        self.with_status(StatusCode::ACCEPTED)
+    }
+}
+
+impl<T: SerializeObject> IntoResponse for BaseResponse<T> {
+    fn into_response(mut self) -> Response {
+        // Metadata set on the response takes precedence over the ResponseMetaLayer
+        let _ = RESPONSE_META.try_with(|meta| meta.apply(&mut self));
+        let status = self.status;
+        let headers = mem::take(&mut self.headers);
+        let mut response = Json(self).into_response();
+        // Bodies which cannot be serialized are already turned into an error
+        if response.status().is_success() {
+            *response.status_mut() = status;
+            response.headers_mut().extend(headers);
+        }
+        response
+    }
+}
+
+/// Layer adding metadata to every `BaseResponse` returned by the wrapped routes.
+/// The request id is taken from the `RequestId` extension of `SetRequestIdLayer`,
+/// or the `x-request-id` header, and the self link is the URI of the request.
+/// Metadata set on a response is not overwritten.
+/// # Default Options
+/// - timestamp: true, with the `time` feature
+/// - request_id: true
+/// - self_link: true
+/// # Example
+/// ```
+/// use axum::{extract::Request, ServiceExt};
+/// use lib::{axum::response::ResponseMetaLayer, from, serde::response::Data};
+/// use tower::Layer;
+///
+/// async fn handler() -> impl axum::response::IntoResponse {
+///     from!(Data("Hello"))
+/// }
+///
+/// let router: axum::Router = lib::routes!(get "/" => handler);
+/// let app = ResponseMetaLayer::new().self_link(false).layer(router);
+/// let _service = ServiceExt::<Request>::into_make_service(app);
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct ResponseMetaLayer {
+    #[cfg(feature = "time")]
+    timestamp: bool,
+    request_id: bool,
+    self_link: bool,
+}
+
+impl ResponseMetaLayer {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds the time of the response.
+    #[cfg(feature = "time")]
+    pub fn timestamp(mut self, timestamp: bool) -> Self {
+        self.timestamp = timestamp;
+        self
+    }
+
+    /// Adds the id of the request, if it has one.
+    pub fn request_id(mut self, request_id: bool) -> Self {
+        self.request_id = request_id;
+        self
+    }
+
+    /// Adds the URI of the request as the `self` link.
+    pub fn self_link(mut self, self_link: bool) -> Self {
+        self.self_link = self_link;
+        self
+    }
+
+    fn meta(&self, req: &Request) -> RequestMeta {
+        let request_id = self.request_id.then(|| {
+            req.extensions()
+                .get::<RequestId>()
+                .map(RequestId::header_value)
+                .or_else(|| req.headers().get(X_REQUEST_ID))
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string)
+        });
+        let self_link = self.self_link.then(|| {
+            let uri = match req.extensions().get::<OriginalUri>() {
+                Some(OriginalUri(uri)) => uri,
+                None => req.uri(),
+            };
+            uri.path_and_query()
+                .map_or_else(|| uri.path().to_string(), ToString::to_string)
+        });
+        RequestMeta {
+            #[cfg(feature = "time")]
+            timestamp: self.timestamp,
+            request_id: request_id.flatten(),
+            self_link,
+        }
+    }
+}
+
+impl Default for ResponseMetaLayer {
+    fn default() -> Self {
+        Self {
+            #[cfg(feature = "time")]
+            timestamp: true,
+            request_id: true,
+            self_link: true,
+        }
+    }
+}
+
+impl<S> Layer<S> for ResponseMetaLayer {
+    type Service = ResponseMetaService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        ResponseMetaService {
+            inner,
+            layer: *self,
+        }
+    }
+}
+
+/// Service created by the `ResponseMetaLayer`.
+#[derive(Debug, Clone)]
+pub struct ResponseMetaService<S> {
+    inner: S,
+    layer: ResponseMetaLayer,
+}
+
+impl<S> Service<Request> for ResponseMetaService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = mem::replace(&mut self.inner, clone);
+        let meta = self.layer.meta(&req);
+        Box::pin(RESPONSE_META.scope(meta, async move { inner.call(req).await }))
+    }
+}
+
+/// The metadata of the current request, added to responses by the `ResponseMetaLayer`.
+#[derive(Debug, Clone)]
+struct RequestMeta {
+    #[cfg(feature = "time")]
+    timestamp: bool,
+    request_id: Option<String>,
+    self_link: Option<String>,
+}
+
+impl RequestMeta {
+    fn apply<T: SerializeObject>(&self, response: &mut BaseResponse<T>) {
+        #[cfg(feature = "time")]
+        if self.timestamp && response.timestamp.is_none() {
+            response.timestamp = Some(crate::serde::response::rfc3339(chrono::Utc::now()));
+        }
+        if response.request_id.is_none() {
+            response.request_id.clone_from(&self.request_id);
+        }
+        if let Some(self_link) = &self.self_link {
+            let links = response.links.get_or_insert_with(Links::default);
+            if links.self_link.is_none() {
+                links.self_link = Some(self_link.clone());
+            }
+        }
+    }
+}
+
+/// Responds with 204 No Content, without the `BaseResponse` envelope since there is no body.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct NoContent;
+
+impl IntoResponse for NoContent {
     fn into_response(self) -> Response {
-        Json(self).into_response()
+        StatusCode::NO_CONTENT.into_response()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use axum::http::header::CONTENT_TYPE;
+    use axum::body::{to_bytes, Body};
+    use axum::extract::Request;
+    use axum::http::header::{CACHE_CONTROL, CONTENT_TYPE, LOCATION};
     use axum::http::{HeaderValue, StatusCode};
     use axum::response::IntoResponse;
     use mime::APPLICATION_JSON;
     use serde::Serialize;
 
-    use crate::serde::response::BaseResponse;
+    use super::{NoContent, ResponseMetaLayer};
+    use crate::serde::response::{BaseResponse, Data, Links, Meta, SerializeObject};
 
     #[derive(Serialize)]
     struct Response {
         message: String,
     }
 
+    impl SerializeObject for Response {}
+
     #[test]
     fn test_into_response() {
         let response = BaseResponse::new(
@@ -45,11 +295,136 @@ mod tests {
     }
 
     #[test]
-    fn test_into_response_with_primitive() {
-        let response = BaseResponse::new("", 42);
+    fn test_into_response_with_status() {
+        let message = Response {
+            message: "Hi".to_string(),
+        };
+        let response = crate::from!(202, message).into_response();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+
+        let message = Response {
+            message: "Hi".to_string(),
+        };
+        let response = crate::from!(StatusCode::IM_A_TEAPOT, message)
+            .with_header(CACHE_CONTROL, HeaderValue::from_static("no-store"))
+            .into_response();
+        assert_eq!(response.status(), StatusCode::IM_A_TEAPOT);
+        assert_eq!(
+            response.headers().get(CACHE_CONTROL),
+            Some(&HeaderValue::from_static("no-store"))
+        );
         assert_eq!(
-            response.into_response().status(),
-            StatusCode::INTERNAL_SERVER_ERROR
+            response.headers().get(CONTENT_TYPE),
+            Some(&HeaderValue::from_static(APPLICATION_JSON.as_ref()))
+        );
+    }
+
+    #[test]
+    fn test_created() {
+        let response = BaseResponse::new(
+            "",
+            Response {
+                message: "Hi".to_string(),
+            },
+        )
+        .created("/messages/1")
+        .into_response();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            response.headers().get(LOCATION),
+            Some(&HeaderValue::from_static("/messages/1"))
+        );
+    }
+
+    #[test]
+    fn test_created_with_invalid_location() {
+        let response = BaseResponse::new("", Data(1))
+            .created("/numbers/\n1")
+            .into_response();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert!(response.headers().get(LOCATION).is_none());
+    }
+
+    #[test]
+    fn test_from_with_status_literal() {
+        let response = crate::from!(202, Data(1)).into_response();
+        assert_eq!(response.status(), StatusCode::ACCEPTED);
+    }
+
+    #[test]
+    fn test_no_content() {
+        let response = NoContent.into_response();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(response.headers().get(CONTENT_TYPE).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_into_response_with_primitive() {
+        let response = BaseResponse::new("", Data(42)).created("/numbers/42");
+        let response = response.into_response();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert_eq!(body, r#"{"version":"","data":42}"#);
+    }
+
+    async fn send(layer: ResponseMetaLayer, request: Request) -> String {
+        use tower::{Layer, ServiceExt};
+
+        let router: axum::Router = crate::routes!(
+            get "/items" => || async { crate::from!(Data(1)) },
+            get "/page" => || async {
+                crate::from!(Data([1, 2]))
+                    .with_request_id("own")
+                    .with_meta(Meta::new(2, 2, 5))
+                    .with_links(Links::new().self_link("/page?page=2").next("/page?page=3"))
+            },
+        );
+        let response = layer.layer(router).oneshot(request).await.unwrap();
+        let body = to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        String::from_utf8(body.to_vec()).unwrap()
+    }
+
+    /// A layer without timestamps, so the bodies can be compared.
+    fn meta_layer() -> ResponseMetaLayer {
+        let layer = ResponseMetaLayer::new();
+        #[cfg(feature = "time")]
+        let layer = layer.timestamp(false);
+        layer
+    }
+
+    #[tokio::test]
+    async fn test_response_meta_layer() {
+        let layer = meta_layer();
+        let request = Request::get("/items?page=1")
+            .header("x-request-id", "abc")
+            .body(Body::empty())
+            .unwrap();
+        let expected = format!(
+            r#"{{"version":"{}","request_id":"abc","data":1,"links":{{"self":"/items?page=1"}}}}"#,
+            env!("CARGO_PKG_VERSION")
+        );
+        assert_eq!(send(layer, request).await, expected);
+
+        let request = Request::get("/items").body(Body::empty()).unwrap();
+        let body = send(ResponseMetaLayer::new(), request).await;
+        #[cfg(feature = "time")]
+        assert!(body.contains(r#""timestamp":""#));
+        assert!(!body.contains("request_id"));
+    }
+
+    #[tokio::test]
+    async fn test_response_meta_per_response() {
+        let layer = meta_layer();
+        let request = Request::get("/page")
+            .header("x-request-id", "abc")
+            .body(Body::empty())
+            .unwrap();
+        let expected = format!(
+            r#"{{"version":"{}","request_id":"own","data":[1,2],{},{}}}"#,
+            env!("CARGO_PKG_VERSION"),
+            r#""meta":{"page":2,"per_page":2,"total":5,"total_pages":3}"#,
+            r#""links":{"self":"/page?page=2","next":"/page?page=3"}"#
         );
+        assert_eq!(send(layer, request).await, expected);
     }
 }
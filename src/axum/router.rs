@@ -1,3 +1,22 @@
+use {
+    axum::{
+        async_trait,
+        extract::FromRequestParts,
+        http::{request::Parts, Method, StatusCode},
+        response::{IntoResponse, Response},
+    },
+    std::{
+        cell::RefCell,
+        cmp::Ordering,
+        collections::BTreeSet,
+        fmt::{self, Display, Formatter},
+        ops::Deref,
+        sync::Arc,
+    },
+    thiserror::Error,
+    tracing::{info, warn},
+};
+
 /// Create an axum router function with the given body or routes.
 /// # Examples
 /// ```
@@ -40,40 +59,191 @@ macro_rules! router {
         }
     };
     ($route:expr, $router:expr) => {
-        router!(axum::Router::new().nest($route, $router));
+        router!(axum::Router::new().nest($route, $crate::axum::router::nest_routes($route, || $router)));
     };
     ($route:expr, $router:expr, $state:ty) => {
-        router!(axum::Router::new().nest($route, $router); $state);
+        router!(axum::Router::new().nest($route, $crate::axum::router::nest_routes($route, || $router)); $state);
     };
     ($route:expr, $router:expr, $state:ident: $($bound:tt),*) => {
-        router!(axum::Router::new().nest($route, $router); $state: $($bound),*);
+        router!(axum::Router::new().nest($route, $crate::axum::router::nest_routes($route, || $router)); $state: $($bound),*);
     };
       ($route:expr, $router:expr, $generic:ident: $($bound:tt),* -> $state:ty) => {
-        router!(axum::Router::new().nest($route, $router); $generic: $($bound),* -> $state);
+        router!(axum::Router::new().nest($route, $crate::axum::router::nest_routes($route, || $router)); $generic: $($bound),* -> $state);
     };
-    ($($method:ident $route:expr => $func:expr),* $(,)?) => {
-        router!($crate::routes!($($method $route => $func),*));
+    ($($routes:tt)*) => {
+        router!($crate::routes!($($routes)*));
     };
 }
 
 /// Create a router with the given routes.
+/// The routes are recorded in the `RouteTable` when the router is created with `RouteTable::record`.
+///
+/// A route is either a method and a path, a list of methods sharing a handler,
+/// or a path literal with a handler for each method, which creates a single `MethodRouter` for the path.
+/// The `any` method matches every method, and a `fallback` in a method map
+/// handles the methods without a handler, instead of responding with `405 Method Not Allowed`.
+///
+/// Each route can be prefixed with attributes, in this order:
+/// - `#[name = "..."]` names the route, to generate URLs with `url_for`.
+///   Routes with a function as handler can be named with `=> handler as "..."` instead,
+///   which also accepts the `NAME` of a route declared with `named_routes!`.
+/// - `#[layer(...)]` adds one or more layers to the route, with `route_layer`,
+///   so guards like authentication only run for requests matching the route.
+///
+/// Routes with the `nest` method create a group, nesting the router under the prefix,
+/// with the layers applied to every route in the group. Nesting under `/` merges the router instead.
+///
+/// Routers with only functions as handlers are expanded at once, while routers with closures
+/// or method maps are expanded one route at a time, so very large ones may need a higher
+/// `recursion_limit`.
 /// # Examples
 /// ```
+/// use axum::extract::DefaultBodyLimit;
+///
 /// async fn index() {}
+/// async fn show_user() {}
+/// async fn update_user() {}
 ///
 /// let _: axum::Router<()> = lib::routes!(
 ///     get "/" => index,
-///     post "/" => || async {}
+///     post "/" => || async {},
+///     get, head "/health" => || async {},
+///     any "/echo" => || async {},
+///     #[name = "user.show"] get "/users/:id" => show_user,
+///     put "/users/:id" => update_user as "user.update",
+///     #[layer(DefaultBodyLimit::max(1024))] post "/upload" => || async {},
+///     #[layer(DefaultBodyLimit::disable())] nest "/admin" => lib::routes!(
+///         get "/stats" => || async {}
+///     )
+/// );
+/// ```
+/// ```
+/// async fn show() {}
+/// async fn update() {}
+/// async fn not_allowed() {}
+///
+/// let _: axum::Router<()> = lib::routes!(
+///     get "/users" => || async {},
+///     "/users/:id" => { get: show, put: update, fallback: not_allowed },
 /// );
 /// ```
 #[macro_export]
 macro_rules! routes {
-    ($($method:ident $route:expr => $func:expr),* $(,)?) => {
-        axum::Router::new()
-            $(
-                .route($route, axum::routing::$method($func))
-            )*
+    (@entry $router:ident; [] [$($layer:expr),*] nest $route:expr => $nested:expr) => {{
+        let route = $route;
+        let nested = $crate::axum::router::nest_routes(route, || $nested)$(.route_layer($layer))*;
+        if route == "/" {
+            $router.merge(nested)
+        } else {
+            $router.nest(route, nested)
+        }
+    }};
+    (@entry $router:ident; [$name:expr] [$($layer:expr),*] nest $route:expr => $nested:expr) => {
+        compile_error!("Nested groups cannot be named, name the routes in the group instead")
+    };
+    (@entry $router:ident; [$($name:expr)?] [$($layer:expr),*] $route:expr => {
+        $($method:ident: $func:expr),* $(,)?
+    }) => {{
+        let route = $route;
+        let name = $crate::routes!(@name $($name)?);
+        $(
+            $crate::axum::router::record_route(stringify!($method), route, stringify!($func), name);
+        )*
+        $router.route(
+            route,
+            axum::routing::MethodRouter::new()$(.$method($func))*$(.route_layer($layer))*,
+        )
+    }};
+    (@entry $router:ident; [$($name:expr)?] [$($layer:expr),*] $method:ident $route:expr => $func:expr) => {{
+        let route = $route;
+        $crate::axum::router::record_route(
+            stringify!($method),
+            route,
+            stringify!($func),
+            $crate::routes!(@name $($name)?),
+        );
+        $router.route(route, axum::routing::$method($func)$(.route_layer($layer))*)
+    }};
+    (@entry $router:ident; [$($name:expr)?] [$($layer:expr),*] $method:ident $(, $more:ident)+ $route:expr => $func:expr) => {{
+        let route = $route;
+        let name = $crate::routes!(@name $($name)?);
+        for method in [stringify!($method) $(, stringify!($more))+] {
+            $crate::axum::router::record_route(method, route, stringify!($func), name);
+        }
+        let filter = $crate::routes!(@filter $method)$(.or($crate::routes!(@filter $more)))+;
+        $router.route(route, axum::routing::on(filter, $func)$(.route_layer($layer))*)
+    }};
+    (@filter delete) => { axum::routing::MethodFilter::DELETE };
+    (@filter get) => { axum::routing::MethodFilter::GET };
+    (@filter head) => { axum::routing::MethodFilter::HEAD };
+    (@filter options) => { axum::routing::MethodFilter::OPTIONS };
+    (@filter patch) => { axum::routing::MethodFilter::PATCH };
+    (@filter post) => { axum::routing::MethodFilter::POST };
+    (@filter put) => { axum::routing::MethodFilter::PUT };
+    (@filter trace) => { axum::routing::MethodFilter::TRACE };
+    (@name $name:expr) => {
+        Some($name)
     };
+    (@name) => {
+        None
+    };
+    // Method maps and handlers other than functions can't be matched in a single repetition
+    // with the other routes, so routers using them are expanded one route at a time.
+    // Handlers named with `as` are matched first, as identifiers, since parsing them
+    // as an expression fails on the cast, and as a path fails on `move` closures
+    (@munch $router:ident; $(,)?) => {
+        $router
+    };
+    (@munch $router:ident;
+        $(#[name = $name:literal])?
+        $(#[layer($($layer:expr),+ $(,)?)])*
+        $route:literal => { $($map:tt)* }
+        $(, $($rest:tt)*)?
+    ) => {{
+        let $router = $crate::routes!(
+            @entry $router; [$($name)?] [$($($layer),+),*] $route => { $($map)* }
+        );
+        $crate::routes!(@munch $router; $($($rest)*)?)
+    }};
+    (@munch $router:ident;
+        $(#[name = $name:literal])?
+        $(#[layer($($layer:expr),+ $(,)?)])*
+        $method:ident $(, $more:ident)* $route:expr => $($func:ident)::+ as $as_name:expr
+        $(, $($rest:tt)*)?
+    ) => {{
+        let $router = $crate::routes!(
+            @entry $router; [$($name)? $as_name] [$($($layer),+),*] $method $(, $more)* $route => $($func)::+
+        );
+        $crate::routes!(@munch $router; $($($rest)*)?)
+    }};
+    (@munch $router:ident;
+        $(#[name = $name:literal])?
+        $(#[layer($($layer:expr),+ $(,)?)])*
+        $method:ident $(, $more:ident)* $route:expr => $func:expr
+        $(, $($rest:tt)*)?
+    ) => {{
+        let $router = $crate::routes!(
+            @entry $router; [$($name)?] [$($($layer),+),*] $method $(, $more)* $route => $func
+        );
+        $crate::routes!(@munch $router; $($($rest)*)?)
+    }};
+    ($(
+        $(#[name = $name:literal])?
+        $(#[layer($($layer:expr),+ $(,)?)])*
+        $method:ident $(, $more:ident)* $route:expr => $($func:ident)::+ $(as $as_name:expr)?
+    ),* $(,)?) => {{
+        let router = axum::Router::new();
+        $(
+            let router = $crate::routes!(
+                @entry router; [$($name)? $($as_name)?] [$($($layer),+),*] $method $(, $more)* $route => $($func)::+
+            );
+        )*
+        router
+    }};
+    ($($input:tt)*) => {{
+        let router = axum::Router::new();
+        $crate::routes!(@munch router; $($input)*)
+    }};
 }
 
 /// Merges the given routers into a single router.
@@ -90,10 +260,287 @@ macro_rules! join_routes {
     };
 }
 
+/// The methods handled by a recorded route.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum RouteMethod {
+    /// A single method, like `GET`.
+    Method(Method),
+    /// Every method, for routes created with `any`.
+    Any,
+    /// The methods without another handler, for a `fallback` in a method map.
+    Fallback,
+}
+
+impl RouteMethod {
+    /// Returns the method, if the route handles a single method.
+    pub fn as_method(&self) -> Option<&Method> {
+        match self {
+            RouteMethod::Method(method) => Some(method),
+            RouteMethod::Any | RouteMethod::Fallback => None,
+        }
+    }
+
+    fn parse(method: &str) -> Self {
+        match method {
+            "any" => RouteMethod::Any,
+            "fallback" => RouteMethod::Fallback,
+            method => RouteMethod::Method(
+                Method::from_bytes(method.to_uppercase().as_bytes())
+                    .expect("Identifiers are valid methods"),
+            ),
+        }
+    }
+}
+
+impl Display for RouteMethod {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        match self {
+            RouteMethod::Method(method) => f.pad(method.as_str()),
+            RouteMethod::Any => f.pad("ANY"),
+            RouteMethod::Fallback => f.pad("FALLBACK"),
+        }
+    }
+}
+
+impl PartialOrd for RouteMethod {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for RouteMethod {
+    fn cmp(&self, other: &Self) -> Ordering {
+        let key = |method: &Self| match method {
+            RouteMethod::Method(method) => (0, method.as_str().to_string()),
+            RouteMethod::Any => (1, String::new()),
+            RouteMethod::Fallback => (2, String::new()),
+        };
+        key(self).cmp(&key(other))
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for RouteMethod {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// A route recorded by the `routes!` and `router!` macros.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct RouteInfo {
+    pub path: String,
+    pub method: RouteMethod,
+    pub handler: String,
+    pub name: Option<String>,
+}
+
+impl Display for RouteInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "{:<7} {} => {}", self.method, self.path, self.handler)?;
+        if let Some(name) = &self.name {
+            write!(f, " as {name}")?;
+        }
+        Ok(())
+    }
+}
+
+thread_local! {
+    static PREFIXES: RefCell<Vec<String>> = const { RefCell::new(Vec::new()) };
+    static RECORDERS: RefCell<Vec<BTreeSet<RouteInfo>>> = const { RefCell::new(Vec::new()) };
+}
+
+/// The routes of a router created with the `routes!`, `router!` and `collect_routes!` macros,
+/// sorted by path. Nested paths include the prefix given to `router!` or a `nest` route.
+/// Routes added with `axum::Router::route`, or nested with `axum::Router::nest`, are not recorded.
+///
+/// The table is created together with the router by `RouteTable::record`. To use it in handlers,
+/// e.g. to build URLs with `url_for`, add it to the router as an `Extension`,
+/// and extract it as `RouteTable`.
+/// # Example
+/// ```
+/// use axum::Extension;
+/// use lib::axum::router::RouteTable;
+///
+/// let (router, routes) = RouteTable::record(|| -> axum::Router {
+///     lib::routes!(get "/health" => |routes: RouteTable| async move { routes.to_string() })
+/// });
+/// assert!(routes.iter().any(|route| route.path == "/health"));
+/// let _router = router.layer(Extension(routes));
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RouteTable(Arc<[RouteInfo]>);
+
+impl RouteTable {
+    /// Calls the function, returning its result with the table of the routes it created.
+    /// Tables recorded at the same time, like for a router created inside another, both get the routes.
+    pub fn record<T>(f: impl FnOnce() -> T) -> (T, Self) {
+        RECORDERS.with_borrow_mut(|recorders| recorders.push(BTreeSet::new()));
+        let result = f();
+        let routes = RECORDERS
+            .with_borrow_mut(|recorders| recorders.pop())
+            .unwrap_or_default();
+        (result, routes.into_iter().collect())
+    }
+
+    /// Returns the routes matching the path template.
+    pub fn find<'a>(&'a self, path: &'a str) -> impl Iterator<Item = &'a RouteInfo> {
+        self.iter().filter(move |route| route.path == path)
+    }
+
+    /// Returns the route with the name.
+    pub fn named(&self, name: &str) -> Option<&RouteInfo> {
+        self.iter()
+            .find(|route| route.name.as_deref() == Some(name))
+    }
+
+    /// Logs every route at the info level.
+    pub fn log(&self) {
+        for route in self.iter() {
+            info!("{route}");
+        }
+    }
+}
+
+impl Deref for RouteTable {
+    type Target = [RouteInfo];
+
+    fn deref(&self) -> &Self::Target {
+        &self.0
+    }
+}
+
+impl Display for RouteTable {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        for route in self.iter() {
+            writeln!(f, "{route}")?;
+        }
+        Ok(())
+    }
+}
+
+impl From<Vec<RouteInfo>> for RouteTable {
+    fn from(mut routes: Vec<RouteInfo>) -> Self {
+        routes.sort();
+        routes.dedup();
+        Self(routes.into())
+    }
+}
+
+impl FromIterator<RouteInfo> for RouteTable {
+    fn from_iter<I: IntoIterator<Item = RouteInfo>>(iter: I) -> Self {
+        Self::from(iter.into_iter().collect::<Vec<_>>())
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for RouteTable {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_seq(self.iter())
+    }
+}
+
+/// Responds with the route table as JSON, for use as an admin endpoint.
+#[cfg(feature = "serde")]
+impl IntoResponse for RouteTable {
+    fn into_response(self) -> Response {
+        axum::Json(self).into_response()
+    }
+}
+
+/// Rejection type for the `RouteTable` extractor.
+#[derive(Debug, Error)]
+pub enum RouteTableRejection {
+    #[error("Route table is missing, add it to the router with `Extension`")]
+    MissingExtension,
+}
+
+impl IntoResponse for RouteTableRejection {
+    fn into_response(self) -> Response {
+        (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for RouteTable
+where
+    S: Send + Sync,
+{
+    type Rejection = RouteTableRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<RouteTable>()
+            .cloned()
+            .ok_or(RouteTableRejection::MissingExtension)
+    }
+}
+
+/// Records a route in the route tables being recorded with `RouteTable::record`.
+/// If the name is already used for a route with another path, like for a named router
+/// nested under two prefixes, the name keeps its first path and a warning is logged.
+#[doc(hidden)]
+pub fn record_route(method: &str, path: &str, handler: &str, name: Option<&str>) {
+    let path = PREFIXES.with_borrow(|prefixes| {
+        let prefix = prefixes.concat();
+        match path {
+            "/" if !prefix.is_empty() => prefix,
+            _ => prefix + path,
+        }
+    });
+    let method = RouteMethod::parse(method);
+    RECORDERS.with_borrow_mut(|recorders| {
+        let mut conflict = None;
+        for routes in recorders {
+            let existing = name.and_then(|name| {
+                routes
+                    .iter()
+                    .find(|route| route.name.as_deref() == Some(name) && route.path != path)
+            });
+            let name = match existing {
+                Some(route) => {
+                    conflict.get_or_insert_with(|| route.path.clone());
+                    None
+                }
+                None => name.map(str::to_string),
+            };
+            routes.insert(RouteInfo {
+                path: path.clone(),
+                method: method.clone(),
+                handler: handler.to_string(),
+                name,
+            });
+        }
+        if let (Some(name), Some(first)) = (name, conflict) {
+            warn!("Route name `{name}` is used for both {first} and {path}, keeping {first}");
+        }
+    });
+}
+
+/// Records the routes created by `f` as nested under the prefix.
+#[doc(hidden)]
+pub fn nest_routes<T>(prefix: &str, f: impl FnOnce() -> T) -> T {
+    let prefix = prefix.strip_suffix('/').unwrap_or(prefix);
+    PREFIXES.with_borrow_mut(|prefixes| prefixes.push(prefix.to_string()));
+    let result = f();
+    PREFIXES.with_borrow_mut(|prefixes| prefixes.pop());
+    result
+}
+
 #[cfg(test)]
 mod tests {
-    use axum::extract::State;
+    use super::{RouteInfo, RouteMethod, RouteTable};
     use axum::Router;
+    use axum::{
+        body::Body,
+        extract::{Request, State},
+        http::{header::AUTHORIZATION, Method, StatusCode},
+        middleware::{from_fn, Next},
+        response::Response,
+    };
+    use tower::ServiceExt;
 
     async fn index() {}
 
@@ -153,6 +600,223 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_route_table() {
+        router!(
+            "/table",
+            routes!(
+                get "/" => index,
+                post "/:id" => || async {}
+            )
+        );
+        let (_router, table) = RouteTable::record(router);
+        assert_eq!(
+            table.find("/table").collect::<Vec<_>>(),
+            vec![&RouteInfo {
+                path: "/table".to_string(),
+                method: RouteMethod::Method(Method::GET),
+                handler: "index".to_string(),
+                name: None,
+            }]
+        );
+        assert_eq!(
+            table.find("/table/:id").next().unwrap().method,
+            RouteMethod::Method(Method::POST)
+        );
+        assert!(table.to_string().contains("GET     /table => index"));
+    }
+
+    async fn status(router: Router, uri: &str, authorized: bool) -> StatusCode {
+        let mut request = Request::builder().uri(uri);
+        if authorized {
+            request = request.header(AUTHORIZATION, "yes");
+        }
+        router
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap()
+            .status()
+    }
+
+    async fn guard(request: Request, next: Next) -> Result<Response, StatusCode> {
+        if request.headers().contains_key(AUTHORIZATION) {
+            Ok(next.run(request).await)
+        } else {
+            Err(StatusCode::UNAUTHORIZED)
+        }
+    }
+
+    #[tokio::test]
+    async fn test_route_layers() {
+        let router: Router = routes!(
+            get "/" => index,
+            #[layer(from_fn(guard))] get "/private" => index
+        );
+        assert_eq!(status(router.clone(), "/", false).await, StatusCode::OK);
+        assert_eq!(
+            status(router.clone(), "/private", false).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(status(router, "/private", true).await, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_nested_group() {
+        let (router, table) = RouteTable::record(|| -> Router {
+            routes!(
+                get "/" => index,
+                #[layer(from_fn(guard))] nest "/group" => routes!(
+                    #[name = "group.show"] get "/:id" => index,
+                    nest "/" => routes!(get "/inner" => index)
+                )
+            )
+        });
+        assert_eq!(status(router.clone(), "/", false).await, StatusCode::OK);
+        assert_eq!(
+            status(router.clone(), "/group/1", false).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            status(router.clone(), "/group/1", true).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(router.clone(), "/group/inner", true).await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            status(router, "/missing", false).await,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(table.named("group.show").unwrap().path, "/group/:id");
+        assert!(table.find("/group/inner").next().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_named_with_as() {
+        let (router, table) = RouteTable::record(|| -> Router {
+            routes!(
+                get "/as" => || async {},
+                #[layer(from_fn(guard))] get "/as/:id" => index as "as.show",
+                put "/as/:id" => index
+            )
+        });
+        assert_eq!(
+            status(router.clone(), "/as/1", false).await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(send(router, "PUT", "/as/1").await, StatusCode::OK);
+        let (_router, fast) =
+            RouteTable::record(|| -> Router { routes!(get "/as/fast/:id" => index as "as.fast") });
+        assert_eq!(table.named("as.show").unwrap().path, "/as/:id");
+        assert_eq!(fast.named("as.fast").unwrap().path, "/as/fast/:id");
+    }
+
+    async fn send(router: Router, method: &str, uri: &str) -> StatusCode {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .body(Body::empty())
+            .unwrap();
+        router.oneshot(request).await.unwrap().status()
+    }
+
+    #[tokio::test]
+    async fn test_method_list() {
+        let (router, table) = RouteTable::record(|| -> Router {
+            routes!(
+                get, head "/list" => index,
+                any "/any" => index
+            )
+        });
+        assert_eq!(send(router.clone(), "GET", "/list").await, StatusCode::OK);
+        assert_eq!(send(router.clone(), "HEAD", "/list").await, StatusCode::OK);
+        assert_eq!(
+            send(router.clone(), "POST", "/list").await,
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(send(router, "DELETE", "/any").await, StatusCode::OK);
+        let methods: Vec<_> = table.find("/list").map(|route| &route.method).collect();
+        assert_eq!(
+            methods,
+            vec![
+                &RouteMethod::Method(Method::GET),
+                &RouteMethod::Method(Method::HEAD)
+            ]
+        );
+        assert_eq!(table.find("/any").next().unwrap().method, RouteMethod::Any);
+    }
+
+    #[tokio::test]
+    async fn test_method_map() {
+        let (router, table) = RouteTable::record(|| -> Router {
+            routes!(
+                get "/map" => index,
+                #[name = "map.show"]
+                #[layer(from_fn(guard))]
+                "/map/:id" => {
+                    get: index,
+                    put: || async { StatusCode::ACCEPTED },
+                },
+                "/map/:id/fallback" => {
+                    delete: index,
+                    fallback: || async { StatusCode::IM_A_TEAPOT }
+                },
+            )
+        });
+        assert_eq!(send(router.clone(), "GET", "/map").await, StatusCode::OK);
+        assert_eq!(
+            send(router.clone(), "GET", "/map/1").await,
+            StatusCode::UNAUTHORIZED
+        );
+        assert_eq!(
+            send(router.clone(), "POST", "/map/1").await,
+            StatusCode::METHOD_NOT_ALLOWED
+        );
+        assert_eq!(
+            send(router.clone(), "DELETE", "/map/1/fallback").await,
+            StatusCode::OK
+        );
+        assert_eq!(
+            send(router, "PATCH", "/map/1/fallback").await,
+            StatusCode::IM_A_TEAPOT
+        );
+        let methods: Vec<_> = table.find("/map/:id").map(|route| &route.method).collect();
+        assert_eq!(
+            methods,
+            vec![
+                &RouteMethod::Method(Method::GET),
+                &RouteMethod::Method(Method::PUT)
+            ]
+        );
+        assert!(table
+            .find("/map/:id/fallback")
+            .any(|route| route.method == RouteMethod::Fallback));
+        assert_eq!(table.named("map.show").unwrap().path, "/map/:id");
+    }
+
+    #[test]
+    fn test_named_router_nested_twice() {
+        let named = || -> Router { routes!(get "/users/:id" => index as "user.show") };
+        let (_router, table) = RouteTable::record(|| -> Router {
+            routes!(
+                nest "/v1" => named(),
+                nest "/v2" => named()
+            )
+        });
+        assert_eq!(table.named("user.show").unwrap().path, "/v1/users/:id");
+        assert_eq!(table.find("/v2/users/:id").next().unwrap().name, None);
+    }
+
+    #[test]
+    fn test_router_with_method_map() {
+        router!(
+            get "/" => index,
+            "/items/:id" => { get: index, delete: index }
+        );
+        let _router = router();
+    }
+
     #[test]
     fn test_join_routes() {
         let _router: Router = join_routes![Router::new(), Router::new()];
@@ -0,0 +1,234 @@
+use {
+    axum::{
+        async_trait,
+        extract::FromRequestParts,
+        http::{request::Parts, StatusCode},
+        response::{IntoResponse, Response},
+    },
+    serde::{de::DeserializeOwned, Deserialize, Serialize},
+    serde_json::Value,
+    std::{
+        collections::HashMap,
+        sync::{Arc, Mutex, MutexGuard},
+        time::Duration,
+    },
+    thiserror::Error,
+};
+
+const FLASH_KEY: &str = "_flash";
+
+/// A message stored in the session until it is read, typically shown once after a redirect.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FlashMessage {
+    pub level: String,
+    pub message: String,
+}
+
+impl FlashMessage {
+    pub fn new(level: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            level: level.into(),
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub(crate) struct SessionState {
+    pub(crate) id: Option<String>,
+    pub(crate) data: HashMap<String, Value>,
+    pub(crate) expiry: Option<Duration>,
+    pub(crate) modified: bool,
+    pub(crate) rotate: bool,
+    pub(crate) destroyed: bool,
+}
+
+/// Extractor for the session of the current request.
+/// Requires a `SessionLayer` on the router.
+/// Changes are persisted in the session store after the handler has returned.
+/// # Example
+/// ```
+/// use lib::axum::session::Session;
+///
+/// async fn login(session: Session) {
+///     session.rotate();
+///     session.insert("user", "admin").unwrap();
+///     session.flash("info", "Logged in");
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct Session {
+    state: Arc<Mutex<SessionState>>,
+}
+
+impl Session {
+    pub(crate) fn new(id: Option<String>, data: HashMap<String, Value>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(SessionState {
+                id,
+                data,
+                ..SessionState::default()
+            })),
+        }
+    }
+
+    pub(crate) fn state(&self) -> MutexGuard<'_, SessionState> {
+        self.state.lock().expect("Session lock poisoned")
+    }
+
+    /// The id of the session, or `None` if the session has not been persisted yet.
+    pub fn id(&self) -> Option<String> {
+        self.state().id.clone()
+    }
+
+    /// Gets the value for the given key.
+    /// Returns `None` if the key does not exist, or the value cannot be deserialized into `T`.
+    pub fn get<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        self.state()
+            .data
+            .get(key)
+            .and_then(|value| serde_json::from_value(value.clone()).ok())
+    }
+
+    /// Inserts a value for the given key, replacing any previous value.
+    pub fn insert<T: Serialize>(&self, key: impl Into<String>, value: T) -> serde_json::Result<()> {
+        let value = serde_json::to_value(value)?;
+        let mut state = self.state();
+        state.data.insert(key.into(), value);
+        state.modified = true;
+        Ok(())
+    }
+
+    /// Removes the value for the given key, returning it if it existed.
+    pub fn remove<T: DeserializeOwned>(&self, key: &str) -> Option<T> {
+        let mut state = self.state();
+        let value = state.data.remove(key)?;
+        state.modified = true;
+        serde_json::from_value(value).ok()
+    }
+
+    /// Removes all values from the session, but keeps the session itself.
+    pub fn clear(&self) {
+        let mut state = self.state();
+        state.data.clear();
+        state.modified = true;
+    }
+
+    /// Deletes the session from the store, and removes the session cookie.
+    /// Typically used when logging out.
+    pub fn destroy(&self) {
+        let mut state = self.state();
+        state.data.clear();
+        state.destroyed = true;
+    }
+
+    /// Gives the session a new id, keeping the data.
+    /// Should be called whenever the privileges of the user change, e.g. when logging in,
+    /// to prevent session fixation.
+    pub fn rotate(&self) {
+        let mut state = self.state();
+        state.rotate = true;
+        state.modified = true;
+    }
+
+    /// Overrides how long the session lives after the current request.
+    pub fn set_expiry(&self, expiry: Duration) {
+        let mut state = self.state();
+        state.expiry = Some(expiry);
+        state.modified = true;
+    }
+
+    /// Adds a flash message, which is kept until it is read with `take_flashes`.
+    pub fn flash(&self, level: impl Into<String>, message: impl Into<String>) {
+        let mut flashes = self.peek_flashes();
+        flashes.push(FlashMessage::new(level, message));
+        // A vector of strings cannot fail to serialize
+        let _ = self.insert(FLASH_KEY, flashes);
+    }
+
+    /// Returns the flash messages without removing them.
+    pub fn peek_flashes(&self) -> Vec<FlashMessage> {
+        self.get(FLASH_KEY).unwrap_or_default()
+    }
+
+    /// Returns and removes all flash messages.
+    pub fn take_flashes(&self) -> Vec<FlashMessage> {
+        self.remove(FLASH_KEY).unwrap_or_default()
+    }
+}
+
+/// Rejection type for the `Session` extractor.
+#[derive(Debug, Error)]
+pub enum SessionRejection {
+    #[error("Session layer is missing")]
+    MissingLayer,
+}
+
+impl IntoResponse for SessionRejection {
+    fn into_response(self) -> Response {
+        (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+    }
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for Session
+where
+    S: Send + Sync,
+{
+    type Rejection = SessionRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<Session>()
+            .cloned()
+            .ok_or(SessionRejection::MissingLayer)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_insert_and_get() {
+        let session = Session::new(None, HashMap::new());
+        session.insert("key", 42).unwrap();
+        assert_eq!(session.get::<i32>("key"), Some(42));
+        assert_eq!(session.get::<String>("key"), None);
+        assert!(session.state().modified);
+    }
+
+    #[test]
+    fn test_remove() {
+        let session = Session::new(None, HashMap::new());
+        session.insert("key", "value").unwrap();
+        assert_eq!(session.remove::<String>("key"), Some("value".to_string()));
+        assert_eq!(session.get::<String>("key"), None);
+    }
+
+    #[test]
+    fn test_flash() {
+        let session = Session::new(None, HashMap::new());
+        session.flash("info", "Hello");
+        session.flash("error", "World");
+        assert_eq!(session.peek_flashes().len(), 2);
+        assert_eq!(
+            session.take_flashes(),
+            vec![
+                FlashMessage::new("info", "Hello"),
+                FlashMessage::new("error", "World")
+            ]
+        );
+        assert!(session.take_flashes().is_empty());
+    }
+
+    #[test]
+    fn test_destroy() {
+        let session = Session::new(Some("id".to_string()), HashMap::new());
+        session.insert("key", 1).unwrap();
+        session.destroy();
+        assert!(session.state().destroyed);
+        assert_eq!(session.get::<i32>("key"), None);
+    }
+}
@@ -0,0 +1,439 @@
+use {
+    super::{
+        extractor::Session,
+        store::{SessionError, SessionRecord, SessionStore},
+    },
+    crate::axum::cookies::{random_token, request_jar},
+    axum::{
+        extract::Request,
+        http::{header::SET_COOKIE, HeaderMap, HeaderValue, StatusCode},
+        response::{IntoResponse, Response},
+    },
+    cookie::{Cookie, CookieJar, Key, SameSite},
+    futures_util::future::BoxFuture,
+    std::{
+        collections::HashMap,
+        sync::Arc,
+        task::{Context, Poll},
+        time::{Duration, SystemTime},
+    },
+    tower::{Layer, Service},
+    tracing::error,
+};
+
+/// How the session id is protected in the cookie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CookieSecurity {
+    /// The id is signed, it can be read but not modified by the client.
+    Signed,
+    /// The id is encrypted and authenticated, it can neither be read nor modified by the client.
+    #[default]
+    Private,
+}
+
+#[derive(Clone)]
+struct SessionConfig {
+    store: Arc<dyn SessionStore>,
+    key: Key,
+    security: CookieSecurity,
+    cookie_name: String,
+    path: String,
+    domain: Option<String>,
+    secure: bool,
+    same_site: SameSite,
+    expiry: Duration,
+}
+
+/// Layer that loads the session before the request is handled, and persists it afterwards.
+/// The session cookie only contains the session id, the data is kept in the `SessionStore`.
+///
+/// A session is only created when data is inserted, and its expiry is renewed whenever it is modified.
+/// # Default Options
+/// - Cookie name == "session"
+/// - Security == Private (encrypted)
+/// - Path == "/"
+/// - Secure == true
+/// - SameSite == Lax
+/// - Expiry == 24 hours
+/// # Example
+/// ```
+/// use lib::axum::session::{Key, MemoryStore, Session, SessionLayer};
+///
+/// async fn index(session: Session) -> String {
+///     session.get::<String>("user").unwrap_or_default()
+/// }
+///
+/// let layer = SessionLayer::new(MemoryStore::new(), Key::generate()).cookie_name("id");
+/// let _router: axum::Router = lib::routes!(get "/" => index).layer(layer);
+/// ```
+#[derive(Clone)]
+pub struct SessionLayer {
+    config: Arc<SessionConfig>,
+}
+
+impl SessionLayer {
+    /// Creates a layer using the given store, and the key for signing or encrypting cookies.
+    pub fn new(store: impl SessionStore, key: Key) -> Self {
+        Self {
+            config: Arc::new(SessionConfig {
+                store: Arc::new(store),
+                key,
+                security: CookieSecurity::default(),
+                cookie_name: "session".to_string(),
+                path: "/".to_string(),
+                domain: None,
+                secure: true,
+                same_site: SameSite::Lax,
+                expiry: Duration::from_secs(24 * 60 * 60),
+            }),
+        }
+    }
+
+    fn config(mut self, f: impl FnOnce(&mut SessionConfig)) -> Self {
+        f(Arc::make_mut(&mut self.config));
+        self
+    }
+
+    /// Sets how the session cookie is protected.
+    pub fn security(self, security: CookieSecurity) -> Self {
+        self.config(|config| config.security = security)
+    }
+
+    /// Sets the name of the session cookie.
+    pub fn cookie_name(self, name: impl Into<String>) -> Self {
+        self.config(|config| config.cookie_name = name.into())
+    }
+
+    /// Sets the path of the session cookie.
+    pub fn path(self, path: impl Into<String>) -> Self {
+        self.config(|config| config.path = path.into())
+    }
+
+    /// Sets the domain of the session cookie.
+    pub fn domain(self, domain: impl Into<String>) -> Self {
+        self.config(|config| config.domain = Some(domain.into()))
+    }
+
+    /// Sets whether the cookie should only be sent over https.
+    pub fn secure(self, secure: bool) -> Self {
+        self.config(|config| config.secure = secure)
+    }
+
+    /// Sets the SameSite attribute of the session cookie.
+    pub fn same_site(self, same_site: SameSite) -> Self {
+        self.config(|config| config.same_site = same_site)
+    }
+
+    /// Sets how long a session lives after it was last modified.
+    pub fn expiry(self, expiry: Duration) -> Self {
+        self.config(|config| config.expiry = expiry)
+    }
+}
+
+impl<S> Layer<S> for SessionLayer {
+    type Service = SessionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        SessionService {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// Service created by the `SessionLayer`.
+#[derive(Clone)]
+pub struct SessionService<S> {
+    inner: S,
+    config: Arc<SessionConfig>,
+}
+
+impl<S> Service<Request> for SessionService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let config = self.config.clone();
+        Box::pin(async move {
+            let session = match config.load(req.headers()).await {
+                Ok(session) => session,
+                Err(error) => return Ok(session_error(error)),
+            };
+            req.extensions_mut().insert(session.clone());
+            let mut response = inner.call(req).await?;
+            match config.persist(&session).await {
+                Ok(Some(cookie)) => {
+                    if let Ok(value) = HeaderValue::from_str(&cookie.to_string()) {
+                        response.headers_mut().append(SET_COOKIE, value);
+                    }
+                    Ok(response)
+                }
+                Ok(None) => Ok(response),
+                Err(error) => Ok(session_error(error)),
+            }
+        })
+    }
+}
+
+impl SessionConfig {
+    fn session_id(&self, headers: &HeaderMap) -> Option<String> {
+        let jar = request_jar(headers);
+        let cookie = match self.security {
+            CookieSecurity::Signed => jar.signed(&self.key).get(&self.cookie_name),
+            CookieSecurity::Private => jar.private(&self.key).get(&self.cookie_name),
+        }?;
+        Some(cookie.value().to_string())
+    }
+
+    async fn load(&self, headers: &HeaderMap) -> Result<Session, SessionError> {
+        let Some(id) = self.session_id(headers) else {
+            return Ok(Session::new(None, HashMap::new()));
+        };
+        Ok(match self.store.load(&id).await? {
+            Some(record) => Session::new(Some(record.id), record.data),
+            None => Session::new(None, HashMap::new()),
+        })
+    }
+
+    /// Saves or deletes the session, returning the cookie to set, if any.
+    async fn persist(&self, session: &Session) -> Result<Option<Cookie<'static>>, SessionError> {
+        let (id, data, expiry, rotate) = {
+            let state = session.state();
+            if state.destroyed {
+                (state.id.clone(), None, Duration::ZERO, false)
+            } else if !state.modified || (state.id.is_none() && state.data.is_empty()) {
+                return Ok(None);
+            } else {
+                let expiry = state.expiry.unwrap_or(self.expiry);
+                (
+                    state.id.clone(),
+                    Some(state.data.clone()),
+                    expiry,
+                    state.rotate,
+                )
+            }
+        };
+        let Some(data) = data else {
+            return self.destroy(id).await;
+        };
+        let new_id = match id {
+            Some(id) if !rotate => id,
+            Some(id) => {
+                self.store.delete(&id).await?;
+                random_token()
+            }
+            None => random_token(),
+        };
+        self.store
+            .save(&SessionRecord::new(
+                new_id.clone(),
+                data,
+                SystemTime::now() + expiry,
+            ))
+            .await?;
+        session.state().id = Some(new_id.clone());
+        Ok(Some(self.cookie(new_id, expiry)))
+    }
+
+    async fn destroy(&self, id: Option<String>) -> Result<Option<Cookie<'static>>, SessionError> {
+        let Some(id) = id else {
+            return Ok(None);
+        };
+        self.store.delete(&id).await?;
+        let mut cookie = self.cookie(String::new(), Duration::ZERO);
+        cookie.make_removal();
+        Ok(Some(cookie))
+    }
+
+    fn cookie(&self, id: String, expiry: Duration) -> Cookie<'static> {
+        let mut cookie = Cookie::build((self.cookie_name.clone(), id))
+            .path(self.path.clone())
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(self.same_site)
+            .max_age(cookie::time::Duration::seconds(expiry.as_secs() as i64))
+            .build();
+        if let Some(domain) = &self.domain {
+            cookie.set_domain(domain.clone());
+        }
+        let mut jar = CookieJar::new();
+        match self.security {
+            CookieSecurity::Signed => jar.signed_mut(&self.key).add(cookie),
+            CookieSecurity::Private => jar.private_mut(&self.key).add(cookie),
+        }
+        jar.get(&self.cookie_name)
+            .cloned()
+            .expect("Cookie was just added")
+    }
+}
+
+fn session_error(error: SessionError) -> Response {
+    error!("Session error: {error}");
+    StatusCode::INTERNAL_SERVER_ERROR.into_response()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::axum::session::MemoryStore;
+    use axum::{body::Body, http::header::COOKIE, Router};
+    use tower::ServiceExt;
+
+    fn app(layer: SessionLayer) -> Router {
+        crate::routes!(
+            get "/" => |session: Session| async move {
+                session.get::<String>("user").unwrap_or_default()
+            },
+            post "/login" => |session: Session| async move {
+                session.rotate();
+                session.insert("user", "admin").unwrap();
+            },
+            post "/logout" => |session: Session| async move { session.destroy() }
+        )
+        .layer(layer)
+    }
+
+    fn request(method: &str, uri: &str, cookie: Option<&str>) -> Request {
+        let builder = Request::builder().method(method).uri(uri);
+        match cookie {
+            Some(cookie) => builder.header(COOKIE, cookie),
+            None => builder,
+        }
+        .body(Body::empty())
+        .unwrap()
+    }
+
+    fn set_cookie(response: &Response) -> String {
+        let value = response
+            .headers()
+            .get(SET_COOKIE)
+            .unwrap()
+            .to_str()
+            .unwrap();
+        Cookie::parse(value.to_string())
+            .unwrap()
+            .stripped()
+            .to_string()
+    }
+
+    async fn body(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_configure_after_clone() {
+        let layer = SessionLayer::new(MemoryStore::new(), Key::generate());
+        let default = layer.clone();
+        let renamed = layer.cookie_name("renamed");
+        let response = app(renamed)
+            .oneshot(request("POST", "/login", None))
+            .await
+            .unwrap();
+        assert!(set_cookie(&response).starts_with("renamed="));
+        let response = app(default)
+            .oneshot(request("POST", "/login", None))
+            .await
+            .unwrap();
+        assert!(!set_cookie(&response).starts_with("renamed="));
+    }
+
+    #[tokio::test]
+    async fn test_no_cookie_without_data() {
+        let app = app(SessionLayer::new(MemoryStore::new(), Key::generate()));
+        let response = app.oneshot(request("GET", "/", None)).await.unwrap();
+        assert!(response.headers().get(SET_COOKIE).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_login_and_logout() {
+        for security in [CookieSecurity::Signed, CookieSecurity::Private] {
+            let store = MemoryStore::new();
+            let app = app(SessionLayer::new(store.clone(), Key::generate()).security(security));
+
+            let response = app
+                .clone()
+                .oneshot(request("POST", "/login", None))
+                .await
+                .unwrap();
+            let cookie = set_cookie(&response);
+
+            let response = app
+                .clone()
+                .oneshot(request("GET", "/", Some(&cookie)))
+                .await
+                .unwrap();
+            assert_eq!(body(response).await, "admin");
+
+            let response = app
+                .clone()
+                .oneshot(request("POST", "/logout", Some(&cookie)))
+                .await
+                .unwrap();
+            assert!(set_cookie(&response).starts_with("session="));
+
+            let response = app
+                .oneshot(request("GET", "/", Some(&cookie)))
+                .await
+                .unwrap();
+            assert_eq!(body(response).await, "");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_rotate_invalidates_old_id() {
+        let store = MemoryStore::new();
+        let app = app(SessionLayer::new(store.clone(), Key::generate()));
+
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/login", None))
+            .await
+            .unwrap();
+        let first = set_cookie(&response);
+        let response = app
+            .clone()
+            .oneshot(request("POST", "/login", Some(&first)))
+            .await
+            .unwrap();
+        let second = set_cookie(&response);
+        assert_ne!(first, second);
+
+        let response = app
+            .oneshot(request("GET", "/", Some(&first)))
+            .await
+            .unwrap();
+        assert_eq!(body(response).await, "");
+    }
+
+    #[tokio::test]
+    async fn test_tampered_cookie() {
+        let store = MemoryStore::new();
+        let router = app(SessionLayer::new(store.clone(), Key::generate()));
+        let response = router
+            .oneshot(request("POST", "/login", None))
+            .await
+            .unwrap();
+        let cookie = set_cookie(&response);
+
+        let other = app(SessionLayer::new(store, Key::generate()));
+        let response = other
+            .oneshot(request("GET", "/", Some(&cookie)))
+            .await
+            .unwrap();
+        assert_eq!(body(response).await, "");
+    }
+}
@@ -0,0 +1,12 @@
+pub mod extractor;
+pub mod layer;
+#[cfg(feature = "diesel")]
+pub mod postgres;
+pub mod store;
+
+pub use cookie::{Key, SameSite};
+pub use extractor::{FlashMessage, Session, SessionRejection};
+pub use layer::{CookieSecurity, SessionLayer};
+#[cfg(feature = "diesel")]
+pub use postgres::PgSessionStore;
+pub use store::{MemoryStore, SessionError, SessionRecord, SessionStore};
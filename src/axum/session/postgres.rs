@@ -0,0 +1,144 @@
+use {
+    super::store::{SessionError, SessionRecord, SessionStore},
+    crate::diesel::{pool::PgPool, unix_timestamp},
+    axum::async_trait,
+    diesel::{
+        sql_types::{BigInt, Text},
+        QueryableByName,
+    },
+    diesel_async::RunQueryDsl,
+    std::time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// A session store persisting sessions in a PostgreSQL table.
+/// The table can be created with `PgSessionStore::migrate`, or with a migration:
+/// ```sql
+/// CREATE TABLE sessions (
+///     id         VARCHAR(64) PRIMARY KEY,
+///     data       TEXT        NOT NULL,
+///     expires_at BIGINT      NOT NULL
+/// );
+/// ```
+#[derive(Clone)]
+pub struct PgSessionStore {
+    pool: PgPool,
+    table: String,
+}
+
+#[derive(QueryableByName)]
+struct SessionRow {
+    #[diesel(sql_type = Text)]
+    id: String,
+    #[diesel(sql_type = Text)]
+    data: String,
+    #[diesel(sql_type = BigInt)]
+    expires_at: i64,
+}
+
+impl PgSessionStore {
+    /// Creates a store using the `sessions` table.
+    pub fn new(pool: PgPool) -> Self {
+        Self::with_table(pool, "sessions")
+    }
+
+    /// Creates a store using the given table.
+    /// The table name is not escaped, and must not come from user input.
+    pub fn with_table(pool: PgPool, table: impl Into<String>) -> Self {
+        Self {
+            pool,
+            table: table.into(),
+        }
+    }
+
+    /// Creates the session table, if it does not exist.
+    pub async fn migrate(&self) -> Result<(), SessionError> {
+        let query = format!(
+            "CREATE TABLE IF NOT EXISTS {} (id VARCHAR(64) PRIMARY KEY, data TEXT NOT NULL, expires_at BIGINT NOT NULL)",
+            self.table
+        );
+        let mut conn = self.connection().await?;
+        diesel::sql_query(query)
+            .execute(&mut conn)
+            .await
+            .map_err(store_error)?;
+        Ok(())
+    }
+
+    /// Deletes all expired sessions, returning the number of deleted sessions.
+    pub async fn delete_expired(&self) -> Result<usize, SessionError> {
+        let query = format!("DELETE FROM {} WHERE expires_at <= $1", self.table);
+        let mut conn = self.connection().await?;
+        diesel::sql_query(query)
+            .bind::<BigInt, _>(unix_timestamp(SystemTime::now()))
+            .execute(&mut conn)
+            .await
+            .map_err(store_error)
+    }
+
+    async fn connection(
+        &self,
+    ) -> Result<
+        diesel_async::pooled_connection::deadpool::Object<diesel_async::AsyncPgConnection>,
+        SessionError,
+    > {
+        self.pool.get().await.map_err(store_error)
+    }
+}
+
+#[async_trait]
+impl SessionStore for PgSessionStore {
+    async fn load(&self, id: &str) -> Result<Option<SessionRecord>, SessionError> {
+        let query = format!(
+            "SELECT id, data, expires_at FROM {} WHERE id = $1 AND expires_at > $2",
+            self.table
+        );
+        let mut conn = self.connection().await?;
+        let row = diesel::sql_query(query)
+            .bind::<Text, _>(id)
+            .bind::<BigInt, _>(unix_timestamp(SystemTime::now()))
+            .get_results::<SessionRow>(&mut conn)
+            .await
+            .map_err(store_error)?
+            .pop();
+        row.map(|row| {
+            Ok(SessionRecord::new(
+                row.id,
+                serde_json::from_str(&row.data)?,
+                UNIX_EPOCH + Duration::from_secs(row.expires_at.max(0) as u64),
+            ))
+        })
+        .transpose()
+    }
+
+    async fn save(&self, record: &SessionRecord) -> Result<(), SessionError> {
+        let query = format!(
+            "INSERT INTO {} (id, data, expires_at) VALUES ($1, $2, $3) \
+             ON CONFLICT (id) DO UPDATE SET data = EXCLUDED.data, expires_at = EXCLUDED.expires_at",
+            self.table
+        );
+        let mut conn = self.connection().await?;
+        diesel::sql_query(query)
+            .bind::<Text, _>(&record.id)
+            .bind::<Text, _>(serde_json::to_string(&record.data)?)
+            .bind::<BigInt, _>(unix_timestamp(record.expires_at))
+            .execute(&mut conn)
+            .await
+            .map_err(store_error)?;
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), SessionError> {
+        let query = format!("DELETE FROM {} WHERE id = $1", self.table);
+        let mut conn = self.connection().await?;
+        diesel::sql_query(query)
+            .bind::<Text, _>(id)
+            .execute(&mut conn)
+            .await
+            .map_err(store_error)?;
+        Ok(())
+    }
+}
+
+fn store_error(error: impl ToString) -> SessionError {
+    SessionError::Store(error.to_string())
+}
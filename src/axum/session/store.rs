@@ -0,0 +1,143 @@
+use {
+    axum::async_trait,
+    serde_json::Value,
+    std::{
+        collections::HashMap,
+        sync::{Arc, RwLock},
+        time::SystemTime,
+    },
+    thiserror::Error,
+};
+
+/// The data of a session, as persisted by a `SessionStore`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SessionRecord {
+    pub id: String,
+    pub data: HashMap<String, Value>,
+    pub expires_at: SystemTime,
+}
+
+impl SessionRecord {
+    /// Creates a new record with the given id, data and expiry time.
+    pub fn new(
+        id: impl Into<String>,
+        data: HashMap<String, Value>,
+        expires_at: SystemTime,
+    ) -> Self {
+        Self {
+            id: id.into(),
+            data,
+            expires_at,
+        }
+    }
+
+    /// Returns true if the session has expired.
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= SystemTime::now()
+    }
+}
+
+/// Error type for session stores.
+#[derive(Debug, Error)]
+pub enum SessionError {
+    #[error(transparent)]
+    Serde(#[from] serde_json::Error),
+    #[error("Session store error: {0}")]
+    Store(String),
+}
+
+/// Server-side storage for sessions.
+/// Implementations should not return expired sessions.
+///
+/// Implementing the trait requires the `async_trait` macro.
+#[async_trait]
+pub trait SessionStore: Send + Sync + 'static {
+    /// Loads the session with the given id, or `None` if it does not exist or has expired.
+    async fn load(&self, id: &str) -> Result<Option<SessionRecord>, SessionError>;
+    /// Creates or replaces the session.
+    async fn save(&self, record: &SessionRecord) -> Result<(), SessionError>;
+    /// Deletes the session with the given id, if it exists.
+    async fn delete(&self, id: &str) -> Result<(), SessionError>;
+}
+
+/// A session store keeping all sessions in memory.
+/// Sessions are lost when the application restarts, and are not shared between instances.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryStore {
+    sessions: Arc<RwLock<HashMap<String, SessionRecord>>>,
+}
+
+impl MemoryStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Removes all expired sessions from the store.
+    pub fn delete_expired(&self) {
+        self.sessions
+            .write()
+            .expect("Session store lock poisoned")
+            .retain(|_, record| !record.is_expired());
+    }
+}
+
+#[async_trait]
+impl SessionStore for MemoryStore {
+    async fn load(&self, id: &str) -> Result<Option<SessionRecord>, SessionError> {
+        Ok(self
+            .sessions
+            .read()
+            .expect("Session store lock poisoned")
+            .get(id)
+            .filter(|record| !record.is_expired())
+            .cloned())
+    }
+
+    async fn save(&self, record: &SessionRecord) -> Result<(), SessionError> {
+        self.sessions
+            .write()
+            .expect("Session store lock poisoned")
+            .insert(record.id.clone(), record.clone());
+        Ok(())
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), SessionError> {
+        self.sessions
+            .write()
+            .expect("Session store lock poisoned")
+            .remove(id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    fn record(id: &str, expires_at: SystemTime) -> SessionRecord {
+        SessionRecord::new(id, HashMap::new(), expires_at)
+    }
+
+    #[tokio::test]
+    async fn test_memory_store() {
+        let store = MemoryStore::new();
+        let record = record("id", SystemTime::now() + Duration::from_secs(60));
+        store.save(&record).await.unwrap();
+        assert_eq!(store.load("id").await.unwrap(), Some(record));
+        store.delete("id").await.unwrap();
+        assert_eq!(store.load("id").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_memory_store_expired() {
+        let store = MemoryStore::new();
+        store
+            .save(&record("id", SystemTime::now() - Duration::from_secs(1)))
+            .await
+            .unwrap();
+        assert_eq!(store.load("id").await.unwrap(), None);
+        store.delete_expired();
+        assert!(store.sessions.read().unwrap().is_empty());
+    }
+}
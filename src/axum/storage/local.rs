@@ -0,0 +1,304 @@
+use {
+    crate::axum::storage::{
+        store::{copy_hashed, validate_key},
+        FileObject, FileStore, StorageError, StoredFile, UrlSigner,
+    },
+    axum::async_trait,
+    futures_util::StreamExt,
+    mime::Mime,
+    std::{
+        io,
+        path::{Path, PathBuf},
+        sync::Arc,
+        time::Duration,
+    },
+    tempfile::NamedTempFile,
+    tokio::{fs, io::AsyncRead, sync::Mutex},
+    tokio_util::io::ReaderStream,
+};
+
+/// Extension of the files holding the content type of a stored file.
+const CONTENT_TYPE_EXTENSION: &str = "type";
+/// Extension of the files counting the uploads of a stored file.
+const REFERENCES_EXTENSION: &str = "refs";
+
+/// A file store in a directory on the local filesystem.
+/// Files are stored in subdirectories named after the first two characters of their key,
+/// with their content type in a file next to them, like `<root>/df/dffd...986f.type`,
+/// and the number of times they were stored in a `.refs` file.
+///
+/// The references are counted under a lock shared by the clones of the store,
+/// so several stores, or applications, using the same directory may lose counts.
+/// # Example
+/// ```
+/// use lib::axum::{
+///     extractor::MultipartFile,
+///     storage::{FileStore, LocalFileStore, StorageError},
+/// };
+///
+/// async fn upload(MultipartFile(file): MultipartFile) -> Result<String, StorageError> {
+///     let store = LocalFileStore::new("uploads");
+///     Ok(store.put_file(&file).await?.key)
+/// }
+/// ```
+#[derive(Debug, Clone)]
+pub struct LocalFileStore {
+    root: PathBuf,
+    signer: Option<UrlSigner>,
+    references: Arc<Mutex<()>>,
+}
+
+impl LocalFileStore {
+    /// Creates a store in the directory, which is created when the first file is stored.
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self {
+            root: root.into(),
+            signer: None,
+            references: Arc::default(),
+        }
+    }
+
+    /// Sets the signer used by `presign`.
+    pub fn signer(mut self, signer: UrlSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+
+    /// Returns the path of the file with the key, or an error if the key is invalid.
+    pub fn path(&self, key: &str) -> Result<PathBuf, StorageError> {
+        validate_key(key)?;
+        Ok(self.root.join(&key[..2]).join(key))
+    }
+
+    /// Returns the number of times the file at the path was stored, 0 if it does not exist.
+    /// Files stored before the references were counted have one.
+    async fn references(path: &Path) -> io::Result<u64> {
+        match fs::read_to_string(path.with_extension(REFERENCES_EXTENSION)).await {
+            Ok(references) => Ok(references.trim().parse().unwrap_or(1)),
+            Err(error) if error.kind() == io::ErrorKind::NotFound => {
+                Ok(u64::from(fs::try_exists(path).await?))
+            }
+            Err(error) => Err(error),
+        }
+    }
+
+    async fn stored_file(&self, key: &str, path: &Path) -> io::Result<StoredFile> {
+        let size = fs::metadata(path).await?.len();
+        let content_type = fs::read_to_string(path.with_extension(CONTENT_TYPE_EXTENSION))
+            .await
+            .ok()
+            .and_then(|content_type| content_type.parse().ok())
+            .unwrap_or(mime::APPLICATION_OCTET_STREAM);
+        Ok(StoredFile {
+            key: key.to_string(),
+            size,
+            content_type,
+        })
+    }
+}
+
+#[async_trait]
+impl FileStore for LocalFileStore {
+    async fn put(
+        &self,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        content_type: &Mime,
+    ) -> Result<StoredFile, StorageError> {
+        fs::create_dir_all(&self.root).await?;
+        let temp = NamedTempFile::new_in(&self.root)?;
+        let mut writer = fs::File::from_std(temp.reopen()?);
+        let (_, key) = copy_hashed(reader, &mut writer).await?;
+        let path = self.path(&key)?;
+        let _lock = self.references.lock().await;
+        let references = Self::references(&path).await?;
+        if references == 0 {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent).await?;
+            }
+            fs::write(
+                path.with_extension(CONTENT_TYPE_EXTENSION),
+                content_type.to_string(),
+            )
+            .await?;
+            temp.persist(&path).map_err(|error| error.error)?;
+        }
+        fs::write(
+            path.with_extension(REFERENCES_EXTENSION),
+            (references + 1).to_string(),
+        )
+        .await?;
+        Ok(self.stored_file(&key, &path).await?)
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<FileObject>, StorageError> {
+        let path = self.path(key)?;
+        let file = match fs::File::open(&path).await {
+            Ok(file) => file,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(error) => return Err(error.into()),
+        };
+        Ok(Some(FileObject {
+            file: self.stored_file(key, &path).await?,
+            body: ReaderStream::new(file).boxed(),
+        }))
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, StorageError> {
+        let path = self.path(key)?;
+        let _lock = self.references.lock().await;
+        match Self::references(&path).await? {
+            0 => return Ok(false),
+            1 => {}
+            references => {
+                fs::write(
+                    path.with_extension(REFERENCES_EXTENSION),
+                    (references - 1).to_string(),
+                )
+                .await?;
+                return Ok(true);
+            }
+        }
+        match fs::remove_file(&path).await {
+            Ok(()) => {}
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(false),
+            Err(error) => return Err(error.into()),
+        }
+        for extension in [CONTENT_TYPE_EXTENSION, REFERENCES_EXTENSION] {
+            match fs::remove_file(path.with_extension(extension)).await {
+                Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+                _ => {}
+            }
+        }
+        Ok(true)
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<StoredFile>, StorageError> {
+        let mut files = Vec::new();
+        // Keys are hex, so a prefix which cannot be split at two bytes matches no shard
+        let shard = prefix.get(..2).unwrap_or(prefix);
+        let mut directories = match fs::read_dir(&self.root).await {
+            Ok(directories) => directories,
+            Err(error) if error.kind() == io::ErrorKind::NotFound => return Ok(files),
+            Err(error) => return Err(error.into()),
+        };
+        while let Some(directory) = directories.next_entry().await? {
+            let name = directory.file_name();
+            let name = name.to_string_lossy();
+            if name.len() != 2 || !name.starts_with(shard) || !directory.file_type().await?.is_dir()
+            {
+                continue;
+            }
+            let mut entries = fs::read_dir(directory.path()).await?;
+            while let Some(entry) = entries.next_entry().await? {
+                let key = entry.file_name().to_string_lossy().to_string();
+                if key.starts_with(prefix) && validate_key(&key).is_ok() {
+                    files.push(self.stored_file(&key, &entry.path()).await?);
+                }
+            }
+        }
+        files.sort_by(|a, b| a.key.cmp(&b.key));
+        Ok(files)
+    }
+
+    async fn presign(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        let path = self.path(key)?;
+        let signer = self
+            .signer
+            .as_ref()
+            .ok_or(StorageError::Unsupported("Signed URLs without a signer"))?;
+        if !fs::try_exists(&path).await? {
+            return Err(StorageError::NotFound(key.to_string()));
+        }
+        Ok(signer.sign(key, expires_in))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const KEY: &str = "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f";
+
+    #[tokio::test]
+    async fn test_put_get_delete() {
+        let root = TempDir::new().unwrap();
+        let store = LocalFileStore::new(root.path());
+        let stored = store
+            .put(&mut b"Hello, World!".as_slice(), &mime::TEXT_PLAIN)
+            .await
+            .unwrap();
+        assert_eq!(stored.key, KEY);
+        assert!(root.path().join("df").join(KEY).exists());
+        store
+            .put(&mut b"Other".as_slice(), &mime::IMAGE_PNG)
+            .await
+            .unwrap();
+
+        let object = store.get(KEY).await.unwrap().unwrap();
+        assert_eq!(object.file, stored);
+        let bytes = object
+            .body
+            .map(|chunk| chunk.unwrap().to_vec())
+            .concat()
+            .await;
+        assert_eq!(bytes, b"Hello, World!");
+
+        let listed = store.list("").await.unwrap();
+        assert_eq!(listed.len(), 2);
+        assert!(listed[0].key < listed[1].key);
+        assert_eq!(store.list("dffd").await.unwrap(), vec![stored]);
+        assert!(store.list("æ").await.unwrap().is_empty());
+        assert!(store.list("dæ").await.unwrap().is_empty());
+
+        assert!(store.delete(KEY).await.unwrap());
+        assert!(!store.delete(KEY).await.unwrap());
+        assert!(store.get(KEY).await.unwrap().is_none());
+        assert_eq!(store.list("").await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_references() {
+        let root = TempDir::new().unwrap();
+        let store = LocalFileStore::new(root.path());
+        let put = |content_type| {
+            let store = store.clone();
+            async move {
+                store
+                    .put(&mut b"Hello, World!".as_slice(), &content_type)
+                    .await
+                    .unwrap()
+            }
+        };
+        put(mime::TEXT_PLAIN).await;
+        // The content type of the first upload is kept
+        assert_eq!(put(mime::IMAGE_PNG).await.content_type, mime::TEXT_PLAIN);
+
+        assert!(store.delete(KEY).await.unwrap());
+        assert!(store.get(KEY).await.unwrap().is_some());
+        assert!(store.delete(KEY).await.unwrap());
+        assert!(store.get(KEY).await.unwrap().is_none());
+        assert!(!store.delete(KEY).await.unwrap());
+        assert_eq!(
+            std::fs::read_dir(root.path().join("df")).unwrap().count(),
+            0
+        );
+    }
+
+    #[tokio::test]
+    async fn test_invalid_key() {
+        let store = LocalFileStore::new("uploads");
+        assert!(matches!(
+            store.get("../secret").await,
+            Err(StorageError::InvalidKey(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_missing_root() {
+        let root = TempDir::new().unwrap();
+        let store = LocalFileStore::new(root.path().join("missing"));
+        assert!(store.list("").await.unwrap().is_empty());
+        assert!(store.get(KEY).await.unwrap().is_none());
+    }
+}
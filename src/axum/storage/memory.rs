@@ -0,0 +1,204 @@
+use {
+    crate::axum::storage::{
+        store::{copy_hashed, validate_key},
+        FileObject, FileStore, StorageError, StoredFile, UrlSigner,
+    },
+    axum::{async_trait, body::Bytes},
+    futures_util::{future, stream, StreamExt},
+    mime::Mime,
+    std::{
+        collections::BTreeMap,
+        sync::{Arc, RwLock},
+        time::Duration,
+    },
+    tokio::io::AsyncRead,
+};
+
+/// A file store keeping all files in memory.
+/// Files are lost when the application restarts, and are not shared between instances.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFileStore {
+    files: Arc<RwLock<BTreeMap<String, MemoryFile>>>,
+    signer: Option<UrlSigner>,
+}
+
+impl MemoryFileStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Sets the signer used by `presign`.
+    pub fn signer(mut self, signer: UrlSigner) -> Self {
+        self.signer = Some(signer);
+        self
+    }
+}
+
+#[derive(Debug)]
+struct MemoryFile {
+    content_type: Mime,
+    bytes: Bytes,
+    /// The number of times the file was stored.
+    references: u64,
+}
+
+impl MemoryFile {
+    fn stored_file(&self, key: &str) -> StoredFile {
+        StoredFile {
+            key: key.to_string(),
+            size: self.bytes.len() as u64,
+            content_type: self.content_type.clone(),
+        }
+    }
+}
+
+#[async_trait]
+impl FileStore for MemoryFileStore {
+    async fn put(
+        &self,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        content_type: &Mime,
+    ) -> Result<StoredFile, StorageError> {
+        let mut bytes = Vec::new();
+        let (_, key) = copy_hashed(reader, &mut bytes).await?;
+        let mut files = self.files.write().expect("File store lock poisoned");
+        let file = files.entry(key.clone()).or_insert_with(|| MemoryFile {
+            content_type: content_type.clone(),
+            bytes: bytes.into(),
+            references: 0,
+        });
+        file.references += 1;
+        Ok(file.stored_file(&key))
+    }
+
+    async fn get(&self, key: &str) -> Result<Option<FileObject>, StorageError> {
+        validate_key(key)?;
+        let files = self.files.read().expect("File store lock poisoned");
+        Ok(files.get(key).map(|file| FileObject {
+            file: file.stored_file(key),
+            body: stream::once(future::ready(Ok(file.bytes.clone()))).boxed(),
+        }))
+    }
+
+    async fn delete(&self, key: &str) -> Result<bool, StorageError> {
+        validate_key(key)?;
+        let mut files = self.files.write().expect("File store lock poisoned");
+        let Some(file) = files.get_mut(key) else {
+            return Ok(false);
+        };
+        file.references -= 1;
+        if file.references == 0 {
+            files.remove(key);
+        }
+        Ok(true)
+    }
+
+    async fn list(&self, prefix: &str) -> Result<Vec<StoredFile>, StorageError> {
+        Ok(self
+            .files
+            .read()
+            .expect("File store lock poisoned")
+            .range(prefix.to_string()..)
+            .take_while(|(key, _)| key.starts_with(prefix))
+            .map(|(key, file)| file.stored_file(key))
+            .collect())
+    }
+
+    async fn presign(&self, key: &str, expires_in: Duration) -> Result<String, StorageError> {
+        validate_key(key)?;
+        let signer = self
+            .signer
+            .as_ref()
+            .ok_or(StorageError::Unsupported("Signed URLs without a signer"))?;
+        if !self
+            .files
+            .read()
+            .expect("File store lock poisoned")
+            .contains_key(key)
+        {
+            return Err(StorageError::NotFound(key.to_string()));
+        }
+        Ok(signer.sign(key, expires_in))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::axum::{extractor::File, storage::signed_routes};
+    use axum::{body::Body, http::Request, http::StatusCode};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_put_get_delete() {
+        let store = MemoryFileStore::new();
+        let file = File::new("hello.txt", "Hello, World!", mime::TEXT_PLAIN);
+        let stored = store.put_file(&file).await.unwrap();
+        assert_eq!(
+            stored.key,
+            "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f"
+        );
+        assert_eq!(stored.size, 13);
+        // The same content is stored once
+        assert_eq!(store.put_file(&file).await.unwrap(), stored);
+        assert_eq!(store.list("").await.unwrap(), vec![stored.clone()]);
+        assert_eq!(store.list("dffd").await.unwrap().len(), 1);
+        assert!(store.list("0").await.unwrap().is_empty());
+
+        let mut object = store.get(&stored.key).await.unwrap().unwrap();
+        assert_eq!(object.file, stored);
+        let bytes = object.body.next().await.unwrap().unwrap();
+        assert_eq!(bytes, "Hello, World!");
+
+        // The file was stored twice, so it is only removed by the second delete
+        assert!(store.delete(&stored.key).await.unwrap());
+        assert!(store.get(&stored.key).await.unwrap().is_some());
+        assert!(store.delete(&stored.key).await.unwrap());
+        assert!(!store.delete(&stored.key).await.unwrap());
+        assert!(store.get(&stored.key).await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_signed_url() {
+        let signer = UrlSigner::new("secret");
+        let store = MemoryFileStore::new().signer(signer.clone());
+        let stored = store
+            .put(&mut b"Hello".as_slice(), &mime::TEXT_PLAIN)
+            .await
+            .unwrap();
+        let url = store
+            .presign(&stored.key, Duration::from_secs(60))
+            .await
+            .unwrap();
+        let router: axum::Router = signed_routes(store, signer);
+
+        let response = router
+            .clone()
+            .oneshot(Request::get(&url).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()["content-type"], "text/plain");
+        assert_eq!(response.headers()["content-length"], "5");
+
+        let tampered = url.replace("expires=", "expires=1");
+        let response = router
+            .oneshot(Request::get(&tampered).body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+    }
+
+    #[tokio::test]
+    async fn test_presign_without_signer() {
+        let store = MemoryFileStore::new();
+        let stored = store
+            .put(&mut b"Hello".as_slice(), &mime::TEXT_PLAIN)
+            .await
+            .unwrap();
+        assert!(matches!(
+            store.presign(&stored.key, Duration::from_secs(60)).await,
+            Err(StorageError::Unsupported(_))
+        ));
+    }
+}
@@ -0,0 +1,9 @@
+pub mod local;
+pub mod memory;
+pub mod signed;
+pub mod store;
+
+pub use local::LocalFileStore;
+pub use memory::MemoryFileStore;
+pub use signed::{signed_routes, UrlSigner};
+pub use store::{ByteStream, FileObject, FileStore, StorageError, StoredFile};
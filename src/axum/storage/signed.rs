@@ -0,0 +1,184 @@
+use {
+    crate::axum::storage::{FileObject, FileStore, StorageError},
+    axum::{
+        extract::{Path, Query, State},
+        routing::get,
+        Router,
+    },
+    base64::{engine::general_purpose::URL_SAFE_NO_PAD, Engine},
+    hmac::{Hmac, Mac},
+    serde::Deserialize,
+    sha2::Sha256,
+    std::{
+        fmt::{self, Debug, Formatter},
+        sync::Arc,
+        time::{Duration, SystemTime, UNIX_EPOCH},
+    },
+};
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Signs and verifies URLs for downloading files from a `FileStore`.
+/// A URL contains the key of the file, a unix timestamp after which it expires,
+/// and an HMAC-SHA256 signature of both.
+/// # Default Options
+/// - Base path == `/files`
+/// # Example
+/// ```
+/// use std::time::Duration;
+/// use lib::axum::storage::UrlSigner;
+///
+/// let signer = UrlSigner::new("secret").base_path("/downloads");
+/// let url = signer.sign(
+///     "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f",
+///     Duration::from_secs(3600),
+/// );
+/// assert!(url.starts_with("/downloads/dffd6021"));
+/// ```
+#[derive(Clone)]
+pub struct UrlSigner {
+    secret: Arc<[u8]>,
+    base_path: String,
+}
+
+impl Debug for UrlSigner {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        f.debug_struct("UrlSigner")
+            .field("base_path", &self.base_path)
+            .finish_non_exhaustive()
+    }
+}
+
+impl UrlSigner {
+    /// Creates a signer with the secret key used for the signatures.
+    pub fn new(secret: impl AsRef<[u8]>) -> Self {
+        Self {
+            secret: secret.as_ref().into(),
+            base_path: "/files".to_string(),
+        }
+    }
+
+    /// Sets the path the files are served from.
+    pub fn base_path(mut self, path: impl Into<String>) -> Self {
+        self.base_path = path.into().trim_end_matches('/').to_string();
+        self
+    }
+
+    /// Creates a signed URL for the file, valid for the given duration.
+    pub fn sign(&self, key: &str, expires_in: Duration) -> String {
+        let expires = (SystemTime::now() + expires_in)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        let signature = URL_SAFE_NO_PAD.encode(self.mac(key, expires).finalize().into_bytes());
+        format!(
+            "{}/{key}?expires={expires}&signature={signature}",
+            self.base_path
+        )
+    }
+
+    /// Verifies the signature for the key and expiry time, and that the URL has not expired.
+    pub fn verify(&self, key: &str, expires: u64, signature: &str) -> Result<(), StorageError> {
+        let signature = URL_SAFE_NO_PAD
+            .decode(signature)
+            .map_err(|_| StorageError::InvalidSignature)?;
+        self.mac(key, expires)
+            .verify_slice(&signature)
+            .map_err(|_| StorageError::InvalidSignature)?;
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default()
+            .as_secs();
+        if now > expires {
+            return Err(StorageError::Expired);
+        }
+        Ok(())
+    }
+
+    fn mac(&self, key: &str, expires: u64) -> HmacSha256 {
+        let mut mac =
+            HmacSha256::new_from_slice(&self.secret).expect("HMAC accepts keys of any length");
+        mac.update(format!("{key}:{expires}").as_bytes());
+        mac
+    }
+}
+
+#[derive(Debug, Deserialize)]
+struct SignedQuery {
+    expires: u64,
+    signature: String,
+}
+
+/// Creates a router serving the files of the store at the URLs signed by the signer.
+/// Requests with a missing, invalid or expired signature are rejected with 403 Forbidden.
+/// # Example
+/// ```
+/// use lib::axum::storage::{signed_routes, MemoryFileStore, UrlSigner};
+///
+/// let signer = UrlSigner::new("secret");
+/// let store = MemoryFileStore::new().signer(signer.clone());
+/// let _router: axum::Router = axum::Router::new().merge(signed_routes(store, signer));
+/// ```
+pub fn signed_routes<Store, S>(store: Store, signer: UrlSigner) -> Router<S>
+where
+    Store: FileStore,
+    S: Clone + Send + Sync + 'static,
+{
+    let path = format!("{}/:key", signer.base_path);
+    let store: Arc<dyn FileStore> = Arc::new(store);
+    Router::new()
+        .route(&path, get(serve))
+        .with_state((store, signer))
+}
+
+async fn serve(
+    State((store, signer)): State<(Arc<dyn FileStore>, UrlSigner)>,
+    Path(key): Path<String>,
+    Query(query): Query<SignedQuery>,
+) -> Result<FileObject, StorageError> {
+    signer.verify(&key, query.expires, &query.signature)?;
+    store.get(&key).await?.ok_or(StorageError::NotFound(key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEY: &str = "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f";
+
+    fn query(url: &str) -> SignedQuery {
+        Query::try_from_uri(&url.parse().unwrap()).unwrap().0
+    }
+
+    #[test]
+    fn test_sign_and_verify() {
+        let signer = UrlSigner::new("secret");
+        let url = signer.sign(KEY, Duration::from_secs(60));
+        assert!(url.starts_with(&format!("/files/{KEY}?expires=")));
+        let query = query(&url);
+        assert!(signer.verify(KEY, query.expires, &query.signature).is_ok());
+        assert!(matches!(
+            signer.verify(KEY, query.expires + 1, &query.signature),
+            Err(StorageError::InvalidSignature)
+        ));
+        assert!(matches!(
+            UrlSigner::new("other").verify(KEY, query.expires, &query.signature),
+            Err(StorageError::InvalidSignature)
+        ));
+    }
+
+    #[test]
+    fn test_expired() {
+        let signer = UrlSigner::new("secret");
+        let expires = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap()
+            .as_secs()
+            - 1;
+        let signature = URL_SAFE_NO_PAD.encode(signer.mac(KEY, expires).finalize().into_bytes());
+        assert!(matches!(
+            signer.verify(KEY, expires, &signature),
+            Err(StorageError::Expired)
+        ));
+    }
+}
@@ -0,0 +1,249 @@
+#[cfg(feature = "multipart")]
+use crate::axum::multipart::SpooledFile;
+use {
+    crate::axum::extractor::File,
+    axum::{
+        async_trait,
+        body::{Body, Bytes},
+        http::{
+            header::{
+                CONTENT_DISPOSITION, CONTENT_LENGTH, CONTENT_TYPE, ETAG, X_CONTENT_TYPE_OPTIONS,
+            },
+            StatusCode,
+        },
+        response::{IntoResponse, Response},
+        Json,
+    },
+    futures_util::stream::BoxStream,
+    mime::Mime,
+    serde_json::json,
+    sha2::{Digest, Sha256},
+    std::{io, time::Duration},
+    thiserror::Error,
+    tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt},
+};
+
+/// A stream of the content of a stored file.
+pub type ByteStream = BoxStream<'static, io::Result<Bytes>>;
+
+/// Metadata of a file in a `FileStore`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StoredFile {
+    /// The hex encoded SHA-256 hash of the content.
+    pub key: String,
+    pub size: u64,
+    pub content_type: Mime,
+}
+
+/// A stored file with a stream of its content.
+/// Responds with the content, its type and length, and the key as the `ETag`.
+///
+/// The content type is uploaded by clients, so browsers are told not to sniff it,
+/// and only images, audio, video, plain text and PDF files are displayed inline.
+/// Other files, like HTML and SVG which could run scripts on the origin, are downloaded
+/// as attachments.
+pub struct FileObject {
+    pub file: StoredFile,
+    pub body: ByteStream,
+}
+
+impl IntoResponse for FileObject {
+    fn into_response(self) -> Response {
+        let disposition = if is_inline(&self.file.content_type) {
+            "inline"
+        } else {
+            "attachment"
+        };
+        (
+            [
+                (CONTENT_TYPE, self.file.content_type.to_string()),
+                (CONTENT_LENGTH, self.file.size.to_string()),
+                (ETAG, format!("\"{}\"", self.file.key)),
+                (X_CONTENT_TYPE_OPTIONS, "nosniff".to_string()),
+                (CONTENT_DISPOSITION, disposition.to_string()),
+            ],
+            Body::from_stream(self.body),
+        )
+            .into_response()
+    }
+}
+
+/// Whether browsers can display the content type without running scripts.
+fn is_inline(content_type: &Mime) -> bool {
+    match content_type.type_() {
+        mime::IMAGE => content_type.subtype() != mime::SVG,
+        mime::AUDIO | mime::VIDEO => true,
+        _ => matches!(content_type.essence_str(), "text/plain" | "application/pdf"),
+    }
+}
+
+/// Error type for file stores.
+#[derive(Debug, Error)]
+pub enum StorageError {
+    #[error("Invalid key `{0}`")]
+    InvalidKey(String),
+    #[error("File `{0}` not found")]
+    NotFound(String),
+    #[error("Invalid signature")]
+    InvalidSignature,
+    #[error("The signed URL has expired")]
+    Expired,
+    #[error("{0} is not supported by this store")]
+    Unsupported(&'static str),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+    #[error("File store error: {0}")]
+    Store(String),
+}
+
+impl StorageError {
+    /// The status code of the error.
+    pub fn status(&self) -> StatusCode {
+        match self {
+            StorageError::InvalidKey(_) => StatusCode::BAD_REQUEST,
+            StorageError::NotFound(_) => StatusCode::NOT_FOUND,
+            StorageError::InvalidSignature | StorageError::Expired => StatusCode::FORBIDDEN,
+            StorageError::Unsupported(_) => StatusCode::NOT_IMPLEMENTED,
+            StorageError::Io(_) | StorageError::Store(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl IntoResponse for StorageError {
+    fn into_response(self) -> Response {
+        (self.status(), Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+/// Storage for uploaded files, addressed by the SHA-256 hash of their content.
+/// Storing the same content twice keeps a single copy, with the content type of the first upload,
+/// and counts the references to it: the file is only removed once it is deleted as many times
+/// as it was stored.
+///
+/// Implementing the trait requires the `async_trait` macro.
+#[async_trait]
+pub trait FileStore: Send + Sync + 'static {
+    /// Stores the content of the reader, and returns the metadata of the stored file.
+    async fn put(
+        &self,
+        reader: &mut (dyn AsyncRead + Send + Unpin),
+        content_type: &Mime,
+    ) -> Result<StoredFile, StorageError>;
+    /// Returns the file with the given key and a stream of its content, or `None` if it does not exist.
+    async fn get(&self, key: &str) -> Result<Option<FileObject>, StorageError>;
+    /// Removes a reference to the file with the given key, deleting it if it was the last one.
+    /// Returns false if it did not exist.
+    async fn delete(&self, key: &str) -> Result<bool, StorageError>;
+    /// Lists the files with keys starting with the prefix, ordered by key.
+    async fn list(&self, prefix: &str) -> Result<Vec<StoredFile>, StorageError>;
+
+    /// Creates a URL for downloading the file without further authentication, valid for the given duration.
+    /// Not supported by default.
+    async fn presign(&self, _key: &str, _expires_in: Duration) -> Result<String, StorageError> {
+        Err(StorageError::Unsupported("Signed URLs"))
+    }
+
+    /// Stores a file extracted from a multipart request.
+    async fn put_file(&self, file: &File) -> Result<StoredFile, StorageError> {
+        self.put(&mut file.bytes.as_slice(), &file.content_type)
+            .await
+    }
+
+    /// Stores a file spooled to disk from a multipart request.
+    #[cfg(feature = "multipart")]
+    async fn put_spooled(&self, file: &SpooledFile) -> Result<StoredFile, StorageError> {
+        self.put(&mut file.open().await?, &file.content_type).await
+    }
+}
+
+/// Returns an error if the key is not a hex encoded SHA-256 hash.
+pub(crate) fn validate_key(key: &str) -> Result<(), StorageError> {
+    if key.len() == 64
+        && key
+            .bytes()
+            .all(|byte| byte.is_ascii_digit() || (b'a'..=b'f').contains(&byte))
+    {
+        Ok(())
+    } else {
+        Err(StorageError::InvalidKey(key.to_string()))
+    }
+}
+
+/// Copies the reader to the writer, and returns the number of bytes and their hex encoded SHA-256 hash.
+pub(crate) async fn copy_hashed<W>(
+    reader: &mut (dyn AsyncRead + Send + Unpin),
+    writer: &mut W,
+) -> io::Result<(u64, String)>
+where
+    W: AsyncWrite + Unpin,
+{
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0; 64 * 1024];
+    let mut size = 0;
+    loop {
+        let read = reader.read(&mut buffer).await?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+        writer.write_all(&buffer[..read]).await?;
+        size += read as u64;
+    }
+    writer.flush().await?;
+    let key = hasher
+        .finalize()
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect();
+    Ok((size, key))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_validate_key() {
+        let key = "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f";
+        assert!(validate_key(key).is_ok());
+        assert!(validate_key(&key.to_uppercase()).is_err());
+        assert!(validate_key("../../etc/passwd").is_err());
+        assert!(validate_key("").is_err());
+    }
+
+    #[test]
+    fn test_file_object_headers() {
+        let response = |content_type: Mime| {
+            FileObject {
+                file: StoredFile {
+                    key: "key".to_string(),
+                    size: 0,
+                    content_type,
+                },
+                body: Box::pin(futures_util::stream::empty()),
+            }
+            .into_response()
+        };
+        let png = response(mime::IMAGE_PNG);
+        assert_eq!(png.headers()[X_CONTENT_TYPE_OPTIONS], "nosniff");
+        assert_eq!(png.headers()[CONTENT_DISPOSITION], "inline");
+        for content_type in [mime::TEXT_HTML, mime::IMAGE_SVG, mime::TEXT_JAVASCRIPT] {
+            let response = response(content_type);
+            assert_eq!(response.headers()[CONTENT_DISPOSITION], "attachment");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_copy_hashed() {
+        let mut written = Vec::new();
+        let (size, key) = copy_hashed(&mut b"Hello, World!".as_slice(), &mut written)
+            .await
+            .unwrap();
+        assert_eq!(size, 13);
+        assert_eq!(
+            key,
+            "dffd6021bb2bd5b0af676290809ec3a53191dd81c7f70a4b28688a362182986f"
+        );
+        assert_eq!(written, b"Hello, World!");
+    }
+}
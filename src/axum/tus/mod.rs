@@ -0,0 +1,5 @@
+pub mod server;
+pub mod store;
+
+pub use server::{TusRejection, TusServer, TUS_RESUMABLE, TUS_VERSION};
+pub use store::{MemoryUploadStore, Upload, UploadStore, UploadStoreError};
@@ -0,0 +1,760 @@
+use {
+    crate::axum::tus::{Upload, UploadStore, UploadStoreError},
+    axum::{
+        body::Body,
+        extract::{OriginalUri, Path, Request, State},
+        http::{
+            header::{CACHE_CONTROL, CONTENT_TYPE, LOCATION},
+            HeaderMap, HeaderName, HeaderValue, Method, StatusCode,
+        },
+        middleware::{from_fn, Next},
+        response::{IntoResponse, Response},
+        routing::{head, options},
+        Json, Router,
+    },
+    base64::{engine::general_purpose::STANDARD, Engine},
+    futures_util::{future::BoxFuture, StreamExt},
+    rand::RngCore,
+    serde_json::json,
+    sha1::Sha1,
+    sha2::{Digest, Sha256},
+    std::{
+        collections::{BTreeMap, HashSet},
+        future::Future,
+        io,
+        path::PathBuf,
+        sync::{Arc, Mutex},
+    },
+    thiserror::Error,
+    tokio::{
+        fs::{self, OpenOptions},
+        io::{AsyncSeekExt, AsyncWriteExt, SeekFrom},
+    },
+    tracing::warn,
+};
+
+/// The version of the tus protocol implemented by the `TusServer`.
+pub const TUS_VERSION: &str = "1.0.0";
+pub const TUS_RESUMABLE: HeaderName = HeaderName::from_static("tus-resumable");
+const TUS_VERSION_HEADER: HeaderName = HeaderName::from_static("tus-version");
+const TUS_EXTENSION: HeaderName = HeaderName::from_static("tus-extension");
+const TUS_MAX_SIZE: HeaderName = HeaderName::from_static("tus-max-size");
+const TUS_CHECKSUM_ALGORITHM: HeaderName = HeaderName::from_static("tus-checksum-algorithm");
+const UPLOAD_OFFSET: HeaderName = HeaderName::from_static("upload-offset");
+const UPLOAD_LENGTH: HeaderName = HeaderName::from_static("upload-length");
+const UPLOAD_DEFER_LENGTH: HeaderName = HeaderName::from_static("upload-defer-length");
+const UPLOAD_METADATA: HeaderName = HeaderName::from_static("upload-metadata");
+const UPLOAD_CHECKSUM: HeaderName = HeaderName::from_static("upload-checksum");
+const OFFSET_OCTET_STREAM: &str = "application/offset+octet-stream";
+
+/// Rejection type for requests to the `TusServer`.
+#[derive(Debug, Error)]
+pub enum TusRejection {
+    #[error("Unsupported tus version, expected {TUS_VERSION}")]
+    UnsupportedVersion,
+    #[error("Invalid header `{0}`")]
+    InvalidHeader(HeaderName),
+    #[error("Deferred upload lengths are not supported")]
+    DeferredLength,
+    #[error("Upload is larger than the limit of {0} bytes")]
+    TooLarge(u64),
+    #[error("Upload not found")]
+    NotFound,
+    #[error("Upload is at offset {expected}, got {actual}")]
+    OffsetMismatch { expected: u64, actual: u64 },
+    #[error("Expected content type `{OFFSET_OCTET_STREAM}`")]
+    UnsupportedMediaType,
+    #[error("Unsupported checksum algorithm `{0}`")]
+    UnsupportedChecksum(String),
+    #[error("Checksum of the request body does not match")]
+    ChecksumMismatch,
+    #[error("Upload is locked by another request")]
+    Locked,
+    #[error(transparent)]
+    Store(#[from] UploadStoreError),
+    #[error(transparent)]
+    Io(#[from] io::Error),
+}
+
+impl IntoResponse for TusRejection {
+    fn into_response(self) -> Response {
+        let status = match self {
+            TusRejection::UnsupportedVersion => StatusCode::PRECONDITION_FAILED,
+            TusRejection::InvalidHeader(_)
+            | TusRejection::DeferredLength
+            | TusRejection::UnsupportedChecksum(_) => StatusCode::BAD_REQUEST,
+            TusRejection::TooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            TusRejection::NotFound => StatusCode::NOT_FOUND,
+            TusRejection::OffsetMismatch { .. } => StatusCode::CONFLICT,
+            TusRejection::UnsupportedMediaType => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            TusRejection::ChecksumMismatch => {
+                StatusCode::from_u16(460).expect("460 is a valid status code")
+            }
+            TusRejection::Locked => StatusCode::LOCKED,
+            TusRejection::Store(_) | TusRejection::Io(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        let mut response = (status, Json(json!({ "error": self.to_string() }))).into_response();
+        if matches!(self, TusRejection::UnsupportedVersion) {
+            response
+                .headers_mut()
+                .insert(TUS_VERSION_HEADER, HeaderValue::from_static(TUS_VERSION));
+        }
+        response
+    }
+}
+
+type CompleteHandler = Arc<dyn Fn(Upload, PathBuf) -> BoxFuture<'static, ()> + Send + Sync>;
+
+#[derive(Clone)]
+struct TusConfig {
+    dir: PathBuf,
+    store: Arc<dyn UploadStore>,
+    max_size: Option<u64>,
+    on_complete: Option<CompleteHandler>,
+    locks: Arc<Mutex<HashSet<String>>>,
+}
+
+impl TusConfig {
+    fn path(&self, id: &str) -> PathBuf {
+        self.dir.join(id)
+    }
+
+    fn lock(&self, id: &str) -> Result<UploadLock<'_>, TusRejection> {
+        if !self
+            .locks
+            .lock()
+            .expect("Upload locks poisoned")
+            .insert(id.to_string())
+        {
+            return Err(TusRejection::Locked);
+        }
+        Ok(UploadLock {
+            locks: &self.locks,
+            id: id.to_string(),
+        })
+    }
+
+    async fn complete(&self, upload: Upload) {
+        if let Some(on_complete) = &self.on_complete {
+            let path = self.path(&upload.id);
+            on_complete(upload, path).await;
+        }
+    }
+}
+
+/// Releases the lock of an upload when dropped.
+struct UploadLock<'a> {
+    locks: &'a Mutex<HashSet<String>>,
+    id: String,
+}
+
+impl Drop for UploadLock<'_> {
+    fn drop(&mut self) {
+        self.locks
+            .lock()
+            .expect("Upload locks poisoned")
+            .remove(&self.id);
+    }
+}
+
+/// A server for resumable uploads with the [tus protocol](https://tus.io/protocols/resumable-upload),
+/// with the creation, termination and checksum extensions.
+/// The bytes of each upload are written to a file named after its id in the directory,
+/// and the state of the uploads is kept in the `UploadStore`.
+/// A request which is interrupted keeps the bytes received so far, so the client can resume from there.
+/// # Default Options
+/// - Max size == unlimited
+/// - On complete == nothing
+/// # Example
+/// ```
+/// use lib::axum::tus::{MemoryUploadStore, TusServer};
+///
+/// let tus = TusServer::new("uploads", MemoryUploadStore::new())
+///     .max_size(10 * 1024 * 1024 * 1024)
+///     .on_complete(|upload, path| async move {
+///         println!("Upload {} finished at {}", upload.id, path.display());
+///     });
+/// let _router: axum::Router = axum::Router::new().nest("/files", tus.router());
+/// ```
+#[derive(Clone)]
+pub struct TusServer {
+    config: Arc<TusConfig>,
+}
+
+impl TusServer {
+    /// Creates a server writing uploads to the directory, which is created if it does not exist.
+    pub fn new(dir: impl Into<PathBuf>, store: impl UploadStore) -> Self {
+        Self {
+            config: Arc::new(TusConfig {
+                dir: dir.into(),
+                store: Arc::new(store),
+                max_size: None,
+                on_complete: None,
+                locks: Arc::default(),
+            }),
+        }
+    }
+
+    /// Sets the maximum size of an upload in bytes.
+    pub fn max_size(self, size: u64) -> Self {
+        self.config(|config| config.max_size = Some(size))
+    }
+
+    /// Sets a function which is called with the upload and the path of its file when all bytes have been received.
+    /// The response to the last request is sent after the function completes.
+    pub fn on_complete<F, Fut>(self, f: F) -> Self
+    where
+        F: Fn(Upload, PathBuf) -> Fut + Send + Sync + 'static,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let on_complete: CompleteHandler = Arc::new(move |upload, path| Box::pin(f(upload, path)));
+        self.config(|config| config.on_complete = Some(on_complete))
+    }
+
+    /// Creates a router handling the tus protocol, to be nested at the path the uploads are sent to.
+    pub fn router<S>(self) -> Router<S>
+    where
+        S: Clone + Send + Sync + 'static,
+    {
+        Router::new()
+            .route("/", options(discover).post(create))
+            .route("/:id", head(info).patch(append).delete(terminate))
+            .layer(from_fn(tus_resumable))
+            .with_state(self.config)
+    }
+
+    fn config(mut self, f: impl FnOnce(&mut TusConfig)) -> Self {
+        f(Arc::make_mut(&mut self.config));
+        self
+    }
+}
+
+/// Rejects requests for other versions of the protocol, and adds the version to all responses.
+async fn tus_resumable(request: Request, next: Next) -> Response {
+    let supported = request.method() == Method::OPTIONS
+        || request
+            .headers()
+            .get(TUS_RESUMABLE)
+            .is_some_and(|version| version == TUS_VERSION);
+    let mut response = if supported {
+        next.run(request).await
+    } else {
+        TusRejection::UnsupportedVersion.into_response()
+    };
+    response
+        .headers_mut()
+        .insert(TUS_RESUMABLE, HeaderValue::from_static(TUS_VERSION));
+    response
+}
+
+async fn discover(State(config): State<Arc<TusConfig>>) -> Response {
+    let mut headers = HeaderMap::new();
+    headers.insert(TUS_VERSION_HEADER, HeaderValue::from_static(TUS_VERSION));
+    headers.insert(
+        TUS_EXTENSION,
+        HeaderValue::from_static("creation,termination,checksum"),
+    );
+    headers.insert(
+        TUS_CHECKSUM_ALGORITHM,
+        HeaderValue::from_static("sha1,sha256"),
+    );
+    if let Some(max_size) = config.max_size {
+        headers.insert(TUS_MAX_SIZE, HeaderValue::from(max_size));
+    }
+    (StatusCode::NO_CONTENT, headers).into_response()
+}
+
+async fn create(
+    State(config): State<Arc<TusConfig>>,
+    OriginalUri(uri): OriginalUri,
+    headers: HeaderMap,
+) -> Result<Response, TusRejection> {
+    let length = match parse_header::<u64>(&headers, UPLOAD_LENGTH)? {
+        Some(length) => length,
+        None if headers.contains_key(UPLOAD_DEFER_LENGTH) => {
+            return Err(TusRejection::DeferredLength)
+        }
+        None => return Err(TusRejection::InvalidHeader(UPLOAD_LENGTH)),
+    };
+    if let Some(max_size) = config.max_size.filter(|max_size| length > *max_size) {
+        return Err(TusRejection::TooLarge(max_size));
+    }
+    let metadata = match headers.get(UPLOAD_METADATA) {
+        Some(metadata) => metadata
+            .to_str()
+            .ok()
+            .and_then(decode_metadata)
+            .ok_or(TusRejection::InvalidHeader(UPLOAD_METADATA))?,
+        None => BTreeMap::new(),
+    };
+    let upload = Upload::new(random_id(), length, metadata);
+    fs::create_dir_all(&config.dir).await?;
+    fs::File::create(config.path(&upload.id)).await?;
+    config.store.create(&upload).await?;
+    let location = format!("{}/{}", uri.path().trim_end_matches('/'), upload.id);
+    if upload.is_complete() {
+        config.complete(upload).await;
+    }
+    Ok((StatusCode::CREATED, [(LOCATION, location)]).into_response())
+}
+
+async fn info(
+    State(config): State<Arc<TusConfig>>,
+    Path(id): Path<String>,
+) -> Result<Response, TusRejection> {
+    let upload = config.store.get(&id).await?.ok_or(TusRejection::NotFound)?;
+    let mut headers = HeaderMap::new();
+    headers.insert(UPLOAD_OFFSET, HeaderValue::from(upload.offset));
+    headers.insert(UPLOAD_LENGTH, HeaderValue::from(upload.length));
+    headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    if !upload.metadata.is_empty() {
+        if let Ok(metadata) = HeaderValue::try_from(encode_metadata(&upload.metadata)) {
+            headers.insert(UPLOAD_METADATA, metadata);
+        }
+    }
+    Ok((StatusCode::OK, headers).into_response())
+}
+
+async fn append(
+    State(config): State<Arc<TusConfig>>,
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    body: Body,
+) -> Result<Response, TusRejection> {
+    if !headers
+        .get(CONTENT_TYPE)
+        .is_some_and(|content_type| content_type == OFFSET_OCTET_STREAM)
+    {
+        return Err(TusRejection::UnsupportedMediaType);
+    }
+    let offset = parse_header::<u64>(&headers, UPLOAD_OFFSET)?
+        .ok_or(TusRejection::InvalidHeader(UPLOAD_OFFSET))?;
+    let checksum = headers
+        .get(UPLOAD_CHECKSUM)
+        .map(Checksum::from_header)
+        .transpose()?;
+    let _lock = config.lock(&id)?;
+    let mut upload = config.store.get(&id).await?.ok_or(TusRejection::NotFound)?;
+    if offset != upload.offset {
+        return Err(TusRejection::OffsetMismatch {
+            expected: upload.offset,
+            actual: offset,
+        });
+    }
+
+    let mut file = OpenOptions::new()
+        .write(true)
+        .open(config.path(&id))
+        .await?;
+    // Discards bytes written after the offset by an earlier request which failed
+    file.set_len(offset).await?;
+    file.seek(SeekFrom::Start(offset)).await?;
+    let mut hasher = checksum.as_ref().map(Checksum::hasher);
+    let mut stream = body.into_data_stream();
+    let mut received = 0;
+    let mut interrupted = false;
+    while let Some(chunk) = stream.next().await {
+        let Ok(chunk) = chunk else {
+            interrupted = true;
+            break;
+        };
+        if offset + received + chunk.len() as u64 > upload.length {
+            file.set_len(offset).await?;
+            return Err(TusRejection::TooLarge(upload.length));
+        }
+        file.write_all(&chunk).await?;
+        if let Some(hasher) = &mut hasher {
+            hasher.update(&chunk);
+        }
+        received += chunk.len() as u64;
+    }
+    file.flush().await?;
+    if let (Some(checksum), Some(hasher)) = (checksum, hasher) {
+        if interrupted || hasher.finalize() != checksum.expected {
+            file.set_len(offset).await?;
+            return Err(TusRejection::ChecksumMismatch);
+        }
+    }
+    if interrupted {
+        warn!("Upload {id} was interrupted after {received} bytes");
+    }
+
+    upload.offset += received;
+    config.store.set_offset(&id, upload.offset).await?;
+    let new_offset = upload.offset;
+    if upload.is_complete() {
+        config.complete(upload).await;
+    }
+    Ok((StatusCode::NO_CONTENT, [(UPLOAD_OFFSET, new_offset)]).into_response())
+}
+
+async fn terminate(
+    State(config): State<Arc<TusConfig>>,
+    Path(id): Path<String>,
+) -> Result<Response, TusRejection> {
+    let _lock = config.lock(&id)?;
+    config.store.get(&id).await?.ok_or(TusRejection::NotFound)?;
+    match fs::remove_file(config.path(&id)).await {
+        Err(error) if error.kind() != io::ErrorKind::NotFound => return Err(error.into()),
+        _ => {}
+    }
+    config.store.delete(&id).await?;
+    Ok(StatusCode::NO_CONTENT.into_response())
+}
+
+/// The checksum of a request body, from the `Upload-Checksum` header.
+struct Checksum {
+    algorithm: ChecksumAlgorithm,
+    expected: Vec<u8>,
+}
+
+#[derive(Clone, Copy)]
+enum ChecksumAlgorithm {
+    Sha1,
+    Sha256,
+}
+
+enum ChecksumHasher {
+    Sha1(Sha1),
+    Sha256(Sha256),
+}
+
+impl Checksum {
+    fn from_header(value: &HeaderValue) -> Result<Self, TusRejection> {
+        let invalid = || TusRejection::InvalidHeader(UPLOAD_CHECKSUM);
+        let (algorithm, checksum) = value
+            .to_str()
+            .ok()
+            .and_then(|value| value.split_once(' '))
+            .ok_or_else(invalid)?;
+        let algorithm = match algorithm {
+            "sha1" => ChecksumAlgorithm::Sha1,
+            "sha256" => ChecksumAlgorithm::Sha256,
+            algorithm => return Err(TusRejection::UnsupportedChecksum(algorithm.to_string())),
+        };
+        Ok(Self {
+            algorithm,
+            expected: STANDARD.decode(checksum).map_err(|_| invalid())?,
+        })
+    }
+
+    fn hasher(&self) -> ChecksumHasher {
+        match self.algorithm {
+            ChecksumAlgorithm::Sha1 => ChecksumHasher::Sha1(Sha1::new()),
+            ChecksumAlgorithm::Sha256 => ChecksumHasher::Sha256(Sha256::new()),
+        }
+    }
+}
+
+impl ChecksumHasher {
+    fn update(&mut self, bytes: &[u8]) {
+        match self {
+            ChecksumHasher::Sha1(hasher) => hasher.update(bytes),
+            ChecksumHasher::Sha256(hasher) => hasher.update(bytes),
+        }
+    }
+
+    fn finalize(self) -> Vec<u8> {
+        match self {
+            ChecksumHasher::Sha1(hasher) => hasher.finalize().to_vec(),
+            ChecksumHasher::Sha256(hasher) => hasher.finalize().to_vec(),
+        }
+    }
+}
+
+fn parse_header<T: std::str::FromStr>(
+    headers: &HeaderMap,
+    name: HeaderName,
+) -> Result<Option<T>, TusRejection> {
+    headers
+        .get(&name)
+        .map(|value| {
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.parse().ok())
+                .ok_or(TusRejection::InvalidHeader(name.clone()))
+        })
+        .transpose()
+}
+
+/// Decodes `Upload-Metadata`, a comma separated list of keys followed by an optional base64 encoded value.
+fn decode_metadata(header: &str) -> Option<BTreeMap<String, String>> {
+    header
+        .split(',')
+        .map(str::trim)
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| match pair.split_once(' ') {
+            Some((key, value)) => {
+                let value = String::from_utf8(STANDARD.decode(value).ok()?).ok()?;
+                Some((key.to_string(), value))
+            }
+            None => Some((pair.to_string(), String::new())),
+        })
+        .collect()
+}
+
+fn encode_metadata(metadata: &BTreeMap<String, String>) -> String {
+    metadata
+        .iter()
+        .map(|(key, value)| match value.is_empty() {
+            true => key.clone(),
+            false => format!("{key} {}", STANDARD.encode(value)),
+        })
+        .collect::<Vec<_>>()
+        .join(",")
+}
+
+fn random_id() -> String {
+    let mut bytes = [0u8; 16];
+    rand::thread_rng().fill_bytes(&mut bytes);
+    bytes.iter().map(|byte| format!("{byte:02x}")).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::axum::tus::MemoryUploadStore;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    fn request(method: Method, uri: &str) -> axum::http::request::Builder {
+        Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(TUS_RESUMABLE, TUS_VERSION)
+    }
+
+    fn patch(uri: &str, offset: u64) -> axum::http::request::Builder {
+        request(Method::PATCH, uri)
+            .header(CONTENT_TYPE, OFFSET_OCTET_STREAM)
+            .header(UPLOAD_OFFSET, offset)
+    }
+
+    async fn create(router: &Router, length: u64) -> String {
+        let response = router
+            .clone()
+            .oneshot(
+                request(Method::POST, "/files")
+                    .header(UPLOAD_LENGTH, length)
+                    .header(UPLOAD_METADATA, "filename aGVsbG8udHh0,private")
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CREATED);
+        response.headers()[LOCATION].to_str().unwrap().to_string()
+    }
+
+    fn router(dir: &TempDir) -> (Router, Arc<AtomicBool>) {
+        let completed = Arc::new(AtomicBool::new(false));
+        let flag = completed.clone();
+        let tus = TusServer::new(dir.path(), MemoryUploadStore::new())
+            .max_size(100)
+            .on_complete(move |upload, path| {
+                let flag = flag.clone();
+                async move {
+                    assert_eq!(upload.metadata["filename"], "hello.txt");
+                    assert_eq!(std::fs::read(path).unwrap(), b"Hello, World!");
+                    flag.store(true, Ordering::SeqCst);
+                }
+            });
+        (Router::new().nest("/files", tus.router()), completed)
+    }
+
+    #[tokio::test]
+    async fn test_discover() {
+        let dir = TempDir::new().unwrap();
+        let (router, _) = router(&dir);
+        let response = router
+            .oneshot(Request::options("/files").body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[TUS_VERSION_HEADER], TUS_VERSION);
+        assert_eq!(response.headers()[TUS_MAX_SIZE], "100");
+        assert_eq!(response.headers()[TUS_RESUMABLE], TUS_VERSION);
+    }
+
+    #[tokio::test]
+    async fn test_resumable_upload() {
+        let dir = TempDir::new().unwrap();
+        let (router, completed) = router(&dir);
+        let location = create(&router, 13).await;
+        assert!(location.starts_with("/files/"));
+
+        let response = router
+            .clone()
+            .oneshot(patch(&location, 0).body(Body::from("Hello")).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert_eq!(response.headers()[UPLOAD_OFFSET], "5");
+        assert!(!completed.load(Ordering::SeqCst));
+
+        let response = router
+            .clone()
+            .oneshot(
+                request(Method::HEAD, &location)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[UPLOAD_OFFSET], "5");
+        assert_eq!(response.headers()[UPLOAD_LENGTH], "13");
+        assert_eq!(
+            response.headers()[UPLOAD_METADATA],
+            "filename aGVsbG8udHh0,private"
+        );
+
+        let response = router
+            .clone()
+            .oneshot(patch(&location, 0).body(Body::from("Hello")).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::CONFLICT);
+
+        let response = router
+            .clone()
+            .oneshot(patch(&location, 5).body(Body::from(", World!")).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[UPLOAD_OFFSET], "13");
+        assert!(completed.load(Ordering::SeqCst));
+    }
+
+    #[tokio::test]
+    async fn test_checksum() {
+        let dir = TempDir::new().unwrap();
+        let (router, _) = router(&dir);
+        let location = create(&router, 13).await;
+        let checksum = STANDARD.encode(Sha1::digest(b"Hello"));
+
+        let response = router
+            .clone()
+            .oneshot(
+                patch(&location, 0)
+                    .header(UPLOAD_CHECKSUM, format!("sha1 {checksum}"))
+                    .body(Body::from("Hallo"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status().as_u16(), 460);
+
+        let response = router
+            .clone()
+            .oneshot(
+                patch(&location, 0)
+                    .header(UPLOAD_CHECKSUM, format!("sha1 {checksum}"))
+                    .body(Body::from("Hello"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.headers()[UPLOAD_OFFSET], "5");
+
+        let response = router
+            .oneshot(
+                patch(&location, 5)
+                    .header(UPLOAD_CHECKSUM, "md5 abc=")
+                    .body(Body::from("!"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_terminate() {
+        let dir = TempDir::new().unwrap();
+        let (router, _) = router(&dir);
+        let location = create(&router, 13).await;
+        let id = location.rsplit('/').next().unwrap();
+        assert!(dir.path().join(id).exists());
+
+        let response = router
+            .clone()
+            .oneshot(
+                request(Method::DELETE, &location)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NO_CONTENT);
+        assert!(!dir.path().join(id).exists());
+
+        let response = router
+            .oneshot(
+                request(Method::HEAD, &location)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_rejections() {
+        let dir = TempDir::new().unwrap();
+        let (router, _) = router(&dir);
+        let response = router
+            .clone()
+            .oneshot(
+                Request::post("/files")
+                    .header(UPLOAD_LENGTH, 10)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+        assert_eq!(response.headers()[TUS_VERSION_HEADER], TUS_VERSION);
+
+        let response = router
+            .clone()
+            .oneshot(
+                request(Method::POST, "/files")
+                    .header(UPLOAD_LENGTH, 101)
+                    .body(Body::empty())
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let location = create(&router, 3).await;
+        let response = router
+            .clone()
+            .oneshot(patch(&location, 0).body(Body::from("Hello")).unwrap())
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let response = router
+            .oneshot(
+                request(Method::PATCH, &location)
+                    .header(UPLOAD_OFFSET, 0)
+                    .body(Body::from("Hi"))
+                    .unwrap(),
+            )
+            .await
+            .unwrap();
+        assert_eq!(response.status(), StatusCode::UNSUPPORTED_MEDIA_TYPE);
+    }
+
+    #[test]
+    fn test_metadata() {
+        let metadata = decode_metadata("filename aGVsbG8udHh0, private").unwrap();
+        assert_eq!(metadata["filename"], "hello.txt");
+        assert_eq!(metadata["private"], "");
+        assert_eq!(encode_metadata(&metadata), "filename aGVsbG8udHh0,private");
+        assert!(decode_metadata("filename !!!").is_none());
+    }
+}
@@ -0,0 +1,116 @@
+use {
+    axum::async_trait,
+    std::{
+        collections::{BTreeMap, HashMap},
+        sync::{Arc, RwLock},
+    },
+    thiserror::Error,
+};
+
+/// The state of a resumable upload.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Upload {
+    pub id: String,
+    /// The total size of the upload in bytes.
+    pub length: u64,
+    /// The number of bytes received so far.
+    pub offset: u64,
+    /// The decoded `Upload-Metadata` sent when the upload was created.
+    pub metadata: BTreeMap<String, String>,
+}
+
+impl Upload {
+    /// Creates a new upload with no bytes received.
+    pub fn new(id: impl Into<String>, length: u64, metadata: BTreeMap<String, String>) -> Self {
+        Self {
+            id: id.into(),
+            length,
+            offset: 0,
+            metadata,
+        }
+    }
+
+    /// Returns true if all bytes of the upload have been received.
+    pub fn is_complete(&self) -> bool {
+        self.offset >= self.length
+    }
+}
+
+/// Error type for upload stores.
+#[derive(Debug, Error)]
+pub enum UploadStoreError {
+    #[error("Upload store error: {0}")]
+    Store(String),
+}
+
+/// Storage for the state of resumable uploads.
+/// The bytes of the uploads are written to disk by the `TusServer`.
+///
+/// Implementing the trait requires the `async_trait` macro.
+#[async_trait]
+pub trait UploadStore: Send + Sync + 'static {
+    /// Stores a new upload.
+    async fn create(&self, upload: &Upload) -> Result<(), UploadStoreError>;
+    /// Loads the upload with the given id, or `None` if it does not exist.
+    async fn get(&self, id: &str) -> Result<Option<Upload>, UploadStoreError>;
+    /// Sets the number of bytes received for the upload.
+    async fn set_offset(&self, id: &str, offset: u64) -> Result<(), UploadStoreError>;
+    /// Deletes the upload with the given id, if it exists.
+    async fn delete(&self, id: &str) -> Result<(), UploadStoreError>;
+}
+
+/// An upload store keeping the state of all uploads in memory.
+/// Uploads cannot be resumed after the application restarts, and are not shared between instances.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryUploadStore {
+    uploads: Arc<RwLock<HashMap<String, Upload>>>,
+}
+
+impl MemoryUploadStore {
+    pub fn new() -> Self {
+        Self::default()
+    }
+}
+
+#[async_trait]
+impl UploadStore for MemoryUploadStore {
+    async fn create(&self, upload: &Upload) -> Result<(), UploadStoreError> {
+        self.uploads
+            .write()
+            .expect("Upload store lock poisoned")
+            .insert(upload.id.clone(), upload.clone());
+        Ok(())
+    }
+
+    async fn get(&self, id: &str) -> Result<Option<Upload>, UploadStoreError> {
+        Ok(self
+            .uploads
+            .read()
+            .expect("Upload store lock poisoned")
+            .get(id)
+            .cloned())
+    }
+
+    async fn set_offset(&self, id: &str, offset: u64) -> Result<(), UploadStoreError> {
+        match self
+            .uploads
+            .write()
+            .expect("Upload store lock poisoned")
+            .get_mut(id)
+        {
+            Some(upload) => {
+                upload.offset = offset;
+                Ok(())
+            }
+            None => Err(UploadStoreError::Store(format!("Upload {id} not found"))),
+        }
+    }
+
+    async fn delete(&self, id: &str) -> Result<(), UploadStoreError> {
+        self.uploads
+            .write()
+            .expect("Upload store lock poisoned")
+            .remove(id);
+        Ok(())
+    }
+}
@@ -0,0 +1,261 @@
+use {
+    crate::axum::router::RouteTable,
+    std::fmt::{Display, Write},
+    thiserror::Error,
+};
+
+/// Declares named routes with a typed URL builder, checking the path parameters at compile time.
+/// # Example
+/// ```
+/// use lib::axum::url::named_routes;
+///
+/// named_routes! {
+///     /// The posts of a user.
+///     pub UserPosts = "user.posts" => "/users/:user/posts";
+/// }
+///
+/// async fn user_posts() {}
+///
+/// let (_router, routes) = lib::axum::router::RouteTable::record(|| -> axum::Router {
+///     lib::routes!(get UserPosts::PATH => user_posts as UserPosts::NAME)
+/// });
+/// let url = UserPosts::url("admin").query("page", 2).build(&routes);
+/// assert_eq!(url, Ok("/users/admin/posts?page=2".to_string()));
+/// ```
+#[cfg(feature = "route-macros")]
+pub use route_macros::named_routes;
+
+/// Error type for generating URLs for named routes.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum UrlError {
+    #[error("No route is named `{0}`")]
+    UnknownRoute(String),
+    #[error("Missing parameter `{param}` for route `{route}`")]
+    MissingParam { route: String, param: String },
+    #[error("Route `{route}` has no parameter `{param}`")]
+    UnknownParam { route: String, param: String },
+}
+
+/// Builder for the URL of a named route, created with `url_for` or the `url_for!` macro.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UrlFor {
+    name: String,
+    params: Vec<(String, String)>,
+    query: Vec<(String, String)>,
+}
+
+/// Creates a builder for the URL of a route named in `routes!` or a route attribute.
+/// Parameters and query values are percent-encoded.
+/// Routes declared with `named_routes!` have a typed `url` function creating the builder.
+///
+/// The URL is built from the `RouteTable` of the router, so in handlers,
+/// add the table to the router as an `Extension` and extract it as `RouteTable`.
+/// Routes created outside of `RouteTable::record` are not in the table,
+/// so building their URLs fails with `UrlError::UnknownRoute`.
+/// # Example
+/// ```
+/// use lib::axum::{router::RouteTable, url::url_for};
+///
+/// async fn show_user() {}
+///
+/// let (_router, routes) = RouteTable::record(|| -> axum::Router {
+///     lib::routes!(get "/users/:id" => show_user as "user.show")
+/// });
+/// let url = url_for("user.show").param("id", "a b").query("tab", 1).build(&routes);
+/// assert_eq!(url, Ok("/users/a%20b?tab=1".to_string()));
+/// ```
+pub fn url_for(name: impl Into<String>) -> UrlFor {
+    UrlFor {
+        name: name.into(),
+        params: Vec::new(),
+        query: Vec::new(),
+    }
+}
+
+impl UrlFor {
+    /// Sets the value of a path parameter, like `id` in `/users/:id`.
+    pub fn param(mut self, name: impl Into<String>, value: impl Display) -> Self {
+        self.params.push((name.into(), value.to_string()));
+        self
+    }
+
+    /// Appends a parameter to the query string.
+    pub fn query(mut self, name: impl Into<String>, value: impl Display) -> Self {
+        self.query.push((name.into(), value.to_string()));
+        self
+    }
+
+    /// Builds the URL, using the path of the route in the table.
+    /// Fails if the route does not exist, or if a path parameter is missing or unknown.
+    pub fn build(&self, routes: &RouteTable) -> Result<String, UrlError> {
+        let route = routes
+            .named(&self.name)
+            .ok_or_else(|| UrlError::UnknownRoute(self.name.clone()))?;
+        self.build_path(&route.path)
+    }
+
+    fn build_path(&self, template: &str) -> Result<String, UrlError> {
+        let names: Vec<_> = template
+            .split('/')
+            .filter_map(|segment| segment.strip_prefix([':', '*']))
+            .collect();
+        if let Some((param, _)) = self
+            .params
+            .iter()
+            .find(|(param, _)| !names.contains(&param.as_str()))
+        {
+            return Err(UrlError::UnknownParam {
+                route: self.name.clone(),
+                param: param.clone(),
+            });
+        }
+        let mut url = String::new();
+        for segment in template.split('/').skip(1) {
+            url.push('/');
+            let Some(name) = segment.strip_prefix([':', '*']) else {
+                url.push_str(segment);
+                continue;
+            };
+            let value = self
+                .params
+                .iter()
+                .find(|(param, _)| param == name)
+                .map(|(_, value)| value)
+                .ok_or_else(|| UrlError::MissingParam {
+                    route: self.name.clone(),
+                    param: name.to_string(),
+                })?;
+            if segment.starts_with('*') {
+                let value = value.strip_prefix('/').unwrap_or(value);
+                let segments: Vec<_> = value.split('/').map(percent_encode).collect();
+                url.push_str(&segments.join("/"));
+            } else {
+                url.push_str(&percent_encode(value));
+            }
+        }
+        for (index, (name, value)) in self.query.iter().enumerate() {
+            url.push(if index == 0 { '?' } else { '&' });
+            let _ = write!(url, "{}={}", percent_encode(name), percent_encode(value));
+        }
+        Ok(url)
+    }
+}
+
+/// Encodes everything but unreserved characters, which is safe for both path segments and queries.
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+    }
+    encoded
+}
+
+/// Generates the URL of a named route in the `RouteTable`,
+/// with path parameters, and query parameters after a `;`.
+/// Returns a `Result<String, UrlError>`.
+/// # Example
+/// ```
+/// async fn show_post() {}
+///
+/// let (_router, routes) = lib::axum::router::RouteTable::record(|| -> axum::Router {
+///     lib::routes!(#[name = "post.show"] get "/users/:user/posts/:id" => show_post)
+/// });
+/// let url = lib::url_for!(routes, "post.show", user = "admin", id = 1; page = 2);
+/// assert_eq!(url, Ok("/users/admin/posts/1?page=2".to_string()));
+/// ```
+#[macro_export]
+macro_rules! url_for {
+    ($routes:expr, $name:expr $(, $param:ident = $value:expr)* $(; $($query:ident = $query_value:expr),*)? $(,)?) => {
+        $crate::axum::url::url_for($name)
+            $(.param(stringify!($param), $value))*
+            $($(.query(stringify!($query), $query_value))*)?
+            .build(&$routes)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{router, routes};
+
+    async fn index() {}
+
+    #[test]
+    fn test_percent_encode() {
+        assert_eq!(percent_encode("a-z_0.9~"), "a-z_0.9~");
+        assert_eq!(percent_encode("a b/c?d&e"), "a%20b%2Fc%3Fd%26e");
+        assert_eq!(percent_encode("æ"), "%C3%A6");
+    }
+
+    #[test]
+    fn test_build_path() {
+        let url = url_for("test").param("id", 1).param("path", "/a b/c");
+        assert_eq!(
+            url.build_path("/users/:id/files/*path"),
+            Ok("/users/1/files/a%20b/c".to_string())
+        );
+        assert_eq!(url_for("test").build_path("/"), Ok("/".to_string()));
+    }
+
+    #[test]
+    fn test_missing_param() {
+        assert_eq!(
+            url_for("test").build_path("/users/:id"),
+            Err(UrlError::MissingParam {
+                route: "test".to_string(),
+                param: "id".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_unknown_param() {
+        let url = url_for("test").param("id", 1).param("other", 2);
+        assert_eq!(
+            url.build_path("/users/:id"),
+            Err(UrlError::UnknownParam {
+                route: "test".to_string(),
+                param: "other".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_url_for_nested_route() {
+        router!(
+            "/url",
+            routes!(#[name = "url.show"] get "/items/:id" => || async {})
+        );
+        let (_router, routes) = RouteTable::record(router);
+        assert_eq!(
+            url_for!(routes, "url.show", id = 5; q = "a&b"),
+            Ok("/url/items/5?q=a%26b".to_string())
+        );
+        assert_eq!(
+            url_for("url.missing").build(&routes),
+            Err(UrlError::UnknownRoute("url.missing".to_string()))
+        );
+    }
+
+    #[cfg(feature = "route-macros")]
+    #[test]
+    fn test_named_routes() {
+        named_routes! {
+            Files = "url.files" => "/url/files/:type/*path";
+        }
+        let (_router, routes) = RouteTable::record(|| -> axum::Router {
+            routes!(get Files::PATH => index as Files::NAME)
+        });
+        assert_eq!(
+            Files::url("text", "a/b c").query("v", 1).build(&routes),
+            Ok("/url/files/text/a/b%20c?v=1".to_string())
+        );
+    }
+}
@@ -0,0 +1,605 @@
+pub use regex::Regex;
+#[cfg(feature = "derive")]
+pub use validate_derive::Validate;
+use {
+    axum::{
+        async_trait,
+        extract::{FromRequest, FromRequestParts, Query, Request},
+        http::{request::Parts, StatusCode},
+        response::{IntoResponse, Response},
+        Form, Json,
+    },
+    serde::Serialize,
+    serde_json::json,
+    std::fmt::{self, Display, Formatter},
+};
+
+/// A type which can check its values, after it has been deserialized.
+/// Usually derived with `#[derive(Validate)]`.
+pub trait Validate {
+    /// Returns all the invalid fields, or `Ok` if the value is valid.
+    fn validate(&self) -> Result<(), ValidationErrors>;
+}
+
+impl<T: Validate> Validate for Option<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        match self {
+            Some(value) => value.validate(),
+            None => Ok(()),
+        }
+    }
+}
+
+impl<T: Validate> Validate for Vec<T> {
+    fn validate(&self) -> Result<(), ValidationErrors> {
+        let mut errors = ValidationErrors::new();
+        for (index, value) in self.iter().enumerate() {
+            errors.merge(format!("[{index}]"), value.validate());
+        }
+        errors.into_result()
+    }
+}
+
+/// An invalid field, with the path to the field like `address.zip` or `items[0].name`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// The invalid fields of a value.
+/// Responds with 422 Unprocessable Entity and a JSON body listing every field.
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct ValidationErrors {
+    errors: Vec<FieldError>,
+}
+
+impl ValidationErrors {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds an error for the field.
+    pub fn add(&mut self, field: impl Into<String>, message: impl Into<String>) {
+        self.errors.push(FieldError {
+            field: field.into(),
+            message: message.into(),
+        });
+    }
+
+    /// Adds the errors of a nested value, with their paths prefixed by the field.
+    pub fn merge(&mut self, field: impl Display, result: Result<(), ValidationErrors>) {
+        let Err(nested) = result else {
+            return;
+        };
+        for error in nested.errors {
+            let field = match error.field.starts_with('[') {
+                true => format!("{field}{}", error.field),
+                false => format!("{field}.{}", error.field),
+            };
+            self.add(field, error.message);
+        }
+    }
+
+    /// Returns the errors.
+    pub fn errors(&self) -> &[FieldError] {
+        &self.errors
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.errors.is_empty()
+    }
+
+    /// Returns `Ok` if there are no errors, or `Err` with the errors.
+    pub fn into_result(self) -> Result<(), Self> {
+        match self.is_empty() {
+            true => Ok(()),
+            false => Err(self),
+        }
+    }
+}
+
+impl Display for ValidationErrors {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let errors: Vec<_> = self
+            .errors
+            .iter()
+            .map(|error| format!("{}: {}", error.field, error.message))
+            .collect();
+        write!(f, "{}", errors.join(", "))
+    }
+}
+
+impl std::error::Error for ValidationErrors {}
+
+impl IntoResponse for ValidationErrors {
+    fn into_response(self) -> Response {
+        (
+            StatusCode::UNPROCESSABLE_ENTITY,
+            Json(json!({ "error": "Validation failed", "fields": self.errors })),
+        )
+            .into_response()
+    }
+}
+
+/// Extractor which validates the value of another extractor, like `Valid<Json<T>>`,
+/// `Valid<Query<T>>` or `Valid<Form<T>>`.
+/// Invalid values are rejected with the `ValidationErrors`.
+/// # Example
+/// ```
+/// use axum::Json;
+/// use lib::axum::validation::{Valid, Validate, ValidationErrors};
+///
+/// #[derive(serde::Deserialize)]
+/// struct CreateUser {
+///     name: String,
+/// }
+///
+/// impl Validate for CreateUser {
+///     fn validate(&self) -> Result<(), ValidationErrors> {
+///         let mut errors = ValidationErrors::new();
+///         if self.name.is_empty() {
+///             errors.add("name", "must not be empty");
+///         }
+///         errors.into_result()
+///     }
+/// }
+///
+/// async fn create(Valid(Json(user)): Valid<Json<CreateUser>>) -> String {
+///     user.name
+/// }
+/// let _router: axum::Router = lib::routes!(post "/users" => create);
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Valid<E>(pub E);
+
+/// Extractors with a value which can be validated by `Valid`.
+pub trait Validated {
+    type Value: Validate;
+
+    fn value(&self) -> &Self::Value;
+}
+
+impl<T: Validate> Validated for Json<T> {
+    type Value = T;
+
+    fn value(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Validate> Validated for Query<T> {
+    type Value = T;
+
+    fn value(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T: Validate> Validated for Form<T> {
+    type Value = T;
+
+    fn value(&self) -> &T {
+        &self.0
+    }
+}
+
+/// Rejection type for `Valid`, either from the inner extractor or from validation.
+#[derive(Debug)]
+pub enum ValidRejection<R> {
+    Extractor(R),
+    Invalid(ValidationErrors),
+}
+
+impl<R: IntoResponse> IntoResponse for ValidRejection<R> {
+    fn into_response(self) -> Response {
+        match self {
+            ValidRejection::Extractor(rejection) => rejection.into_response(),
+            ValidRejection::Invalid(errors) => errors.into_response(),
+        }
+    }
+}
+
+#[async_trait]
+impl<S, E> FromRequest<S> for Valid<E>
+where
+    S: Send + Sync,
+    E: FromRequest<S> + Validated,
+{
+    type Rejection = ValidRejection<E::Rejection>;
+
+    async fn from_request(req: Request, state: &S) -> Result<Self, Self::Rejection> {
+        let extracted = E::from_request(req, state)
+            .await
+            .map_err(ValidRejection::Extractor)?;
+        extracted
+            .value()
+            .validate()
+            .map_err(ValidRejection::Invalid)?;
+        Ok(Valid(extracted))
+    }
+}
+
+#[async_trait]
+impl<S, E> FromRequestParts<S> for Valid<E>
+where
+    S: Send + Sync,
+    E: FromRequestParts<S> + Validated,
+{
+    type Rejection = ValidRejection<E::Rejection>;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let extracted = E::from_request_parts(parts, state)
+            .await
+            .map_err(ValidRejection::Extractor)?;
+        extracted
+            .value()
+            .validate()
+            .map_err(ValidRejection::Invalid)?;
+        Ok(Valid(extracted))
+    }
+}
+
+/// Rules used by `#[derive(Validate)]`, returning the error message if the value is invalid.
+pub mod rules {
+    use {
+        regex::Regex,
+        std::{
+            collections::{BTreeMap, HashMap},
+            fmt::Display,
+        },
+    };
+
+    /// Values with a length, counted in characters for strings.
+    pub trait HasLength {
+        fn length(&self) -> usize;
+    }
+
+    impl HasLength for str {
+        fn length(&self) -> usize {
+            self.chars().count()
+        }
+    }
+
+    impl HasLength for String {
+        fn length(&self) -> usize {
+            self.as_str().length()
+        }
+    }
+
+    impl<T: HasLength + ?Sized> HasLength for &T {
+        fn length(&self) -> usize {
+            (**self).length()
+        }
+    }
+
+    impl<T> HasLength for [T] {
+        fn length(&self) -> usize {
+            self.len()
+        }
+    }
+
+    impl<T> HasLength for Vec<T> {
+        fn length(&self) -> usize {
+            self.len()
+        }
+    }
+
+    impl<K, V> HasLength for HashMap<K, V> {
+        fn length(&self) -> usize {
+            self.len()
+        }
+    }
+
+    impl<K, V> HasLength for BTreeMap<K, V> {
+        fn length(&self) -> usize {
+            self.len()
+        }
+    }
+
+    pub fn length<T: HasLength + ?Sized>(
+        value: &T,
+        min: Option<usize>,
+        max: Option<usize>,
+    ) -> Result<(), String> {
+        let length = value.length();
+        match (min, max) {
+            (Some(min), Some(max)) if length < min || length > max => {
+                Err(format!("length must be between {min} and {max}"))
+            }
+            (Some(min), None) if length < min => Err(format!("length must be at least {min}")),
+            (None, Some(max)) if length > max => Err(format!("length must be at most {max}")),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn range<T: PartialOrd + Display>(
+        value: &T,
+        min: Option<T>,
+        max: Option<T>,
+    ) -> Result<(), String> {
+        match (min, max) {
+            (Some(min), Some(max)) if *value < min || *value > max => {
+                Err(format!("must be between {min} and {max}"))
+            }
+            (Some(min), None) if *value < min => Err(format!("must be at least {min}")),
+            (None, Some(max)) if *value > max => Err(format!("must be at most {max}")),
+            _ => Ok(()),
+        }
+    }
+
+    pub fn regex<T: AsRef<str> + ?Sized>(value: &T, regex: &Regex) -> Result<(), String> {
+        match regex.is_match(value.as_ref()) {
+            true => Ok(()),
+            false => Err(format!("must match the pattern `{}`", regex.as_str())),
+        }
+    }
+
+    /// Checks that the value looks like an email address, with a local part and a domain with a dot.
+    pub fn email<T: AsRef<str> + ?Sized>(value: &T) -> Result<(), String> {
+        let valid = value
+            .as_ref()
+            .rsplit_once('@')
+            .is_some_and(|(local, domain)| {
+                !local.is_empty()
+                    && local.len() <= 64
+                    && !local.contains(|char: char| char.is_whitespace() || char == '@')
+                    && domain.contains('.')
+                    && domain.split('.').all(|label| {
+                        !label.is_empty()
+                            && !label.starts_with('-')
+                            && !label.ends_with('-')
+                            && label
+                                .chars()
+                                .all(|char| char.is_alphanumeric() || char == '-')
+                    })
+            });
+        match valid {
+            true => Ok(()),
+            false => Err("must be a valid email address".to_string()),
+        }
+    }
+
+    /// Checks that the value is an absolute URL with a scheme and a host, like `https://example.com/path`.
+    pub fn url<T: AsRef<str> + ?Sized>(value: &T) -> Result<(), String> {
+        let value = value.as_ref();
+        let valid = value.split_once("://").is_some_and(|(scheme, rest)| {
+            let host = rest.split(['/', '?', '#']).next().unwrap_or_default();
+            let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+            scheme.starts_with(|char: char| char.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|char| char.is_ascii_alphanumeric() || "+-.".contains(char))
+                && !host.is_empty()
+                && !value.contains(char::is_whitespace)
+        });
+        match valid {
+            true => Ok(()),
+            false => Err("must be a valid URL".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{rules::*, *};
+    use axum::{body::Body, http::header::CONTENT_TYPE};
+    use serde::Deserialize;
+
+    #[derive(Debug, Deserialize)]
+    struct Item {
+        name: String,
+    }
+
+    impl Validate for Item {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            if let Err(message) = length(&self.name, Some(1), None) {
+                errors.add("name", message);
+            }
+            errors.into_result()
+        }
+    }
+
+    #[derive(Debug, Deserialize)]
+    struct Order {
+        email: String,
+        items: Vec<Item>,
+    }
+
+    impl Validate for Order {
+        fn validate(&self) -> Result<(), ValidationErrors> {
+            let mut errors = ValidationErrors::new();
+            if let Err(message) = email(&self.email) {
+                errors.add("email", message);
+            }
+            errors.merge("items", self.items.validate());
+            errors.into_result()
+        }
+    }
+
+    #[test]
+    fn test_rules() {
+        assert!(length("abc", Some(1), Some(3)).is_ok());
+        assert!(length("æøå", None, Some(3)).is_ok());
+        assert!(length("abcd", Some(1), Some(3)).is_err());
+        assert!(length(&vec![1], Some(2), None).is_err());
+        assert!(range(&5, Some(1), Some(10)).is_ok());
+        assert_eq!(
+            range(&0.5, Some(1.0), None),
+            Err("must be at least 1".to_string())
+        );
+        assert!(regex("abc", &Regex::new("^[a-z]+$").unwrap()).is_ok());
+        assert!(regex("ABC", &Regex::new("^[a-z]+$").unwrap()).is_err());
+        assert!(email("user@example.com").is_ok());
+        assert!(email("user@localhost").is_err());
+        assert!(email("user example@example.com").is_err());
+        assert!(email("@example.com").is_err());
+        assert!(url("https://example.com/path?query").is_ok());
+        assert!(url("ftp://user@host").is_ok());
+        assert!(url("example.com").is_err());
+        assert!(url("https:///path").is_err());
+    }
+
+    #[test]
+    fn test_nested_paths() {
+        let order = Order {
+            email: "invalid".to_string(),
+            items: vec![
+                Item {
+                    name: "a".to_string(),
+                },
+                Item {
+                    name: String::new(),
+                },
+            ],
+        };
+        let errors = order.validate().unwrap_err();
+        let fields: Vec<_> = errors
+            .errors()
+            .iter()
+            .map(|error| error.field.as_str())
+            .collect();
+        assert_eq!(fields, ["email", "items[1].name"]);
+    }
+
+    #[tokio::test]
+    async fn test_valid_json() {
+        let request = Request::post("/")
+            .header(CONTENT_TYPE, "application/json")
+            .body(Body::from(r#"{"email": "a@b", "items": [{"name": ""}]}"#))
+            .unwrap();
+        let rejection = Valid::<Json<Order>>::from_request(request, &())
+            .await
+            .unwrap_err();
+        let response = rejection.into_response();
+        assert_eq!(response.status(), StatusCode::UNPROCESSABLE_ENTITY);
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["fields"][0]["field"], "email");
+        assert_eq!(body["fields"][1]["field"], "items[0].name");
+    }
+
+    #[tokio::test]
+    async fn test_valid_query() {
+        let (mut parts, _) = Request::get("/?name=abc").body(()).unwrap().into_parts();
+        let Valid(Query(item)) = Valid::<Query<Item>>::from_request_parts(&mut parts, &())
+            .await
+            .unwrap();
+        assert_eq!(item.name, "abc");
+
+        let (mut parts, _) = Request::get("/?name=").body(()).unwrap().into_parts();
+        assert!(matches!(
+            Valid::<Query<Item>>::from_request_parts(&mut parts, &()).await,
+            Err(ValidRejection::Invalid(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_valid_form() {
+        let request = Request::post("/")
+            .header(CONTENT_TYPE, "application/x-www-form-urlencoded")
+            .body(Body::from("name="))
+            .unwrap();
+        assert!(matches!(
+            Valid::<Form<Item>>::from_request(request, &()).await,
+            Err(ValidRejection::Invalid(_))
+        ));
+    }
+
+    #[cfg(feature = "derive")]
+    mod derive {
+        use super::*;
+
+        #[derive(Debug, Validate)]
+        struct Address {
+            #[validate(regex = "^[0-9]{4}$", message = "must be four digits")]
+            zip: String,
+        }
+
+        fn not_admin(name: &str) -> Result<(), String> {
+            match name {
+                "admin" => Err("is reserved".to_string()),
+                _ => Ok(()),
+            }
+        }
+
+        #[derive(Debug, Validate)]
+        struct User {
+            #[validate(length(min = 1, max = 5), custom = not_admin)]
+            name: String,
+            #[validate(email)]
+            email: String,
+            #[validate(url, rename = "homePage")]
+            home_page: Option<String>,
+            #[validate(range(min = 13, max = 120))]
+            age: Option<u8>,
+            #[validate(nested)]
+            address: Address,
+            #[validate(nested, length(max = 1))]
+            previous: Vec<Address>,
+            unchecked: String,
+        }
+
+        fn user() -> User {
+            User {
+                name: "alice".to_string(),
+                email: "alice@example.com".to_string(),
+                home_page: None,
+                age: Some(30),
+                address: Address {
+                    zip: "0123".to_string(),
+                },
+                previous: vec![],
+                unchecked: String::new(),
+            }
+        }
+
+        #[test]
+        fn test_derive_valid() {
+            assert!(user().validate().is_ok());
+        }
+
+        #[test]
+        fn test_derive_invalid() {
+            let user = User {
+                name: "admin".to_string(),
+                email: "admin".to_string(),
+                home_page: Some("home".to_string()),
+                age: Some(7),
+                address: Address {
+                    zip: "12345".to_string(),
+                },
+                previous: vec![
+                    Address {
+                        zip: "1".to_string(),
+                    },
+                    Address {
+                        zip: "1234".to_string(),
+                    },
+                ],
+                ..user()
+            };
+            let errors = user.validate().unwrap_err();
+            let errors: Vec<_> = errors
+                .errors()
+                .iter()
+                .map(|error| (error.field.as_str(), error.message.as_str()))
+                .collect();
+            assert_eq!(
+                errors,
+                [
+                    ("name", "is reserved"),
+                    ("email", "must be a valid email address"),
+                    ("homePage", "must be a valid URL"),
+                    ("age", "must be between 13 and 120"),
+                    ("address.zip", "must be four digits"),
+                    ("previous", "length must be at most 1"),
+                    ("previous[0].zip", "must be four digits"),
+                ]
+            );
+        }
+    }
+}
@@ -0,0 +1,706 @@
+#[cfg(feature = "serde")]
+use crate::serde::response::{BaseResponse, SerializeObject};
+use {
+    axum::{
+        async_trait,
+        extract::{FromRequestParts, Request},
+        http::{
+            header::{ACCEPT, LINK, VARY},
+            request::Parts,
+            uri::PathAndQuery,
+            HeaderName, HeaderValue, StatusCode, Uri,
+        },
+        response::{IntoResponse, Response},
+        Json,
+    },
+    futures_util::future::BoxFuture,
+    serde_json::json,
+    std::{
+        fmt::{self, Display, Formatter},
+        str::FromStr,
+        sync::Arc,
+        task::{Context, Poll},
+        time::{SystemTime, UNIX_EPOCH},
+    },
+    thiserror::Error,
+    tower::{Layer, Service},
+};
+
+/// Header used to request an API version, and set on responses by the `VersionLayer`.
+pub const API_VERSION: HeaderName = HeaderName::from_static("api-version");
+const DEPRECATION: HeaderName = HeaderName::from_static("deprecation");
+const SUNSET: HeaderName = HeaderName::from_static("sunset");
+
+/// A major version of the API, written as `v2`.
+///
+/// Extracted from requests passing through a `VersionLayer`.
+/// # Example
+/// ```
+/// use lib::axum::versioning::ApiVersion;
+///
+/// async fn handler(version: ApiVersion) -> String {
+///     format!("Requested {version}")
+/// }
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ApiVersion(pub u32);
+
+impl ApiVersion {
+    /// Wraps the body in a `BaseResponse` with this version,
+    /// instead of the package version used by `from!`.
+    #[cfg(feature = "serde")]
+    pub fn response<T: SerializeObject>(self, body: T) -> BaseResponse<T> {
+        BaseResponse::new(self.to_string(), body)
+    }
+}
+
+impl Display for ApiVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "v{}", self.0)
+    }
+}
+
+/// Error returned when parsing an invalid API version.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("Invalid API version")]
+pub struct ParseApiVersionError;
+
+impl FromStr for ApiVersion {
+    type Err = ParseApiVersionError;
+
+    /// Parses `2` or `v2`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+        let number = s.strip_prefix(['v', 'V']).unwrap_or(s);
+        if number.is_empty() || !number.bytes().all(|byte| byte.is_ascii_digit()) {
+            return Err(ParseApiVersionError);
+        }
+        number
+            .parse()
+            .map(ApiVersion)
+            .map_err(|_| ParseApiVersionError)
+    }
+}
+
+/// Rejection type for versioned requests.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum VersionRejection {
+    #[error("Invalid API version `{0}`")]
+    Invalid(String),
+    #[error("Unsupported API version {0}")]
+    Unsupported(ApiVersion),
+    #[error("Route was removed in API version {0}")]
+    Removed(ApiVersion),
+    #[error("Missing VersionLayer")]
+    MissingLayer,
+}
+
+impl IntoResponse for VersionRejection {
+    fn into_response(self) -> Response {
+        let status = match self {
+            VersionRejection::Invalid(_) | VersionRejection::Unsupported(_) => {
+                StatusCode::BAD_REQUEST
+            }
+            VersionRejection::Removed(_) => StatusCode::GONE,
+            VersionRejection::MissingLayer => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        (status, Json(json!({ "error": self.to_string() }))).into_response()
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ApiVersion {
+    type Rejection = VersionRejection;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        parts
+            .extensions
+            .get::<ApiVersion>()
+            .copied()
+            .ok_or(VersionRejection::MissingLayer)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Config {
+    versions: Vec<ApiVersion>,
+    default: Option<ApiVersion>,
+    header: HeaderName,
+    prefix: bool,
+    accept: bool,
+}
+
+/// Layer negotiating the API version of requests, which handlers extract as `ApiVersion`.
+///
+/// The version is taken from the first of:
+/// - A path prefix like `/v2/users`, which is removed from the path before routing.
+/// - The `Api-Version` header, like `2` or `v2`.
+/// - A `version` parameter or a vendor suffix in the `Accept` header,
+///   like `application/json; version=2` or `application/vnd.example.v2+json`.
+/// - The default version, or the latest supported version.
+///
+/// A single router then serves every version, with routes declared as introduced or deprecated
+/// in a version using the `Introduced` and `Deprecated` layers.
+/// For the path prefix to be removed before routing, the layer must wrap the router as a service,
+/// like the `NormalizePathLayer`, instead of being added with `Router::layer`.
+/// Unsupported versions are rejected with 400 Bad Request,
+/// and responses get the version in the `Api-Version` header.
+/// # Default Options
+/// - Default version == latest
+/// - Header == `Api-Version`
+/// - Path prefix == true
+/// - Accept == true
+/// # Example
+/// ```
+/// use axum::{extract::Request, ServiceExt};
+/// use lib::axum::versioning::{ApiVersion, Deprecated, Introduced, VersionLayer};
+/// use tower::Layer;
+///
+/// async fn show(version: ApiVersion) -> String {
+///     version.to_string()
+/// }
+///
+/// let router: axum::Router = lib::routes!(
+///     get "/users/:id" => show,
+///     #[layer(Introduced::new(2))] get "/users/:id/posts" => || async {},
+///     #[layer(Deprecated::since(2))] get "/users/:id/profile" => || async {},
+/// );
+/// let app = VersionLayer::new([1, 2]).layer(router);
+/// let _service = ServiceExt::<Request>::into_make_service(app);
+/// ```
+#[derive(Debug, Clone)]
+pub struct VersionLayer {
+    config: Arc<Config>,
+}
+
+impl VersionLayer {
+    /// Creates a layer supporting the given major versions.
+    /// # Panics
+    /// If no versions are given.
+    pub fn new(versions: impl IntoIterator<Item = u32>) -> Self {
+        let mut versions: Vec<_> = versions.into_iter().map(ApiVersion).collect();
+        assert!(!versions.is_empty(), "At least one API version is required");
+        versions.sort();
+        versions.dedup();
+        Self {
+            config: Arc::new(Config {
+                versions,
+                default: None,
+                header: API_VERSION,
+                prefix: true,
+                accept: true,
+            }),
+        }
+    }
+
+    /// Sets the version used when the request does not ask for one.
+    pub fn default_version(self, version: u32) -> Self {
+        self.config(|config| config.default = Some(ApiVersion(version)))
+    }
+
+    /// Sets the header used to request a version, and set on responses.
+    pub fn header(self, header: HeaderName) -> Self {
+        self.config(|config| config.header = header)
+    }
+
+    /// Sets whether the version is read from a path prefix like `/v2`.
+    pub fn prefix(self, prefix: bool) -> Self {
+        self.config(|config| config.prefix = prefix)
+    }
+
+    /// Sets whether the version is read from the `Accept` header.
+    pub fn accept(self, accept: bool) -> Self {
+        self.config(|config| config.accept = accept)
+    }
+
+    fn config(mut self, f: impl FnOnce(&mut Config)) -> Self {
+        f(Arc::make_mut(&mut self.config));
+        self
+    }
+}
+
+impl Config {
+    /// Resolves the version of the request, and whether it came from the path.
+    fn resolve(&self, req: &Request) -> Result<(ApiVersion, bool), VersionRejection> {
+        let (version, from_path) = if let Some(version) = self.path_version(req.uri()) {
+            (version, true)
+        } else if let Some(value) = req.headers().get(&self.header) {
+            let value = value.to_str().unwrap_or_default();
+            let version = value
+                .parse()
+                .map_err(|_| VersionRejection::Invalid(value.to_string()))?;
+            (version, false)
+        } else if let Some(version) = req
+            .headers()
+            .get(ACCEPT)
+            .filter(|_| self.accept)
+            .and_then(|value| accept_version(value.to_str().ok()?))
+        {
+            (version?, false)
+        } else {
+            let latest = self.versions[self.versions.len() - 1];
+            (self.default.unwrap_or(latest), false)
+        };
+        if !self.versions.contains(&version) {
+            return Err(VersionRejection::Unsupported(version));
+        }
+        Ok((version, from_path))
+    }
+
+    fn path_version(&self, uri: &Uri) -> Option<ApiVersion> {
+        let segment = uri.path().strip_prefix('/')?.split('/').next()?;
+        segment
+            .strip_prefix('v')
+            .filter(|_| self.prefix)?
+            .parse()
+            .ok()
+    }
+}
+
+/// Reads the version from a `version` parameter or a vendor suffix like `.v2+json`.
+fn accept_version(accept: &str) -> Option<Result<ApiVersion, VersionRejection>> {
+    let invalid = |value: &str| VersionRejection::Invalid(value.to_string());
+    for media_type in accept.split(',') {
+        let mut params = media_type.split(';');
+        let essence = params.next().unwrap_or_default().trim();
+        for param in params {
+            if let Some((name, value)) = param.split_once('=') {
+                if name.trim().eq_ignore_ascii_case("version") {
+                    let value = value.trim().trim_matches('"');
+                    return Some(value.parse().map_err(|_| invalid(value)));
+                }
+            }
+        }
+        let subtype = essence.split_once('/').map(|(_, subtype)| subtype)?;
+        let subtype = subtype.split('+').next().unwrap_or_default();
+        if let Some(suffix) = subtype
+            .strip_prefix("vnd.")
+            .and_then(|vendor| vendor.rsplit('.').next())
+            .filter(|suffix| suffix.starts_with('v'))
+        {
+            if let Ok(version) = suffix.parse() {
+                return Some(Ok(version));
+            }
+        }
+    }
+    None
+}
+
+/// Removes the first segment of the path, keeping the query.
+fn strip_prefix(uri: &Uri) -> Option<Uri> {
+    let path = uri.path();
+    let rest = path[1..].find('/').map_or("/", |index| &path[index + 1..]);
+    let path_and_query = match uri.query() {
+        Some(query) => format!("{rest}?{query}"),
+        None => rest.to_string(),
+    };
+    let mut parts = uri.clone().into_parts();
+    parts.path_and_query = Some(PathAndQuery::try_from(path_and_query).ok()?);
+    Uri::from_parts(parts).ok()
+}
+
+impl<S> Layer<S> for VersionLayer {
+    type Service = VersionService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        VersionService {
+            inner,
+            config: self.config.clone(),
+        }
+    }
+}
+
+/// Service created by the `VersionLayer`.
+#[derive(Debug, Clone)]
+pub struct VersionService<S> {
+    inner: S,
+    config: Arc<Config>,
+}
+
+impl<S> Service<Request> for VersionService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, mut req: Request) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let config = self.config.clone();
+        Box::pin(async move {
+            let (version, from_path) = match config.resolve(&req) {
+                Ok(resolved) => resolved,
+                Err(rejection) => return Ok(rejection.into_response()),
+            };
+            if from_path {
+                if let Some(uri) = strip_prefix(req.uri()) {
+                    *req.uri_mut() = uri;
+                }
+            }
+            req.extensions_mut().insert(version);
+
+            let mut response = inner.call(req).await?;
+            let headers = response.headers_mut();
+            headers.insert(config.header.clone(), HeaderValue::from(version.0));
+            // Responses to unprefixed paths depend on the negotiated version
+            if !from_path {
+                headers.append(VARY, HeaderValue::from_name(config.header.clone()));
+                if config.accept {
+                    headers.append(VARY, HeaderValue::from_name(ACCEPT));
+                }
+            }
+            Ok(response)
+        })
+    }
+}
+
+/// Route layer hiding a route from versions before the one it was introduced in,
+/// responding with 404 Not Found as if the route did not exist.
+/// Requests without a version from the `VersionLayer` are not affected.
+/// # Example
+/// ```
+/// use lib::axum::versioning::Introduced;
+///
+/// let _router: axum::Router = lib::routes!(
+///     #[layer(Introduced::new(2))] get "/reports" => || async {}
+/// );
+/// ```
+#[derive(Debug, Clone, Copy)]
+pub struct Introduced {
+    version: ApiVersion,
+}
+
+impl Introduced {
+    pub fn new(version: u32) -> Self {
+        Self {
+            version: ApiVersion(version),
+        }
+    }
+}
+
+impl<S> Layer<S> for Introduced {
+    type Service = IntroducedService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        IntroducedService {
+            inner,
+            version: self.version,
+        }
+    }
+}
+
+/// Service created by the `Introduced` layer.
+#[derive(Debug, Clone)]
+pub struct IntroducedService<S> {
+    inner: S,
+    version: ApiVersion,
+}
+
+impl<S> Service<Request> for IntroducedService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let introduced = self.version;
+        Box::pin(async move {
+            match req.extensions().get::<ApiVersion>() {
+                Some(version) if *version < introduced => Ok(StatusCode::NOT_FOUND.into_response()),
+                _ => inner.call(req).await,
+            }
+        })
+    }
+}
+
+/// Route layer marking a route as deprecated from a version,
+/// adding the `Deprecation` header to its responses, and the `Sunset` and `Link` headers if set.
+/// Routes removed in a later version respond with 410 Gone in that version and after.
+/// Requests without a version from the `VersionLayer` are not affected.
+/// # Example
+/// ```
+/// use lib::axum::versioning::Deprecated;
+/// use std::time::{Duration, UNIX_EPOCH};
+///
+/// let deprecated = Deprecated::since(2)
+///     .sunset(UNIX_EPOCH + Duration::from_secs(1_800_000_000))
+///     .link("https://example.com/migration")
+///     .removed(3);
+/// let _router: axum::Router = lib::routes!(
+///     #[layer(deprecated)] get "/legacy" => || async {}
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct Deprecated {
+    since: ApiVersion,
+    date: Option<SystemTime>,
+    sunset: Option<SystemTime>,
+    link: Option<HeaderValue>,
+    removed: Option<ApiVersion>,
+}
+
+impl Deprecated {
+    pub fn since(version: u32) -> Self {
+        Self {
+            since: ApiVersion(version),
+            date: None,
+            sunset: None,
+            link: None,
+            removed: None,
+        }
+    }
+
+    /// Sets the date of the deprecation, sent in the `Deprecation` header instead of `true`.
+    pub fn date(mut self, date: SystemTime) -> Self {
+        self.date = Some(date);
+        self
+    }
+
+    /// Sets the date after which the route will stop responding, sent in the `Sunset` header.
+    pub fn sunset(mut self, sunset: SystemTime) -> Self {
+        self.sunset = Some(sunset);
+        self
+    }
+
+    /// Sets a link to documentation about the deprecation, sent in the `Link` header.
+    /// Links which are not valid header values are ignored.
+    pub fn link(mut self, url: &str) -> Self {
+        self.link = HeaderValue::try_from(format!("<{url}>; rel=\"deprecation\"")).ok();
+        self
+    }
+
+    /// Sets the version the route was removed in.
+    pub fn removed(mut self, version: u32) -> Self {
+        self.removed = Some(ApiVersion(version));
+        self
+    }
+
+    fn add_headers(&self, response: &mut Response) {
+        let headers = response.headers_mut();
+        let deprecation = match self
+            .date
+            .and_then(|date| date.duration_since(UNIX_EPOCH).ok())
+        {
+            Some(date) => HeaderValue::from_str(&format!("@{}", date.as_secs())),
+            None => Ok(HeaderValue::from_static("true")),
+        };
+        if let Ok(deprecation) = deprecation {
+            headers.insert(DEPRECATION, deprecation);
+        }
+        if let Some(sunset) = self.sunset {
+            if let Ok(value) = HeaderValue::from_str(&httpdate::fmt_http_date(sunset)) {
+                headers.insert(SUNSET, value);
+            }
+        }
+        if let Some(link) = &self.link {
+            headers.append(LINK, link.clone());
+        }
+    }
+}
+
+impl<S> Layer<S> for Deprecated {
+    type Service = DeprecatedService<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        DeprecatedService {
+            inner,
+            deprecated: Arc::new(self.clone()),
+        }
+    }
+}
+
+/// Service created by the `Deprecated` layer.
+#[derive(Debug, Clone)]
+pub struct DeprecatedService<S> {
+    inner: S,
+    deprecated: Arc<Deprecated>,
+}
+
+impl<S> Service<Request> for DeprecatedService<S>
+where
+    S: Service<Request, Response = Response> + Clone + Send + 'static,
+    S::Future: Send + 'static,
+{
+    type Response = Response;
+    type Error = S::Error;
+    type Future = BoxFuture<'static, Result<Response, S::Error>>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx)
+    }
+
+    fn call(&mut self, req: Request) -> Self::Future {
+        let clone = self.inner.clone();
+        let mut inner = std::mem::replace(&mut self.inner, clone);
+        let deprecated = self.deprecated.clone();
+        Box::pin(async move {
+            let Some(version) = req.extensions().get::<ApiVersion>().copied() else {
+                return inner.call(req).await;
+            };
+            if let Some(removed) = deprecated.removed.filter(|removed| version >= *removed) {
+                return Ok(VersionRejection::Removed(removed).into_response());
+            }
+            let mut response = inner.call(req).await?;
+            if version >= deprecated.since {
+                deprecated.add_headers(&mut response);
+            }
+            Ok(response)
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::routes;
+    use axum::{body::Body, Router};
+    use std::time::Duration;
+    use tower::ServiceExt;
+
+    async fn show(version: ApiVersion) -> String {
+        version.to_string()
+    }
+
+    fn app() -> VersionService<Router> {
+        let router: Router = routes!(
+            get "/users" => show,
+            #[layer(Introduced::new(2))] get "/posts" => show,
+            #[layer(Deprecated::since(2)
+                .sunset(UNIX_EPOCH + Duration::from_secs(1_800_000_000))
+                .link("/docs")
+                .removed(3))]
+            get "/legacy" => show,
+        );
+        VersionLayer::new([1, 2, 3])
+            .default_version(1)
+            .layer(router)
+    }
+
+    async fn send(request: axum::http::request::Builder) -> (StatusCode, Response) {
+        let response = app()
+            .oneshot(request.body(Body::empty()).unwrap())
+            .await
+            .unwrap();
+        (response.status(), response)
+    }
+
+    async fn body(response: Response) -> String {
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        String::from_utf8(bytes.to_vec()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!("2".parse(), Ok(ApiVersion(2)));
+        assert_eq!("v10".parse(), Ok(ApiVersion(10)));
+        assert_eq!("v".parse::<ApiVersion>(), Err(ParseApiVersionError));
+        assert_eq!("+1".parse::<ApiVersion>(), Err(ParseApiVersionError));
+        assert_eq!(ApiVersion(2).to_string(), "v2");
+    }
+
+    #[test]
+    fn test_accept_version() {
+        assert_eq!(
+            accept_version("text/html, application/json; version=2"),
+            Some(Ok(ApiVersion(2)))
+        );
+        assert_eq!(
+            accept_version("application/vnd.example.v3+json"),
+            Some(Ok(ApiVersion(3)))
+        );
+        assert_eq!(accept_version("application/json"), None);
+        assert!(matches!(
+            accept_version("application/json; version=x"),
+            Some(Err(VersionRejection::Invalid(_)))
+        ));
+    }
+
+    #[test]
+    fn test_strip_prefix() {
+        let uri: Uri = "/v2/users?page=1".parse().unwrap();
+        assert_eq!(strip_prefix(&uri).unwrap(), "/users?page=1");
+        let uri: Uri = "/v2".parse().unwrap();
+        assert_eq!(strip_prefix(&uri).unwrap(), "/");
+    }
+
+    #[tokio::test]
+    async fn test_path_version() {
+        let (status, response) = send(Request::get("/v2/users")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(response.headers()[API_VERSION], "2");
+        assert!(response.headers().get(VARY).is_none());
+        assert_eq!(body(response).await, "v2");
+    }
+
+    #[tokio::test]
+    async fn test_negotiated_version() {
+        let (_, response) = send(Request::get("/users")).await;
+        assert_eq!(body(response).await, "v1");
+        let (_, response) = send(Request::get("/users").header(API_VERSION, "v3")).await;
+        assert_eq!(response.headers()[VARY], "api-version");
+        assert_eq!(body(response).await, "v3");
+        let (_, response) =
+            send(Request::get("/users").header(ACCEPT, "application/vnd.app.v2+json")).await;
+        assert_eq!(body(response).await, "v2");
+    }
+
+    #[tokio::test]
+    async fn test_unsupported_version() {
+        let (status, _) = send(Request::get("/v4/users")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        let (status, _) = send(Request::get("/users").header(API_VERSION, "two")).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_introduced() {
+        assert_eq!(
+            send(Request::get("/v1/posts")).await.0,
+            StatusCode::NOT_FOUND
+        );
+        assert_eq!(send(Request::get("/v2/posts")).await.0, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_deprecated() {
+        let (_, response) = send(Request::get("/v1/legacy")).await;
+        assert!(response.headers().get(DEPRECATION).is_none());
+        let (_, response) = send(Request::get("/v2/legacy")).await;
+        assert_eq!(response.headers()[DEPRECATION], "true");
+        assert_eq!(response.headers()[SUNSET], "Fri, 15 Jan 2027 08:00:00 GMT");
+        assert_eq!(response.headers()[LINK], "</docs>; rel=\"deprecation\"");
+        assert_eq!(send(Request::get("/v3/legacy")).await.0, StatusCode::GONE);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_versioned_response() {
+        #[derive(serde::Serialize)]
+        struct User {
+            name: String,
+        }
+        impl SerializeObject for User {}
+        let response = ApiVersion(2).response(User {
+            name: "Ola".to_string(),
+        });
+        assert_eq!(response.version, "v2");
+    }
+}
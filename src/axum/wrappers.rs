@@ -1,17 +1,33 @@
 use axum::response::{IntoResponse, Response};
 use derive_more::{Constructor, From};
-use into_response_derive::IntoResponse;
+use into_response_derive::{IntoResponse, SerializeObject};
 use serde::Serialize;
 
 /// Wrapper for a vector of items.
-#[derive(Debug, Clone, PartialEq, Default, Serialize, From, Constructor)]
+#[derive(Debug, Clone, PartialEq, Default, Serialize, SerializeObject, From, Constructor)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
 pub struct Array<T: Serialize> {
     pub data: Vec<T>,
 }
 
 /// Wrapper for a count.
 #[derive(
-    Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, IntoResponse, From, Constructor,
+    Debug,
+    Clone,
+    Copy,
+    PartialEq,
+    Eq,
+    Default,
+    Serialize,
+    SerializeObject,
+    IntoResponse,
+    From,
+    Constructor,
+)]
+#[cfg_attr(
+    feature = "openapi",
+    derive(schemars::JsonSchema),
+    into_response(openapi)
 )]
 pub struct Count {
     pub count: usize,
@@ -22,3 +38,10 @@ impl<T: Serialize> IntoResponse for Array<T> {
         crate::from!(self).into_response()
     }
 }
+
+#[cfg(feature = "openapi")]
+impl<T: Serialize + schemars::JsonSchema> crate::axum::openapi::ResponseBody for Array<T> {
+    fn body_schema(generator: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        generator.subschema_for::<crate::serde::response::BaseResponse<Self>>()
+    }
+}
@@ -2,3 +2,10 @@ pub mod pool;
 
 /// Re-export diesel::result::Error as DieselError
 pub type DieselError = diesel::result::Error;
+
+/// Converts the time to seconds since the unix epoch, for storing in a `BIGINT` column.
+pub(crate) fn unix_timestamp(time: std::time::SystemTime) -> i64 {
+    time.duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as i64)
+        .unwrap_or_default()
+}
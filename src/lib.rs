@@ -3,11 +3,17 @@
 pub extern crate diesel_crud_derive;
 #[cfg(feature = "diesel")]
 pub extern crate diesel_crud_trait;
-#[cfg(all(feature = "derive", feature = "axum", feature = "serde"))]
+#[cfg(all(feature = "derive", feature = "serde"))]
 pub extern crate into_response_derive;
+#[cfg(all(feature = "derive", feature = "multipart"))]
+pub extern crate multipart_form_derive;
 #[cfg(feature = "read-files")]
 pub extern crate read_files;
+#[cfg(feature = "route-macros")]
+pub extern crate route_macros;
 extern crate self as lib;
+#[cfg(all(feature = "derive", feature = "validation"))]
+pub extern crate validate_derive;
 
 #[cfg(feature = "axum")]
 pub mod axum;
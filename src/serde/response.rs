@@ -1,27 +1,232 @@
-use serde::Serialize;
+#[cfg(feature = "time")]
+use chrono::{DateTime, SecondsFormat, Utc};
+#[cfg(feature = "derive")]
+pub use into_response_derive::SerializeObject;
+use {
+    serde::{Serialize, Serializer},
+    std::collections::{BTreeMap, HashMap},
+};
+
+/// A type serialized as an object, which can be flattened into a `BaseResponse`.
+/// Derived for structs with named fields with `#[derive(SerializeObject)]`.
+/// Other values can be wrapped in `Data`.
+/// # Example
+/// ```compile_fail
+/// let response = lib::from!(42);
+/// ```
+pub trait SerializeObject: Serialize {}
+
+impl<K: Serialize, V: Serialize, H> SerializeObject for HashMap<K, V, H> {}
+impl<K: Serialize, V: Serialize> SerializeObject for BTreeMap<K, V> {}
+impl<T: SerializeObject + ?Sized> SerializeObject for &T {}
+impl<T: SerializeObject + ?Sized> SerializeObject for Box<T> {}
+
+/// Adapter for a body which is not an object, serialized under a `data` key.
+/// # Example
+/// ```
+/// use lib::{from, serde::response::Data};
+///
+/// let response = from!(Data(42));
+/// assert_eq!(response.body, Data(42));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct Data<T>(pub T);
 
 #[derive(Serialize)]
-pub struct BaseResponse<T: Serialize> {
+#[cfg_attr(
+    feature = "openapi",
+    derive(schemars::JsonSchema),
+    schemars(rename = "Data_for_{T}")
+)]
+struct DataObject<T> {
+    data: T,
+}
+
+impl<T: Serialize> Serialize for Data<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        DataObject { data: &self.0 }.serialize(serializer)
+    }
+}
+
+#[cfg(feature = "openapi")]
+impl<T: schemars::JsonSchema> schemars::JsonSchema for Data<T> {
+    fn schema_name() -> String {
+        DataObject::<T>::schema_name()
+    }
+
+    fn json_schema(generator: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        DataObject::<T>::json_schema(generator)
+    }
+}
+
+impl<T: Serialize> SerializeObject for Data<T> {}
+
+/// Pagination totals of a `BaseResponse`, serialized under the `meta` key.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct Meta {
+    /// The current page, starting at 1.
+    pub page: u64,
+    pub per_page: u64,
+    /// The total number of items in all pages.
+    pub total: u64,
+    pub total_pages: u64,
+}
+
+impl Meta {
+    /// Creates the totals for a page with `per_page` items, out of `total` items.
+    pub fn new(page: u64, per_page: u64, total: u64) -> Self {
+        Self {
+            page,
+            per_page,
+            total,
+            total_pages: total
+                .checked_div(per_page)
+                .map_or(0, |pages| pages + u64::from(total % per_page != 0)),
+        }
+    }
+}
+
+/// Links related to a `BaseResponse`, serialized under the `links` key.
+#[derive(Debug, Clone, PartialEq, Eq, Default, Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct Links {
+    /// The URL of the response itself.
+    #[serde(rename = "self", skip_serializing_if = "Option::is_none")]
+    pub self_link: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub prev: Option<String>,
+}
+
+impl Links {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn self_link(mut self, url: impl Into<String>) -> Self {
+        self.self_link = Some(url.into());
+        self
+    }
+
+    pub fn next(mut self, url: impl Into<String>) -> Self {
+        self.next = Some(url.into());
+        self
+    }
+
+    pub fn prev(mut self, url: impl Into<String>) -> Self {
+        self.prev = Some(url.into());
+        self
+    }
+}
+
+/// The envelope of a response body, with the version of the API and optional metadata.
+/// The metadata is omitted unless it is set on the response,
+/// or added to all responses by the `ResponseMetaLayer` with the `axum` feature.
+#[derive(Serialize)]
+#[cfg_attr(feature = "openapi", derive(schemars::JsonSchema))]
+pub struct BaseResponse<T: SerializeObject> {
     pub version: String,
+    /// When the response was created, formatted as RFC 3339.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
     #[serde(flatten)]
-    pub body: T, // T must be a struct (or enum?) TODO from! macro that validates T on compile time
+    pub body: T,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub meta: Option<Meta>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub links: Option<Links>,
+    /// The status of the response, 200 OK by default.
+    #[cfg(feature = "axum")]
+    #[serde(skip)]
+    pub status: axum::http::StatusCode,
+    /// Headers added to the response.
+    #[cfg(feature = "axum")]
+    #[serde(skip)]
+    pub headers: axum::http::HeaderMap,
 }
 
-impl<T: Serialize> BaseResponse<T> {
+impl<T: SerializeObject> BaseResponse<T> {
     pub fn new(version: impl Into<String>, body: T) -> Self {
         Self {
             version: version.into(),
+            timestamp: None,
+            request_id: None,
             body,
+            meta: None,
+            links: None,
+            #[cfg(feature = "axum")]
+            status: axum::http::StatusCode::OK,
+            #[cfg(feature = "axum")]
+            headers: axum::http::HeaderMap::new(),
         }
     }
+
+    /// Sets the time the response was created, like `Utc::now()` or `SystemTime::now()`.
+    #[cfg(feature = "time")]
+    pub fn with_timestamp(mut self, time: impl Into<DateTime<Utc>>) -> Self {
+        self.timestamp = Some(rfc3339(time.into()));
+        self
+    }
+
+    pub fn with_request_id(mut self, request_id: impl Into<String>) -> Self {
+        self.request_id = Some(request_id.into());
+        self
+    }
+
+    /// Sets the pagination totals.
+    pub fn with_meta(mut self, meta: Meta) -> Self {
+        self.meta = Some(meta);
+        self
+    }
+
+    pub fn with_links(mut self, links: Links) -> Self {
+        self.links = Some(links);
+        self
+    }
+}
+
+/// Formats the time with millisecond precision, like `2024-01-31T12:00:00.000Z`.
+#[cfg(feature = "time")]
+pub(crate) fn rfc3339(time: DateTime<Utc>) -> String {
+    time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+/// Used by `from!` so the expansion doesn't depend on `axum` being a dependency of the caller.
+/// The status is checked at compile time by the macro.
+#[cfg(feature = "axum")]
+#[doc(hidden)]
+pub fn __status_from_u16(status: u16) -> axum::http::StatusCode {
+    axum::http::StatusCode::from_u16(status).expect("Invalid status code")
 }
 
 // TODO version should reference the version in caller's Cargo.toml
+/// Wraps the body in a `BaseResponse`, optionally with a status code like `from!(201, body)`
+/// or `from!(StatusCode::CREATED, body)`.
+/// Status code literals outside of 100 to 599 fail to compile.
+/// # Example
+/// ```compile_fail
+/// let response = lib::from!(999, lib::serde::response::Data(1));
+/// ```
 #[macro_export]
 macro_rules! from {
     ($body:expr) => {
         $crate::serde::response::BaseResponse::new(env!("CARGO_PKG_VERSION"), $body)
     };
+    ($status:literal, $body:expr) => {{
+        const STATUS: u16 = $status;
+        const _: () = assert!(
+            100 <= STATUS && STATUS <= 599,
+            "Status codes must be between 100 and 599"
+        );
+        $crate::from!($body).with_status($crate::serde::response::__status_from_u16(STATUS))
+    }};
+    ($status:expr, $body:expr) => {
+        $crate::from!($body).with_status($status)
+    };
 }
 
 #[cfg(test)]
@@ -33,6 +238,8 @@ mod tests {
         message: String,
     }
 
+    impl SerializeObject for Response {}
+
     #[test]
     fn test_base_response_new() {
         let response = BaseResponse::new(
@@ -44,12 +251,33 @@ mod tests {
         assert_eq!(response.body.message, "Hi".to_string());
     }
 
+    #[test]
+    fn test_meta() {
+        assert_eq!(Meta::new(1, 10, 25).total_pages, 3);
+        assert_eq!(Meta::new(1, 10, 30).total_pages, 3);
+        assert_eq!(Meta::new(1, 10, 0).total_pages, 0);
+        assert_eq!(Meta::new(1, 0, 5).total_pages, 0);
+    }
+
+    #[cfg(feature = "time")]
+    #[test]
+    fn test_rfc3339() {
+        use std::time::{Duration, UNIX_EPOCH};
+        assert_eq!(rfc3339(UNIX_EPOCH.into()), "1970-01-01T00:00:00.000Z");
+        let time = UNIX_EPOCH + Duration::from_millis(951_827_696_789);
+        assert_eq!(rfc3339(time.into()), "2000-02-29T12:34:56.789Z");
+        let response = BaseResponse::new("", Data(1)).with_timestamp(UNIX_EPOCH);
+        assert_eq!(
+            response.timestamp.as_deref(),
+            Some("1970-01-01T00:00:00.000Z")
+        );
+    }
+
     #[test]
     fn test_from_macro() {
         let response = from!(Response {
             message: "Hi".to_string(),
         });
-        from!(1); // Should not be allowed
         assert_eq!(response.version, env!("CARGO_PKG_VERSION"));
         assert_eq!(response.body.message, "Hi".to_string());
     }